@@ -1,15 +1,26 @@
 extern crate core;
 
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use data_encoding::BASE32;
 use dialoguer::PasswordInput;
 use read_input::{InputBuild, InputConstraints};
 use read_input::prelude::input;
-use vault::client::client_config::ClientConfig;
-use vault::client::http_connection::HttpConnection;
+use vault::client::caching_connection::{AsyncCachingConnection, CachingHttpConnection};
+use vault::client::client_config::{CLIENT_FILES_LOCATION, ClientConfig};
+use vault::client::document_path::{entered_document_id, PATH_SEPARATOR, resolve_path};
+use vault::client::http_connection::{HttpConnection, ROOT_CERTIFICATE_FILE_NAME};
 use vault::client::organization_creation::{empirically_choose_argon_config, OrganizationBuilder};
 use vault::client::session_controller::Controller;
-use vault::data::Document;
+use vault::client::share_link::ShareLink;
+use vault::client::sync::sync_directory;
+use vault::data::{Document, DocumentEvent, Permission};
 use vault::error::VaultError;
-use vault::error::VaultError::InputError;
+use vault::error::VaultError::{AmbiguousDocumentName, IncompatibleServerVersion, InputError, ServerUnreachable, SessionExpired, VersionConflict};
+use vault::http_api::PROTOCOL_VERSION;
 
 
 fn main() {
@@ -20,18 +31,34 @@ Please choose an option:
 
 1. Create a new organization
 2. Log in
+3. Open a share link
 ")
-        .inside([1, 2])
+        .inside([1, 2, 3])
         .get();
 
-    if choice == 1 {
-        if let Err(error) = create_new_organization() {
-            println!("{error:?}");
-        }
-    } else {
-        if let Err(error) = log_in() {
-            println!("{error:?}");
-        }
+    let result = match choice {
+        1 => create_new_organization(),
+        2 => log_in(),
+        _ => open_share_link(),
+    };
+    if let Err(error) = result {
+        print_error(&error);
+    }
+}
+
+fn print_error(error: &VaultError) {
+    match error {
+        IncompatibleServerVersion(server_versions) =>
+            println!("The server does not support this client (client protocol version: {PROTOCOL_VERSION}, server protocol versions: {server_versions:?}). Please update the client or the server."),
+        AmbiguousDocumentName =>
+            println!("Several documents have this name. Please designate the document by its ID, as shown in the document list."),
+        ServerUnreachable =>
+            println!("The server could not be reached. Only the documents downloaded recently can be read, if the offline cache is enabled in the client config."),
+        VersionConflict =>
+            println!("The document was modified by someone else since you downloaded it. Please download it again before updating it."),
+        SessionExpired =>
+            println!("The session expired, as it was not used for 5 minutes, or it was revoked. Please restart the client to unlock the vault again."),
+        _ => println!("{error:?}"),
     }
 }
 
@@ -75,7 +102,7 @@ fn create_new_organization() -> Result<(), VaultError> {
         }
     }
 
-    let mut server = HttpConnection::new(ClientConfig::get().server_port);
+    let mut server = HttpConnection::new(ClientConfig::get().server_port)?;
 
     println!("Please wait...");
     organization_builder.create_organization(&mut server)?;
@@ -103,39 +130,72 @@ fn log_in() -> Result<(), VaultError> {
     let password2 = PasswordInput::new().with_prompt("password")
         .interact().map_err(|_| InputError)?;
 
-    let mut server = HttpConnection::new(ClientConfig::get().server_port);
+    let client_config = ClientConfig::get();
+    let cache_validity = client_config.offline_cache_validity_hours.map(|hours| Duration::from_secs(hours * 3600));
+    let mut server = CachingHttpConnection::new(client_config.server_port, cache_validity)?;
 
     let mut controller = Controller::unlock_vault_for_organization(&mut server, &organization_name, &username1, &password1, &username2, &password2)?;
 
     println!("You have unlocked the vault !");
 
+    session_menu(&mut controller)
+}
+
+/// Menu of an organization session, or of a group session opened from it
+fn session_menu(controller: &mut Controller<AsyncCachingConnection>) -> Result<(), VaultError> {
+    print_document_events(controller)?;
+
+    // Document names are entered relatively to the current folder
+    let mut current_folder = String::new();
+
     loop {
         let choice: u8 = input()
-            .msg("
+            .msg(format!("
 
 
+Current folder: /{current_folder}
 Please choose an option:
 1. Revoke user
 2. Upload new document
-3. List documents
+3. List current folder
 4. Download document
 5. Update document
 6. Share document
-7. Delete document
-8. Exit
-")
-            .inside([1, 2, 3, 4, 5, 6, 7])
+7. Remove the access of an organization to a document
+8. List the owners of a document
+9. Delete document
+10. Search documents
+11. Change folder
+12. Move document or folder
+13. Delete folder
+14. Pending shares
+15. Groups
+16. Create share link
+17. Synchronize a local directory with the current folder
+18. Exit
+"))
+            .inside(1..=18)
             .get();
 
         match choice {
-            1 => revoke_user(&mut controller)?,
-            2 => upload(&mut controller)?,
-            3 => list(&mut controller)?,
-            4 => download(&mut controller)?,
-            5 => update(&mut controller)?,
-            6 => share(&mut controller)?,
-            7 => delete(&mut controller)?,
-            8 => break,
+            1 => revoke_user(controller)?,
+            2 => upload(controller, &current_folder)?,
+            3 => list(controller, &current_folder)?,
+            4 => download(controller, &current_folder)?,
+            5 => update(controller, &current_folder)?,
+            6 => share(controller, &current_folder)?,
+            7 => remove_owner(controller, &current_folder)?,
+            8 => owners(controller, &current_folder)?,
+            9 => delete(controller, &current_folder)?,
+            10 => search(controller)?,
+            11 => current_folder = change_folder(&current_folder),
+            12 => move_document_or_folder(controller, &current_folder)?,
+            13 => delete_folder(controller, &current_folder)?,
+            14 => pending_shares(controller)?,
+            15 => groups(controller)?,
+            16 => create_share_link(controller, &current_folder)?,
+            17 => sync(controller, &current_folder)?,
+            18 => break,
             _ => panic!()
         }
     }
@@ -143,25 +203,45 @@ Please choose an option:
     Ok(())
 }
 
-fn revoke_user(controller: &mut Controller<HttpConnection>) -> Result<(), VaultError> {
+/// Prints the changes of the documents made by the other sessions, while the session is open
+fn print_document_events(controller: &mut Controller<AsyncCachingConnection>) -> Result<(), VaultError> {
+    let mut events = controller.subscribe_events()?;
+    thread::spawn(move || {
+        while let Some(event) = events.blocking_recv() {
+            match event {
+                DocumentEvent::Created { document_id } => println!("\n[Document #{} was uploaded]", BASE32.encode(&document_id)),
+                DocumentEvent::Updated { document_id } => println!("\n[Document #{} was updated]", BASE32.encode(&document_id)),
+                DocumentEvent::Deleted { document_id } => println!("\n[Document #{} is no longer accessible]", BASE32.encode(&document_id)),
+                DocumentEvent::Shared { from_organization_name, .. } => println!("\n[{from_organization_name} shared a document, see the pending shares]"),
+                DocumentEvent::EventsMissed => println!("\n[Some changes were missed, list the documents again]"),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn revoke_user(controller: &mut Controller<AsyncCachingConnection>) -> Result<(), VaultError> {
     let username: String = input().msg("user: ").get();
     controller.revoke_user(&username)?;
     Ok(())
 }
 
-fn upload(controller: &mut Controller<HttpConnection>) -> Result<(), VaultError> {
-    let name = input().msg("document name: ").get();
+fn upload(controller: &mut Controller<AsyncCachingConnection>, current_folder: &str) -> Result<(), VaultError> {
+    let name: String = input().msg("document name: ").get();
     let content = input().msg("document content: ").get();
 
 
-    controller.upload(&Document { name, content })?;
+    controller.upload(&Document { name: resolve_path(current_folder, &name), content })?;
     Ok(())
 }
 
-fn download(controller: &mut Controller<HttpConnection>) -> Result<(), VaultError> {
-    let name: String = input().msg("document name: ").get();
+fn download(controller: &mut Controller<AsyncCachingConnection>, current_folder: &str) -> Result<(), VaultError> {
+    let name: String = input().msg("document name or #ID: ").get();
 
-    let document = controller.download(&name)?;
+    let document = match entered_document_id(&name) {
+        Some(document_id) => controller.download_by_id(&document_id)?,
+        None => controller.download(&resolve_path(current_folder, &name))?,
+    };
 
     println!("name: {}", document.name);
     println!("content: {}", document.content);
@@ -169,40 +249,255 @@ fn download(controller: &mut Controller<HttpConnection>) -> Result<(), VaultErro
     Ok(())
 }
 
-fn list(controller: &mut Controller<HttpConnection>) -> Result<(), VaultError> {
-    for name in controller.list_document_names()?{
-        println!("{name}");
+fn list(controller: &mut Controller<AsyncCachingConnection>, current_folder: &str) -> Result<(), VaultError> {
+    let folder_content = controller.list_folder(current_folder)?;
+    for folder in folder_content.folders {
+        println!("{folder}{PATH_SEPARATOR}");
+    }
+    for document in folder_content.documents {
+        let age = document.modified_at.elapsed().unwrap_or_default();
+        let file_name = document.name.rsplit(PATH_SEPARATOR).next().unwrap_or_default();
+        println!("{} ({} bytes, modified {}, #{})", file_name, document.size, format_age(age), BASE32.encode(&document.id));
     }
 
     Ok(())
 }
 
-fn update(controller: &mut Controller<HttpConnection>) -> Result<(), VaultError> {
-    let old_name:String = input().msg("old document name: ").get();
+/// Returns the folder entered by the user, resolved relatively to the current folder
+fn change_folder(current_folder: &str) -> String {
+    let folder: String = input().msg("folder (.. for the parent folder, / for the root folder): ").get();
+    let new_folder = resolve_path(current_folder, &folder);
+    println!("Current folder: /{new_folder}");
+    new_folder
+}
+
+fn move_document_or_folder(controller: &mut Controller<AsyncCachingConnection>, current_folder: &str) -> Result<(), VaultError> {
+    let path: String = input().msg("document or folder to move: ").get();
+    let new_path: String = input().msg("new path: ").get();
+    let (path, new_path) = (resolve_path(current_folder, &path), resolve_path(current_folder, &new_path));
+
+    if controller.list_document_names()?.contains(&path) {
+        controller.move_document(&path, &new_path)
+    } else {
+        controller.move_folder(&path, &new_path)
+    }
+}
 
-    let name = input().msg("document name: ").get();
+fn delete_folder(controller: &mut Controller<AsyncCachingConnection>, current_folder: &str) -> Result<(), VaultError> {
+    let folder: String = input().msg("folder to delete, with all its documents: ").get();
+
+    controller.delete_folder(&resolve_path(current_folder, &folder))
+}
+
+fn search(controller: &mut Controller<AsyncCachingConnection>) -> Result<(), VaultError> {
+    let query: String = input().msg("search: ").get();
+
+    for document_name in controller.search(&query)? {
+        println!("{document_name}");
+    }
+
+    Ok(())
+}
+
+/// Formats a duration with its largest unit, e.g. "3 hours ago"
+fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} minutes ago", seconds / 60),
+        3600..=86399 => format!("{} hours ago", seconds / 3600),
+        _ => format!("{} days ago", seconds / 86400),
+    }
+}
+
+/// Formats the remaining time of an access, e.g. "expires in 3 days"
+fn format_expiry(expires_at: SystemTime) -> String {
+    let remaining_hours = expires_at.duration_since(SystemTime::now()).unwrap_or_default().as_secs() / 3600;
+    match remaining_hours {
+        0 => "expires within an hour".to_string(),
+        1..=47 => format!("expires in {remaining_hours} hours"),
+        _ => format!("expires in {} days", remaining_hours / 24),
+    }
+}
+
+fn update(controller: &mut Controller<AsyncCachingConnection>, current_folder: &str) -> Result<(), VaultError> {
+    let old_name:String = input().msg("old document name or #ID: ").get();
+
+    let name: String = input().msg("document name: ").get();
     let content = input().msg("document content: ").get();
 
-    controller.update(&old_name, &Document { name, content })?;
+    let new_document = Document { name: resolve_path(current_folder, &name), content };
+    match entered_document_id(&old_name) {
+        Some(document_id) => controller.update_by_id(&document_id, &new_document)?,
+        None => controller.update(&resolve_path(current_folder, &old_name), &new_document)?,
+    }
+
+    Ok(())
+}
+
+fn share(controller: &mut Controller<AsyncCachingConnection>, current_folder: &str) -> Result<(), VaultError> {
+
+    let document_name: String = input().msg("document name or #ID: ").get();
+    let other_organization_name:String = input().msg("other organization name: ").get();
+    let permission = match input::<u8>().msg("1. Reader\n2. Editor\n3. Owner\npermission: ").inside([1, 2, 3]).get() {
+        1 => Permission::Reader,
+        2 => Permission::Editor,
+        _ => Permission::Owner,
+    };
+    let access_days: u64 = input().msg("access duration in days (0 for an access that does not expire): ").get();
+    let expires_at = (access_days > 0).then(|| SystemTime::now() + Duration::from_secs(access_days * 86400));
+
+    match entered_document_id(&document_name) {
+        Some(document_id) => controller.share_by_id(&document_id, &other_organization_name, permission, expires_at)?,
+        None => controller.share(&resolve_path(current_folder, &document_name), &other_organization_name, permission, expires_at)?,
+    }
+
+    Ok(())
+}
 
+fn groups(controller: &mut Controller<AsyncCachingConnection>) -> Result<(), VaultError> {
+    let groups = controller.list_groups()?;
+    for group in &groups {
+        println!("{} ({})", group.name, group.members.join(", "));
+    }
+
+    let choice: u8 = input()
+        .msg("1. Open group\n2. Create group\n3. Add group member\n4. Remove group member\n5. Go back\n")
+        .inside(1..=5)
+        .get();
+    if choice == 5 {
+        return Ok(());
+    }
+    let group_name: String = input().msg("Group name: ").get();
+
+    match choice {
+        1 => {
+            let mut group_controller = controller.open_group(&group_name)?;
+            println!("You have opened the group {group_name} !");
+            session_menu(&mut group_controller)
+        }
+        2 => {
+            let members: String = input().msg("Members, separated by spaces: ").get();
+            controller.create_group(&group_name, &members.split_whitespace().collect::<Vec<_>>())
+        }
+        3 => {
+            let user_name: String = input().msg("User name: ").get();
+            controller.add_group_member(&group_name, &user_name)
+        }
+        _ => {
+            let user_name: String = input().msg("User name: ").get();
+            controller.remove_group_member(&group_name, &user_name)
+        }
+    }
+}
+
+fn sync(controller: &mut Controller<AsyncCachingConnection>, current_folder: &str) -> Result<(), VaultError> {
+    let local_directory: String = input().msg("local directory: ").get();
+    let dry_run = input::<u8>().msg("1. Synchronize\n2. Only show the changes (dry run)\n").inside([1, 2]).get() == 2;
+
+    let report = sync_directory(controller, Path::new(&local_directory), current_folder, dry_run)?;
+    println!("{report}");
+    Ok(())
+}
+
+fn create_share_link(controller: &mut Controller<AsyncCachingConnection>, current_folder: &str) -> Result<(), VaultError> {
+    let document_name: String = input().msg("document name or #ID: ").get();
+    let hours: u64 = input().msg("link valid for (hours): ").inside(1..=720).get();
+    let expires_at = SystemTime::now() + Duration::from_secs(hours * 3600);
+
+    let share_link = match entered_document_id(&document_name) {
+        Some(document_id) => controller.create_share_link_by_id(&document_id, expires_at)?,
+        None => controller.create_share_link(&resolve_path(current_folder, &document_name), expires_at)?,
+    };
+    let client_config = ClientConfig::get();
+    println!("Share link, which can be opened once: {}", share_link.to_url(&client_config.server_hostname, client_config.server_port));
     Ok(())
 }
 
-fn share(controller: &mut Controller<HttpConnection>) -> Result<(), VaultError> {
+/// Downloads the document of a share link, without logging in
+fn open_share_link() -> Result<(), VaultError> {
+    let url: String = input().msg("Share link: ").get();
+    let (server_hostname, server_port, share_link) = ShareLink::from_url(url.trim())?;
+
+    // The recipient may have no client files, in which case the server is authenticated with the built-in root certificates
+    let root_certificate = fs::read(Path::new(CLIENT_FILES_LOCATION).join(ROOT_CERTIFICATE_FILE_NAME)).ok();
+    let mut server = HttpConnection::with_server_address(&server_hostname, server_port, root_certificate.as_deref())?;
+    let document = share_link.open_blocking(&mut server)?;
+    println!("name: {}", document.name);
+    println!("content: {}", document.content);
 
-    let document_name: String = input().msg("document name: ").get();
+    Ok(())
+}
+
+fn pending_shares(controller: &mut Controller<AsyncCachingConnection>) -> Result<(), VaultError> {
+    let pending_shares = controller.pending_shares()?;
+    if pending_shares.is_empty() {
+        println!("No pending share.");
+        return Ok(());
+    }
+
+    for (index, pending_share) in pending_shares.iter().enumerate() {
+        let age = pending_share.created_at.elapsed().unwrap_or_default();
+        println!("{}. {} shared by {} as {:?} ({})", index + 1, pending_share.document_name, pending_share.from_organization_name,
+                 pending_share.permission, format_age(age));
+    }
+
+    let choice: usize = input()
+        .msg("share to answer (0 to go back): ")
+        .inside(0..=pending_shares.len())
+        .get();
+    if choice == 0 {
+        return Ok(());
+    }
+    let document_id = &pending_shares[choice - 1].document_id;
+
+    let answer: u8 = input().msg("1. Accept\n2. Decline\n").inside([1, 2]).get();
+    if answer == 1 {
+        controller.accept_share(document_id)
+    } else {
+        controller.decline_share(document_id)
+    }
+}
+
+fn remove_owner(controller: &mut Controller<AsyncCachingConnection>, current_folder: &str) -> Result<(), VaultError> {
+
+    let document_name: String = input().msg("document name or #ID: ").get();
     let other_organization_name:String = input().msg("other organization name: ").get();
 
-    controller.share(&document_name, &other_organization_name)?;
+    match entered_document_id(&document_name) {
+        Some(document_id) => controller.remove_owner_by_id(&document_id, &other_organization_name)?,
+        None => controller.remove_owner(&resolve_path(current_folder, &document_name), &other_organization_name)?,
+    }
+
+    Ok(())
+}
+
+fn owners(controller: &mut Controller<AsyncCachingConnection>, current_folder: &str) -> Result<(), VaultError> {
+
+    let document_name: String = input().msg("document name or #ID: ").get();
+
+    let owners = match entered_document_id(&document_name) {
+        Some(document_id) => controller.owners_by_id(&document_id)?,
+        None => controller.owners(&resolve_path(current_folder, &document_name))?,
+    };
+    for owner in owners {
+        match owner.expires_at {
+            Some(expires_at) => println!("{} ({:?}, {})", owner.organization_name, owner.permission, format_expiry(expires_at)),
+            None => println!("{} ({:?})", owner.organization_name, owner.permission),
+        }
+    }
 
     Ok(())
 }
 
-fn delete(controller: &mut Controller<HttpConnection>) -> Result<(), VaultError> {
+fn delete(controller: &mut Controller<AsyncCachingConnection>, current_folder: &str) -> Result<(), VaultError> {
 
-    let document_name: String = input().msg("document name: ").get();
+    let document_name: String = input().msg("document name or #ID: ").get();
 
-    controller.delete(&document_name)?;
+    match entered_document_id(&document_name) {
+        Some(document_id) => controller.delete_by_id(&document_id)?,
+        None => controller.delete(&resolve_path(current_folder, &document_name))?,
+    }
 
     Ok(())
 }
\ No newline at end of file
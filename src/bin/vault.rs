@@ -0,0 +1,393 @@
+//! Non-interactive client, for scripts
+//!
+//! Each invocation unlocks the vault, runs one subcommand and revokes the session token, unless an agent started with the
+//! `agent` subcommand keeps the vault unlocked: the subcommands are then sent to the agent, without passwords.
+//! The organization and the user names are passed as options or environment variables. The passwords are read from the
+//! `VAULT_PASSWORD1` and `VAULT_PASSWORD2` environment variables, or one per line from a file descriptor or the standard input.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use data_encoding::BASE32;
+use serde::Serialize;
+use vault::client::agent;
+use vault::client::agent::{AgentRequest, AgentResponse};
+use vault::client::caching_connection::{AsyncCachingConnection, CachingHttpConnection};
+use vault::client::client_config::ClientConfig;
+use vault::client::document_path::{entered_document_id, PATH_SEPARATOR};
+use vault::client::session_controller::Controller;
+use vault::client::sync::SyncReport;
+use vault::data::{Document, DocumentInfo, Permission};
+use vault::error::VaultError;
+use vault::error::VaultError::*;
+
+const EXIT_FAILURE: u8 = 1;
+/// Also used by clap for invalid arguments
+const EXIT_USAGE: u8 = 2;
+const EXIT_NOT_FOUND: u8 = 3;
+const EXIT_CONFLICT: u8 = 4;
+const EXIT_UNREACHABLE: u8 = 5;
+const EXIT_AUTHENTICATION: u8 = 6;
+
+#[derive(Parser)]
+#[command(name = "vault", about = "Non-interactive client of the vault, for scripts", after_help = "\
+Passwords are read from the VAULT_PASSWORD1 and VAULT_PASSWORD2 environment variables, unless --password-fd or --password-stdin is used.
+They are not needed while an agent is running.
+
+Exit codes:
+  0  success
+  1  failure, e.g. the server refused the request
+  2  invalid arguments
+  3  document not found, or several documents have this name
+  4  the document was changed by someone else, or the synchronization has conflicts
+  5  the server could not be reached
+  6  wrong password, or the session of the agent expired")]
+struct Cli {
+    /// Prints the results and the errors as JSON
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Needed to unlock the vault, unless an agent is running
+    #[arg(long, env = "VAULT_ORGANIZATION")]
+    organization: Option<String>,
+
+    #[arg(long, env = "VAULT_USER1")]
+    user1: Option<String>,
+
+    #[arg(long, env = "VAULT_USER2")]
+    user2: Option<String>,
+
+    /// Reads the two passwords from this file descriptor, one per line
+    #[arg(long, conflicts_with = "password_stdin")]
+    password_fd: Option<u32>,
+
+    /// Reads the two passwords from the standard input, one per line
+    #[arg(long)]
+    password_stdin: bool,
+
+    /// Socket of the agent, in the client files by default
+    #[arg(long, env = "VAULT_AGENT_SOCKET")]
+    socket: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Uploads a file as a new document
+    Upload {
+        file: PathBuf,
+        /// Name of the document, the file name by default
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Downloads a document, designated by its name or by #ID
+    Get {
+        name: String,
+        /// Writes the content to this file instead of the standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Lists a folder, the root folder by default
+    Ls {
+        #[arg(default_value = "")]
+        folder: String,
+    },
+    /// Invites an other organization to access a document
+    Share {
+        name: String,
+        organization: String,
+        #[arg(long, value_enum, default_value_t = PermissionArg::Reader)]
+        permission: PermissionArg,
+        /// Duration of the access in days, unlimited by default
+        #[arg(long)]
+        days: Option<u64>,
+    },
+    /// Deletes a document
+    Rm {
+        name: String,
+    },
+    /// Synchronizes a local directory with a folder of the vault
+    Sync {
+        directory: PathBuf,
+        #[arg(long, default_value = "")]
+        folder: String,
+        /// Only reports the changes
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Unlocks the vault and keeps it unlocked for the next commands, until `lock` or the idle timeout
+    Agent {
+        /// Locks the vault after this many minutes without commands. The server also ends a session after 5 minutes
+        /// without requests, in which case the agent stops at the next command.
+        #[arg(long, default_value_t = 5)]
+        idle_timeout: u64,
+    },
+    /// Locks the vault kept unlocked by the agent, and stops the agent
+    Lock,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PermissionArg {
+    Reader,
+    Editor,
+    Owner,
+}
+
+impl From<PermissionArg> for Permission {
+    fn from(permission: PermissionArg) -> Self {
+        match permission {
+            PermissionArg::Reader => Permission::Reader,
+            PermissionArg::Editor => Permission::Editor,
+            PermissionArg::Owner => Permission::Owner,
+        }
+    }
+}
+
+/// Output of a subcommand, printed as text or as JSON
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Output {
+    None,
+    Document { name: String, content: String },
+    Folder { folders: Vec<String>, documents: Vec<ListedDocument> },
+    Sync(SyncReport),
+}
+
+#[derive(Serialize)]
+struct ListedDocument {
+    id: String,
+    name: String,
+    size: u64,
+    /// Seconds since the Unix epoch
+    modified_at: u64,
+    version: u64,
+}
+
+impl From<DocumentInfo> for ListedDocument {
+    fn from(document: DocumentInfo) -> Self {
+        ListedDocument {
+            id: BASE32.encode(&document.id),
+            name: document.name,
+            size: document.size,
+            modified_at: document.modified_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            version: document.version,
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(&cli) {
+        Ok(output) => {
+            let exit_code = match &output {
+                Output::Sync(report) if !report.errors.is_empty() => EXIT_FAILURE,
+                Output::Sync(report) if !report.conflicts.is_empty() => EXIT_CONFLICT,
+                _ => 0,
+            };
+            print_output(&output, cli.json);
+            ExitCode::from(exit_code)
+        }
+        Err(error) => {
+            if cli.json {
+                eprintln!("{}", serde_json::json!({ "error": error }));
+            } else {
+                eprintln!("Error: {error:?}");
+            }
+            ExitCode::from(exit_code(&error))
+        }
+    }
+}
+
+fn exit_code(error: &VaultError) -> u8 {
+    match error {
+        DocumentNotFound | AmbiguousDocumentName => EXIT_NOT_FOUND,
+        VersionConflict => EXIT_CONFLICT,
+        ServerUnreachable | IncompatibleServerVersion(_) => EXIT_UNREACHABLE,
+        CryptographyError | SessionExpired => EXIT_AUTHENTICATION,
+        InputError | ValidationError => EXIT_USAGE,
+        _ => EXIT_FAILURE,
+    }
+}
+
+fn print_output(output: &Output, json: bool) {
+    if json {
+        if !matches!(output, Output::None) {
+            println!("{}", serde_json::to_string(output).unwrap_or_default());
+        }
+        return;
+    }
+
+    match output {
+        Output::None => {}
+        Output::Document { content, .. } => {
+            io::stdout().write_all(content.as_bytes()).ok();
+        }
+        Output::Folder { folders, documents } => {
+            for folder in folders {
+                println!("{folder}{PATH_SEPARATOR}");
+            }
+            for document in documents {
+                let file_name = document.name.rsplit(PATH_SEPARATOR).next().unwrap_or_default();
+                println!("{file_name}\t{}\t#{}", document.size, document.id);
+            }
+        }
+        Output::Sync(report) => println!("{report}"),
+    }
+}
+
+/// Reads the passwords of the two users, from the source chosen in the options
+fn read_passwords(cli: &Cli) -> Result<(String, String), VaultError> {
+    let text = match (cli.password_fd, cli.password_stdin) {
+        (Some(fd), _) => fs::read_to_string(format!("/dev/fd/{fd}")).map_err(|_| InputError)?,
+        (None, true) => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text).map_err(|_| InputError)?;
+            text
+        }
+        (None, false) => {
+            let password1 = std::env::var("VAULT_PASSWORD1").map_err(|_| InputError)?;
+            let password2 = std::env::var("VAULT_PASSWORD2").map_err(|_| InputError)?;
+            return Ok((password1, password2));
+        }
+    };
+    parse_passwords(&text)
+}
+
+/// Reads the passwords of the two users from the first two lines of `text`
+fn parse_passwords(text: &str) -> Result<(String, String), VaultError> {
+    let mut lines = text.lines();
+    match (lines.next(), lines.next()) {
+        (Some(password1), Some(password2)) => Ok((password1.to_string(), password2.to_string())),
+        _ => Err(InputError),
+    }
+}
+
+fn run(cli: &Cli) -> Result<Output, VaultError> {
+    let socket_path = cli.socket.clone().unwrap_or_else(agent::default_socket_path);
+    let request = match &cli.command {
+        Command::Agent { idle_timeout } => {
+            let controller = unlock(cli)?;
+            agent::serve(controller, &socket_path, Duration::from_secs(idle_timeout * 60))?;
+            return Ok(Output::None);
+        }
+        Command::Lock if !agent::is_running(&socket_path) => return Ok(Output::None),
+        command => agent_request(command)?,
+    };
+
+    let response = if agent::is_running(&socket_path) {
+        let user_names: Vec<&str> = [&cli.user1, &cli.user2].into_iter().flatten().map(String::as_str).collect();
+        agent::check_session(&socket_path, cli.organization.as_deref(), &user_names)?;
+        agent::send_request(&socket_path, &request)?
+    } else {
+        let mut controller = unlock(cli)?;
+        let response = agent::execute(&mut controller, request);
+        controller.revoke_token().ok();
+        response?
+    };
+    command_output(&cli.command, response)
+}
+
+fn unlock(cli: &Cli) -> Result<Controller<AsyncCachingConnection>, VaultError> {
+    let organization = cli.organization.as_deref().ok_or(InputError)?;
+    let user1 = cli.user1.as_deref().ok_or(InputError)?;
+    let user2 = cli.user2.as_deref().ok_or(InputError)?;
+    let (password1, password2) = read_passwords(cli)?;
+    let client_config = ClientConfig::get();
+    let cache_validity = client_config.offline_cache_validity_hours.map(|hours| Duration::from_secs(hours * 3600));
+    let mut server = CachingHttpConnection::new(client_config.server_port, cache_validity)?;
+    Controller::unlock_vault_for_organization(&mut server, organization, user1, &password1, user2, &password2)
+}
+
+/// Request that runs a subcommand, either locally or in the agent. The files are read here, as the agent may run in an
+/// other working directory.
+fn agent_request(command: &Command) -> Result<AgentRequest, VaultError> {
+    Ok(match command {
+        Command::Upload { file, name } => {
+            let content = fs::read_to_string(file).map_err(|_| FileError)?;
+            let name = match name {
+                Some(name) => name.clone(),
+                None => file.file_name().and_then(|file_name| file_name.to_str()).ok_or(InputError)?.to_string(),
+            };
+            AgentRequest::Upload { document: Document { name, content } }
+        }
+        Command::Get { name, .. } => match entered_document_id(name) {
+            Some(document_id) => AgentRequest::DownloadById { document_id },
+            None => AgentRequest::Download { document_name: name.clone() },
+        },
+        Command::Ls { folder } => AgentRequest::ListFolder { folder: folder.clone() },
+        Command::Share { name, organization, permission, days } => {
+            let expires_at = days.map(|days| {
+                (SystemTime::now() + Duration::from_secs(days * 86400)).duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+            });
+            let other_organization_name = organization.clone();
+            let permission = (*permission).into();
+            match entered_document_id(name) {
+                Some(document_id) => AgentRequest::ShareById { document_id, other_organization_name, permission, expires_at },
+                None => AgentRequest::Share { document_name: name.clone(), other_organization_name, permission, expires_at },
+            }
+        }
+        Command::Rm { name } => match entered_document_id(name) {
+            Some(document_id) => AgentRequest::DeleteById { document_id },
+            None => AgentRequest::Delete { document_name: name.clone() },
+        },
+        Command::Sync { directory, folder, dry_run } => AgentRequest::Sync {
+            local_directory: fs::canonicalize(Path::new(directory)).map_err(|_| FileError)?,
+            vault_folder: folder.clone(),
+            dry_run: *dry_run,
+        },
+        Command::Lock => AgentRequest::Lock,
+        Command::Agent { .. } => return Err(InputError),
+    })
+}
+
+fn command_output(command: &Command, response: AgentResponse) -> Result<Output, VaultError> {
+    match (command, response) {
+        (Command::Get { output: Some(output), .. }, AgentResponse::Document(document)) => {
+            fs::write(output, document.content).map_err(|_| FileError)?;
+            Ok(Output::None)
+        }
+        (_, AgentResponse::Document(document)) => Ok(Output::Document { name: document.name, content: document.content }),
+        (_, AgentResponse::Folder(folder_content)) => Ok(Output::Folder {
+            folders: folder_content.folders,
+            documents: folder_content.documents.into_iter().map(ListedDocument::from).collect(),
+        }),
+        (_, AgentResponse::Sync(report)) => Ok(Output::Sync(report)),
+        (_, AgentResponse::Done | AgentResponse::Session { .. }) => Ok(Output::None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(exit_code(&DocumentNotFound), EXIT_NOT_FOUND);
+        assert_eq!(exit_code(&AmbiguousDocumentName), EXIT_NOT_FOUND);
+        assert_eq!(exit_code(&VersionConflict), EXIT_CONFLICT);
+        assert_eq!(exit_code(&ServerUnreachable), EXIT_UNREACHABLE);
+        assert_eq!(exit_code(&IncompatibleServerVersion(vec![1])), EXIT_UNREACHABLE);
+        assert_eq!(exit_code(&CryptographyError), EXIT_AUTHENTICATION);
+        assert_eq!(exit_code(&SessionExpired), EXIT_AUTHENTICATION);
+        assert_eq!(exit_code(&InputError), EXIT_USAGE);
+        assert_eq!(exit_code(&ValidationError), EXIT_USAGE);
+        assert_eq!(exit_code(&ServerError), EXIT_FAILURE);
+    }
+
+    #[test]
+    fn password_lines() {
+        assert_eq!(parse_passwords("first\nsecond\n"), Ok(("first".to_string(), "second".to_string())));
+        assert_eq!(parse_passwords("first\r\nsecond"), Ok(("first".to_string(), "second".to_string())));
+        assert_eq!(parse_passwords("first\nsecond\nignored\n"), Ok(("first".to_string(), "second".to_string())));
+        assert_eq!(parse_passwords("with spaces \n\n"), Ok(("with spaces ".to_string(), String::new())));
+        assert_eq!(parse_passwords("first\n"), Err(InputError));
+        assert_eq!(parse_passwords(""), Err(InputError));
+    }
+}
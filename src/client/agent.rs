@@ -0,0 +1,227 @@
+//! Agent that keeps a vault unlocked between the commands of the non-interactive client, like ssh-agent
+//!
+//! The agent holds an unlocked `Controller` and serves requests over a Unix domain socket. The socket is created in a
+//! directory that only the user can access, so that the other users of the computer can not use the unlocked vault.
+//! Each connection carries one request and its response, each serialized as one line of JSON.
+//!
+//! The agent locks the vault, by revoking its session token and exiting, when it receives `AgentRequest::Lock` or when
+//! it has not received any request for its idle timeout. It also exits when the server no longer accepts its session,
+//! e.g. after the session timeout of the server, so that the next command unlocks the vault again.
+
+use std::{env, fs};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::client::client_config::CLIENT_FILES_LOCATION;
+use crate::client::session_controller::Controller;
+use crate::client::sync::{sync_directory, SyncReport};
+use crate::data::{Document, DocumentID, FolderContent, Permission};
+use crate::error::VaultError;
+use crate::error::VaultError::{FileError, InputError, ServerError, SessionExpired};
+use crate::server_connection::AsyncServerConnection;
+use crate::validation::{validate_and_standardize_name, validate_and_standardize_owner_name};
+
+/// Maximum time to read a request or write a response, so that a stuck client does not block the agent
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Socket of the agent, unless an other one is configured. It is in the runtime directory of the user, `$XDG_RUNTIME_DIR`,
+/// so that the agent is found from any working directory, or else in the client files.
+pub fn default_socket_path() -> PathBuf {
+    let directory = match env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from) {
+        Some(runtime_directory) if runtime_directory.is_absolute() => runtime_directory.join("vault"),
+        _ => env::current_dir().unwrap_or_default().join(CLIENT_FILES_LOCATION).join("agent"),
+    };
+    directory.join("agent.sock")
+}
+
+/// Operation of the controller requested from the agent
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum AgentRequest {
+    Upload { document: Document },
+    Download { document_name: String },
+    DownloadById { document_id: DocumentID },
+    ListFolder { folder: String },
+    /// `expires_at` is in seconds since the Unix epoch
+    Share { document_name: String, other_organization_name: String, permission: Permission, expires_at: Option<u64> },
+    ShareById { document_id: DocumentID, other_organization_name: String, permission: Permission, expires_at: Option<u64> },
+    Delete { document_name: String },
+    DeleteById { document_id: DocumentID },
+    /// `local_directory` must be absolute, as the agent may run in an other working directory
+    Sync { local_directory: PathBuf, vault_folder: String, dry_run: bool },
+    /// Returns the organization and the users of the unlocked session
+    Session,
+    /// Locks the vault and stops the agent
+    Lock,
+}
+
+/// Result of a request, as returned by the agent
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum AgentResponse {
+    Done,
+    Document(Document),
+    Folder(FolderContent),
+    Sync(SyncReport),
+    Session { organization_name: String, user_names: Vec<String> },
+}
+
+/// Runs a request on an unlocked controller. `Lock` does nothing, as locking is up to the owner of the controller.
+pub fn execute<A: AsyncServerConnection + Clone + 'static>(controller: &mut Controller<A>, request: AgentRequest)
+                                                          -> Result<AgentResponse, VaultError> {
+    let expiry_time = |expires_at: Option<u64>| expires_at.map(|expires_at| UNIX_EPOCH + Duration::from_secs(expires_at));
+    match request {
+        AgentRequest::Upload { document } => controller.upload(&document).map(|_| AgentResponse::Done),
+        AgentRequest::Download { document_name } => controller.download(&document_name).map(AgentResponse::Document),
+        AgentRequest::DownloadById { document_id } => controller.download_by_id(&document_id).map(AgentResponse::Document),
+        AgentRequest::ListFolder { folder } => controller.list_folder(&folder).map(AgentResponse::Folder),
+        AgentRequest::Share { document_name, other_organization_name, permission, expires_at } =>
+            controller.share(&document_name, &other_organization_name, permission, expiry_time(expires_at)).map(|_| AgentResponse::Done),
+        AgentRequest::ShareById { document_id, other_organization_name, permission, expires_at } =>
+            controller.share_by_id(&document_id, &other_organization_name, permission, expiry_time(expires_at)).map(|_| AgentResponse::Done),
+        AgentRequest::Delete { document_name } => controller.delete(&document_name).map(|_| AgentResponse::Done),
+        AgentRequest::DeleteById { document_id } => controller.delete_by_id(&document_id).map(|_| AgentResponse::Done),
+        AgentRequest::Sync { local_directory, vault_folder, dry_run } =>
+            sync_directory(controller, &local_directory, &vault_folder, dry_run).map(AgentResponse::Sync),
+        AgentRequest::Session =>
+            Ok(AgentResponse::Session { organization_name: controller.organization_name().to_string(), user_names: controller.user_names() }),
+        AgentRequest::Lock => Ok(AgentResponse::Done),
+    }
+}
+
+/// Serves the requests sent to `socket_path` with `controller`, until the vault is locked.
+/// Fails with a FileError if an other agent already listens on `socket_path`.
+pub fn serve<A: AsyncServerConnection + Clone + 'static>(mut controller: Controller<A>, socket_path: &Path, idle_timeout: Duration)
+                                                        -> Result<(), VaultError> {
+    let listener = bind_private_socket(socket_path)?;
+
+    // The connections are accepted on an other thread, so that waiting for the next one can time out
+    let (sender, receiver) = mpsc::channel();
+    let accepting_thread = thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if sender.send(stream).is_err() {
+                break;
+            }
+        }
+    });
+
+    loop {
+        let stream = match receiver.recv_timeout(idle_timeout) {
+            Ok(stream) => stream,
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+        };
+        // A client that fails to send a valid request only ends its own connection
+        if let Ok(true) = serve_connection(&mut controller, stream) {
+            break;
+        }
+    }
+
+    controller.revoke_token().ok();
+    // Wakes the accepting thread up, so that it notices that the agent stopped
+    drop(receiver);
+    UnixStream::connect(socket_path).ok();
+    accepting_thread.join().ok();
+    fs::remove_file(socket_path).map_err(|_| FileError)
+}
+
+/// Binds the socket in a directory that only the user can access, replacing the socket of an agent that is no longer running.
+/// The directory is created with mode `0700` if it does not exist. An existing directory is never changed: it is refused,
+/// with a FileError, unless it is owned by the user and has mode `0700`.
+fn bind_private_socket(socket_path: &Path) -> Result<UnixListener, VaultError> {
+    let directory = socket_path.parent().ok_or(FileError)?;
+    if let Some(parent_directory) = directory.parent() {
+        fs::create_dir_all(parent_directory).map_err(|_| FileError)?;
+    }
+    match fs::DirBuilder::new().mode(0o700).create(directory) {
+        Ok(()) => {}
+        Err(error) if error.kind() == ErrorKind::AlreadyExists => check_private_directory(directory)?,
+        Err(_) => return Err(FileError),
+    }
+
+    if socket_path.exists() {
+        if UnixStream::connect(socket_path).is_ok() {
+            return Err(FileError);
+        }
+        fs::remove_file(socket_path).map_err(|_| FileError)?;
+    }
+    let listener = UnixListener::bind(socket_path).map_err(|_| FileError)?;
+    fs::set_permissions(socket_path, fs::Permissions::from_mode(0o600)).map_err(|_| FileError)?;
+    Ok(listener)
+}
+
+/// Checks that only the user can access an existing directory. A symbolic link is refused, as its target could change.
+fn check_private_directory(directory: &Path) -> Result<(), VaultError> {
+    let metadata = fs::symlink_metadata(directory).map_err(|_| FileError)?;
+    // SAFETY: geteuid has no preconditions and can not fail
+    let user_id = unsafe { libc::geteuid() };
+    if metadata.is_dir() && metadata.uid() == user_id && metadata.permissions().mode() & 0o777 == 0o700 {
+        Ok(())
+    } else {
+        Err(FileError)
+    }
+}
+
+/// Reads a request, runs it and writes its result.
+/// Returns whether the agent must stop: the request was `Lock`, or the server no longer accepts the session.
+fn serve_connection<A: AsyncServerConnection + Clone + 'static>(controller: &mut Controller<A>, stream: UnixStream)
+                                                               -> Result<bool, VaultError> {
+    stream.set_read_timeout(Some(CONNECTION_TIMEOUT)).map_err(|_| FileError)?;
+    stream.set_write_timeout(Some(CONNECTION_TIMEOUT)).map_err(|_| FileError)?;
+    let request: AgentRequest = read_message(&stream)?;
+    let is_lock = request == AgentRequest::Lock;
+    let response = execute(controller, request);
+    let is_session_expired = response == Err(SessionExpired);
+    write_message(&stream, &response)?;
+    Ok(is_lock || is_session_expired)
+}
+
+/// Sends a request to the agent listening on `socket_path`, and returns its result.
+/// Fails with a FileError if no agent is running.
+pub fn send_request(socket_path: &Path, request: &AgentRequest) -> Result<AgentResponse, VaultError> {
+    let stream = UnixStream::connect(socket_path).map_err(|_| FileError)?;
+    write_message(&stream, request)?;
+    read_message::<Result<AgentResponse, VaultError>>(&stream)?
+}
+
+/// Checks that the agent listening on `socket_path` holds a session of `organization_name`, if given, unlocked by `user_names`.
+/// Fails with an InputError otherwise, so that a command meant for an other organization does not run in the vault of the agent.
+pub fn check_session(socket_path: &Path, organization_name: Option<&str>, user_names: &[&str]) -> Result<(), VaultError> {
+    let AgentResponse::Session { organization_name: session_organization_name, user_names: session_user_names } =
+        send_request(socket_path, &AgentRequest::Session)? else {
+        return Err(ServerError);
+    };
+    if let Some(organization_name) = organization_name {
+        if validate_and_standardize_owner_name(organization_name)? != session_organization_name {
+            return Err(InputError);
+        }
+    }
+    for user_name in user_names {
+        if !session_user_names.contains(&validate_and_standardize_name(user_name)?) {
+            return Err(InputError);
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether an agent listens on `socket_path`
+pub fn is_running(socket_path: &Path) -> bool {
+    UnixStream::connect(socket_path).is_ok()
+}
+
+fn write_message<T: Serialize>(mut stream: &UnixStream, message: &T) -> Result<(), VaultError> {
+    let mut line = serde_json::to_string(message).map_err(|_| ServerError)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes()).map_err(|_| ServerError)
+}
+
+fn read_message<T: for<'de> Deserialize<'de>>(stream: &UnixStream) -> Result<T, VaultError> {
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).map_err(|_| ServerError)?;
+    serde_json::from_str(&line).map_err(|_| ServerError)
+}
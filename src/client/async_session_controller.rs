@@ -0,0 +1,818 @@
+//! Asynchronous counterpart of the session controller, for the user interfaces that run on a tokio runtime
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use dryoc::{dryocbox, dryocsecretbox};
+use dryoc::dryocsecretbox::NewByteArray;
+use tokio::runtime::Handle;
+use tokio::task;
+
+use crate::client::document_path::{join_path, normalize_path, PATH_SEPARATOR, relative_path};
+use crate::client::document_index::DocumentIndex;
+use crate::client::encryptor_decryptor::{decrypt_document_info_with_key, encrypt_document_key_for_organization, OrganizationEncryptorDecryptor, unique_document_id};
+use crate::client::key_pair::{retrieve_private_key_with_user_keys, user_key};
+use crate::client::search_index::SearchIndex;
+use crate::client::share_link::ShareLink;
+use crate::data::{Document, DocumentID, DocumentInfo, DocumentMetadata, DocumentOrder, DocumentOwner, DocumentPageRequest, EncryptedDocumentInfo, FolderContent, GroupInfo, PendingShare, Permission};
+use crate::error::VaultError;
+use crate::error::VaultError::{CryptographyError, DocumentNotFound, InputError, ServerError};
+use crate::http_api::MAX_BATCH_SIZE;
+use crate::server_connection::{AsyncServerConnection, DocumentEventReceiver};
+use crate::symmetric_encryption_helper::SymEncryptedData;
+use crate::validation::{group_owner_name, validate_and_standardize_name};
+
+/// Number of documents fetched per request when iterating over the documents
+pub const DOCUMENT_PAGE_SIZE: usize = 100;
+
+/// Version at which the server stores a new document
+const NEW_DOCUMENT_VERSION: u64 = 0;
+
+/// An asynchronous controller instance represents a client session.
+/// A new controller must first be built with `unlock_vault_for_organization`, in order to retrieve the organization private key.
+/// The controller is then used to manipulate documents.
+///
+/// The session token should be revoked with `revoke_token` when the session ends. Otherwise, dropping the controller
+/// spawns a task that revokes it, provided that it is dropped within a tokio runtime.
+#[derive(Debug, PartialEq)]
+pub struct AsyncController<A: AsyncServerConnection + Clone + 'static> {
+    server: A,
+    /// Organization of the session, or `<organization>.<group>` for a group session
+    organization_name: String,
+    encryptor_decryptor: OrganizationEncryptorDecryptor,
+    /// Keys derived from the passwords of the users who unlocked the session, by user name
+    user_keys: HashMap<String, dryocsecretbox::Key>,
+    /// Versions of the documents downloaded during the session, on which their updates are based
+    read_versions: HashMap<DocumentID, u64>,
+    /// Decrypted names and keys of the listed documents, refreshed whenever a document is looked up by name
+    document_index: DocumentIndex,
+    /// Set when an update of the search index failed, so that the index misses some changes of the documents
+    search_index_outdated: bool,
+    token_revoked: bool,
+}
+
+impl<A: AsyncServerConnection + Clone + 'static> AsyncController<A> {
+    /// Retrieves the organization private key and decrypts the authentication token.
+    ///
+    /// The private key derivation is computationally expensive, so it runs on a blocking thread.
+    pub async fn unlock_vault_for_organization(server: &mut A, organization_name: &str,
+                                               username1: &str, password1: &str,
+                                               username2: &str, password2: &str)
+                                               -> Result<Self, VaultError> {
+        let organization_name = validate_and_standardize_name(organization_name)?;
+        let username1 = validate_and_standardize_name(username1)?;
+        let username2 = validate_and_standardize_name(username2)?;
+
+        let (user_share1, user_share2, argon_config, public_key, encrypted_token) =
+            server.unlock_vault(&organization_name, &username1, &username2).await?;
+        let (password1, password2) = (password1.to_string(), password2.to_string());
+        let (user_key1, user_key2, private_key) = task::spawn_blocking(move || {
+            let user_key1 = user_key(&password1, &user_share1, &argon_config)?;
+            let user_key2 = user_key(&password2, &user_share2, &argon_config)?;
+            let private_key = retrieve_private_key_with_user_keys(&user_key1, &user_share1, &user_key2, &user_share2)?;
+            Ok::<_, VaultError>((user_key1, user_key2, private_key))
+        }).await.map_err(|_| CryptographyError)??;
+
+        let encryptor_decryptor =
+            OrganizationEncryptorDecryptor::new(dryocbox::KeyPair { public_key, secret_key: private_key });
+        let token = encryptor_decryptor.decrypt_token(&encrypted_token)?;
+
+        let mut server = server.clone();
+        server.set_token(&token);
+
+        let user_keys = HashMap::from([(username1, user_key1), (username2, user_key2)]);
+        Ok(AsyncController {
+            server, organization_name, encryptor_decryptor, user_keys, read_versions: HashMap::new(), document_index: DocumentIndex::default(),
+            search_index_outdated: false, token_revoked: false,
+        })
+    }
+
+    /// Returns the organization of the session, or `<organization>.<group>` for a group session
+    pub fn organization_name(&self) -> &str {
+        &self.organization_name
+    }
+
+    /// Returns the names of the users who unlocked the session, in alphabetical order
+    pub fn user_names(&self) -> Vec<String> {
+        let mut user_names: Vec<String> = self.user_keys.keys().cloned().collect();
+        user_names.sort();
+        user_names
+    }
+
+    pub async fn revoke_user(&mut self, username: &str) -> Result<(), VaultError> {
+        self.server.revoke_user(username).await
+    }
+
+    /// Logs the client out
+    pub async fn revoke_token(&mut self) -> Result<(), VaultError> {
+        self.server.revoke_token().await?;
+        self.token_revoked = true;
+        Ok(())
+    }
+
+    pub(crate) fn is_token_revoked(&self) -> bool {
+        self.token_revoked
+    }
+
+    /// Uploads a new document and adds it to the search index
+    pub async fn upload(&mut self, document: &Document) -> Result<(), VaultError> {
+        let (encrypted_document, encrypted_key) =
+            self.encryptor_decryptor.generate_document_key_and_encrypt_document(document)?;
+        let document_id = self.server.new_document(&encrypted_document, &encrypted_key).await?;
+        self.update_search_index(|search_index| search_index.add_document(&document_id, NEW_DOCUMENT_VERSION, document)).await;
+        Ok(())
+    }
+
+    /// Uploads a new document along with its metadata, which is encrypted with the document key
+    pub async fn upload_with_metadata(&mut self, document: &Document, metadata: &DocumentMetadata) -> Result<(), VaultError> {
+        let (mut encrypted_document, encrypted_key) =
+            self.encryptor_decryptor.generate_document_key_and_encrypt_document(document)?;
+        encrypted_document.metadata = Some(self.encryptor_decryptor.encrypt_metadata_with_key(metadata, &encrypted_key)?);
+        let document_id = self.server.new_document(&encrypted_document, &encrypted_key).await?;
+        self.update_search_index(|search_index| search_index.add_document(&document_id, NEW_DOCUMENT_VERSION, document)).await;
+        Ok(())
+    }
+
+    /// Fetches and decrypts the search index of the organization. An organization without index gets an empty one.
+    async fn fetch_search_index(&mut self) -> Result<SearchIndex, VaultError> {
+        match self.server.get_search_index().await? {
+            Some(encrypted_search_index) => self.encryptor_decryptor.decrypt_search_index(&encrypted_search_index),
+            None => Ok(SearchIndex::default()),
+        }
+    }
+
+    /// Applies `change` to the latest search index and stores it back on the server.
+    /// When two sessions update the index at the same time, the last one wins.
+    ///
+    /// The index is updated after the change of the documents succeeded, so a failure of the update is not reported as a
+    /// failure of the change, which must not be retried. The index is marked as outdated instead, and rebuilt by the next search.
+    async fn update_search_index<F: FnOnce(&mut SearchIndex)>(&mut self, change: F) {
+        let result = async {
+            let mut search_index = self.fetch_search_index().await?;
+            change(&mut search_index);
+            let encrypted_search_index = self.encryptor_decryptor.encrypt_search_index(&search_index)?;
+            self.server.set_search_index(&encrypted_search_index).await
+        }.await;
+        if result.is_err() {
+            self.search_index_outdated = true;
+        }
+    }
+
+    /// Returns whether an update of the search index failed in this session, since the index was last rebuilt
+    pub fn is_search_index_outdated(&self) -> bool {
+        self.search_index_outdated
+    }
+
+    /// Returns the names of the documents whose name or content contain all the words of `query`.
+    /// The search runs on the decrypted index, so that the server does not learn the searched words.
+    /// The index is rebuilt first if it is outdated.
+    ///
+    /// Only the documents of the current listing are searched. The documents that changed since they were indexed, e.g.
+    /// updated by an other owner, are indexed again first.
+    pub async fn search(&mut self, query: &str) -> Result<Vec<String>, VaultError> {
+        if self.search_index_outdated {
+            self.rebuild_search_index().await?;
+        }
+        let document_list = self.refresh_document_index().await?;
+        let mut search_index = self.fetch_search_index().await?;
+        let stale_document_ids: Vec<DocumentID> = document_list.iter()
+            .filter(|document_info| search_index.document_version(&document_info.document_id) != Some(document_info.version))
+            .map(|document_info| document_info.document_id.clone())
+            .collect();
+        let document_ids: Vec<DocumentID> = document_list.into_iter().map(|document_info| document_info.document_id).collect();
+        let has_removed_documents = search_index.retain_documents(&document_ids);
+
+        if has_removed_documents || !stale_document_ids.is_empty() {
+            self.index_documents(&mut search_index, &stale_document_ids).await?;
+            // Storing the refreshed index only saves work to the next searches, which refresh it again otherwise
+            let encrypted_search_index = self.encryptor_decryptor.encrypt_search_index(&search_index)?;
+            self.server.set_search_index(&encrypted_search_index).await.ok();
+        }
+        Ok(search_index.search(query))
+    }
+
+    /// Downloads the documents of `document_ids` and indexes them at their current version
+    async fn index_documents(&mut self, search_index: &mut SearchIndex, document_ids: &[DocumentID]) -> Result<(), VaultError> {
+        for batch in document_ids.chunks(MAX_BATCH_SIZE) {
+            let batch_results = self.server.get_documents(batch).await?;
+            for (document_id, result) in batch.iter().zip(batch_results) {
+                let (encrypted_document, document_key) = result?;
+                let document = self.encryptor_decryptor.decrypt_document(&encrypted_document, &document_key)?;
+                search_index.add_document(document_id, encrypted_document.version, &document);
+            }
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the search index from the content of all the documents of the organization.
+    /// This indexes the documents uploaded by an older client, or accepted before the search index existed.
+    pub async fn rebuild_search_index(&mut self) -> Result<(), VaultError> {
+        let document_list = self.server.list_documents().await?;
+        let document_ids: Vec<_> = document_list.into_iter().map(|document_info| document_info.document_id).collect();
+
+        let mut search_index = SearchIndex::default();
+        self.index_documents(&mut search_index, &document_ids).await?;
+
+        let encrypted_search_index = self.encryptor_decryptor.encrypt_search_index(&search_index)?;
+        self.server.set_search_index(&encrypted_search_index).await?;
+        self.search_index_outdated = false;
+        Ok(())
+    }
+
+    /// Subscribes to the changes of the documents of the session that happen from now on: uploads, updates,
+    /// deletions, and invitations of other organizations. The events stop when the session ends.
+    pub async fn subscribe_events(&mut self) -> Result<DocumentEventReceiver, VaultError> {
+        self.server.subscribe_events().await
+    }
+
+    pub async fn list_document_names(&mut self) -> Result<Vec<String>, VaultError> {
+        let encrypted_documents_info = self.refresh_document_index().await?;
+        encrypted_documents_info
+            .iter()
+            .map(|document_info| self.document_index.document_name(&document_info.document_id).map(str::to_string).ok_or(CryptographyError))
+            .collect()
+    }
+
+    /// Lists the documents with their ID, name, metadata, size and timestamps.
+    /// The names and keys come from the document index, so only the new and changed documents are decrypted with the private key.
+    pub async fn list_documents(&mut self) -> Result<Vec<DocumentInfo>, VaultError> {
+        let encrypted_documents_info = self.refresh_document_index().await?;
+        encrypted_documents_info
+            .iter()
+            .map(|document_info| self.decrypt_document_info(document_info))
+            .collect()
+    }
+
+    /// Decrypts the info of a listed document, with the name and key from the index if it indexes this version
+    fn decrypt_document_info(&self, encrypted_document_info: &EncryptedDocumentInfo) -> Result<DocumentInfo, VaultError> {
+        match self.document_index.document_name_and_key(&encrypted_document_info.document_id, encrypted_document_info.version) {
+            Some((name, document_key)) => decrypt_document_info_with_key(encrypted_document_info, name, document_key),
+            None => self.encryptor_decryptor.decrypt_document_info(encrypted_document_info),
+        }
+    }
+
+    /// Uploads several documents, in batches of at most `MAX_BATCH_SIZE` documents per request, and adds them to the search index.
+    /// Returns the result of each upload, in the order of `documents`.
+    pub async fn upload_many(&mut self, documents: &[Document]) -> Vec<Result<(), VaultError>> {
+        let encrypted_documents: Vec<_> = documents.iter()
+            .map(|document| self.encryptor_decryptor.generate_document_key_and_encrypt_document(document))
+            .collect();
+        let documents_to_upload: Vec<_> = encrypted_documents.iter()
+            .filter_map(|encrypted_document| encrypted_document.as_ref().ok().cloned())
+            .collect();
+
+        let mut upload_results = Vec::with_capacity(documents_to_upload.len());
+        for batch in documents_to_upload.chunks(MAX_BATCH_SIZE) {
+            match self.server.new_documents(batch).await {
+                Ok(batch_results) if batch_results.len() == batch.len() => upload_results.extend(batch_results),
+                Ok(_) => upload_results.extend(batch.iter().map(|_| Err(ServerError))),
+                Err(error) => upload_results.extend(batch.iter().map(|_| Err(error.clone()))),
+            }
+        }
+
+        let mut upload_results = upload_results.into_iter();
+        let document_ids: Vec<_> = encrypted_documents.into_iter()
+            .map(|encrypted_document| match encrypted_document {
+                Ok(_) => upload_results.next().ok_or(ServerError)?,
+                Err(error) => Err(error),
+            })
+            .collect();
+
+        self.update_search_index(|search_index| {
+            for (document_id, document) in document_ids.iter().zip(documents) {
+                if let Ok(document_id) = document_id {
+                    search_index.add_document(document_id, NEW_DOCUMENT_VERSION, document);
+                }
+            }
+        }).await;
+
+        document_ids.into_iter().map(|document_id| document_id.map(|_| ())).collect()
+    }
+
+    /// Downloads several documents, in batches of at most `MAX_BATCH_SIZE` documents per request.
+    /// Returns the result of each download, in the order of `document_names`.
+    pub async fn download_many(&mut self, document_names: &[&str]) -> Result<Vec<Result<Document, VaultError>>, VaultError> {
+        self.refresh_document_index().await?;
+        let document_ids_by_name = self.document_index.document_ids_by_name();
+        let document_ids: Vec<_> = document_names.iter()
+            .map(|document_name| unique_document_id(document_ids_by_name.get(*document_name).map(Vec::as_slice).unwrap_or_default()))
+            .collect();
+        let ids_to_download: Vec<_> = document_ids.iter()
+            .filter_map(|document_id| document_id.as_ref().ok().cloned())
+            .collect();
+
+        let mut download_results = Vec::with_capacity(ids_to_download.len());
+        for batch in ids_to_download.chunks(MAX_BATCH_SIZE) {
+            match self.server.get_documents(batch).await {
+                Ok(batch_results) if batch_results.len() == batch.len() => download_results.extend(batch_results),
+                Ok(_) => download_results.extend(batch.iter().map(|_| Err(ServerError))),
+                Err(error) => download_results.extend(batch.iter().map(|_| Err(error.clone()))),
+            }
+        }
+
+        let mut download_results = download_results.into_iter();
+        Ok(
+            document_ids.into_iter()
+                .map(|document_id| {
+                    let document_id = document_id?;
+                    let (encrypted_document, document_key) = download_results.next().ok_or(ServerError)??;
+                    self.read_versions.insert(document_id, encrypted_document.version);
+                    self.encryptor_decryptor.decrypt_document(&encrypted_document, &document_key)
+                })
+                .collect()
+        )
+    }
+
+    /// Returns an iterator over the documents in the requested order, that fetches them from the server page by page
+    pub fn documents(&mut self, order: DocumentOrder, descending: bool) -> AsyncDocumentIterator<'_, A> {
+        AsyncDocumentIterator {
+            controller: self,
+            page_request: DocumentPageRequest { order, descending, cursor: None, page_size: DOCUMENT_PAGE_SIZE },
+            page: VecDeque::new(),
+            is_last_page: false,
+        }
+    }
+
+    /// Lists the documents and indexes the ones that are new or changed since the last listing. Returns the listed documents.
+    async fn refresh_document_index(&mut self) -> Result<Vec<EncryptedDocumentInfo>, VaultError> {
+        let document_list = self.server.list_documents().await?;
+        self.document_index.refresh(&self.encryptor_decryptor, &document_list);
+        Ok(document_list)
+    }
+
+    async fn get_id_of_document_by_name(&mut self, document_name: &str) -> Result<DocumentID, VaultError> {
+        self.refresh_document_index().await?;
+        self.document_index.find_document_id(document_name)
+    }
+
+    /// Downloads a document.
+    /// Fails with AmbiguousDocumentName if several documents have this name, in which case `download_by_id` must be used.
+    pub async fn download(&mut self, document_name: &str) -> Result<Document, VaultError> {
+        let document_id = self.get_id_of_document_by_name(document_name).await?;
+        self.download_by_id(&document_id).await
+    }
+
+    /// Downloads a document by ID, and records its version as the one on which its next update is based.
+    pub async fn download_by_id(&mut self, document_id: &DocumentID) -> Result<Document, VaultError> {
+        let encrypted_document = self.server.get_document(document_id).await?;
+        self.read_versions.insert(document_id.clone(), encrypted_document.version);
+        if let Some(document_key) = self.document_index.document_key(document_id, encrypted_document.version) {
+            return encrypted_document.decrypt(document_key);
+        }
+        let document_key = self.server.get_document_key(document_id).await?;
+        self.encryptor_decryptor.decrypt_document(&encrypted_document, &document_key)
+    }
+
+    /// Returns the version of a document that was downloaded or stored last in this session, on which its next update is based
+    pub fn document_version(&self, document_id: &DocumentID) -> Option<u64> {
+        self.read_versions.get(document_id).copied()
+    }
+
+    /// Updates a document and its entry in the search index.
+    /// Fails with VersionConflict if the document was updated since it was downloaded in this session, or since it was
+    /// listed if it was not downloaded, in which case it must be downloaded again before retrying.
+    /// Fails with AmbiguousDocumentName if several documents have this name, in which case `update_by_id` must be used.
+    pub async fn update(&mut self, document_name: &str, new_document: &Document) -> Result<(), VaultError> {
+        let document_id = self.get_id_of_document_by_name(document_name).await?;
+        self.update_by_id(&document_id, new_document).await
+    }
+
+    /// Updates a document, designated by its ID, and its entry in the search index, like `update`.
+    pub async fn update_by_id(&mut self, document_id: &DocumentID, new_document: &Document) -> Result<(), VaultError> {
+        let expected_version = self.base_version(document_id).await?;
+        let version = self.store_new_version(document_id, new_document, expected_version).await?;
+        self.update_search_index(|search_index| search_index.add_document(document_id, version, new_document)).await;
+        Ok(())
+    }
+
+    /// Updates a document, designated by its ID, and its entry in the search index, if the document is still at `version`,
+    /// e.g. the version listed by `list_documents`. Fails with VersionConflict otherwise.
+    pub async fn update_by_id_at_version(&mut self, document_id: &DocumentID, new_document: &Document, version: u64) -> Result<(), VaultError> {
+        let version = self.store_new_version(document_id, new_document, version).await?;
+        self.update_search_index(|search_index| search_index.add_document(document_id, version, new_document)).await;
+        Ok(())
+    }
+
+    /// Returns the version on which an update of a document is based: the version downloaded or stored in this session,
+    /// or else the version listed last. The documents are listed again if this one is not indexed.
+    async fn base_version(&mut self, document_id: &DocumentID) -> Result<u64, VaultError> {
+        if let Some(version) = self.document_version(document_id) {
+            return Ok(version);
+        }
+        if self.document_index.document_version(document_id).is_none() {
+            self.refresh_document_index().await?;
+        }
+        self.document_index.document_version(document_id).ok_or(DocumentNotFound)
+    }
+
+    /// Encrypts a new version of a document with its key and stores it, without updating the search index.
+    /// The update is conditional on `expected_version`. The indexed key is only used for that version,
+    /// as the update then fails if the key was rotated since.
+    async fn store_new_version(&mut self, document_id: &DocumentID, new_document: &Document, expected_version: u64)
+                               -> Result<u64, VaultError> {
+        let new_document_encrypted = match self.document_index.document_key(document_id, expected_version) {
+            Some(document_key) => new_document.encrypt(document_key),
+            None => {
+                let document_key = self.server.get_document_key(document_id).await?;
+                self.encryptor_decryptor.encrypt_document_with_key(new_document, &document_key)?
+            }
+        };
+        let version = self.server.update_document(document_id, &new_document_encrypted, Some(expected_version)).await?;
+        self.read_versions.insert(document_id.clone(), version);
+        self.document_index.update_document(document_id, &new_document.name, expected_version, version);
+        Ok(version)
+    }
+
+    /// Invites an other organization to access a document with `permission`, until `expires_at` if any.
+    /// The other organization gets access once it accepts the invitation with `accept_share`. Only the owners of a document may share it.
+    /// Fails with AmbiguousDocumentName if several documents have this name, in which case `share_by_id` must be used.
+    pub async fn share(&mut self, document_name: &str, other_organization_name: &str, permission: Permission, expires_at: Option<SystemTime>)
+                       -> Result<(), VaultError> {
+        let document_id = self.get_id_of_document_by_name(document_name).await?;
+        self.share_listed_document(&document_id, other_organization_name, permission, expires_at).await
+    }
+
+    /// Invites an other organization to access a document, designated by its ID, like `share`.
+    pub async fn share_by_id(&mut self, document_id: &DocumentID, other_organization_name: &str, permission: Permission, expires_at: Option<SystemTime>)
+                             -> Result<(), VaultError> {
+        self.refresh_document_index().await?;
+        self.share_listed_document(document_id, other_organization_name, permission, expires_at).await
+    }
+
+    /// Shares a document right after the document index was refreshed, so that its indexed key is the current one.
+    /// The key is only requested from the server if the document is not indexed.
+    async fn share_listed_document(&mut self, document_id: &DocumentID, other_organization_name: &str, permission: Permission,
+                                   expires_at: Option<SystemTime>)
+                                   -> Result<(), VaultError> {
+        let other_organization_public_key = self.server.get_public_key_of_organization(other_organization_name).await?;
+        let new_encrypted_document_key = match self.document_index.latest_document_key(document_id) {
+            Some(document_key) => encrypt_document_key_for_organization(document_key, &other_organization_public_key)?,
+            None => {
+                let encrypted_document_key = self.server.get_document_key(document_id).await?;
+                self.encryptor_decryptor.encrypt_document_key_for_other_organization(&encrypted_document_key, &other_organization_public_key)?
+            }
+        };
+        let expires_at = expires_at
+            .map(|expires_at| expires_at.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).map_err(|_| InputError))
+            .transpose()?;
+        self.server.add_owner(document_id, other_organization_name, &new_encrypted_document_key, permission, expires_at).await
+    }
+
+    /// Creates a link to a copy of a document, for a recipient that has no organization on the server.
+    /// The copy is encrypted with a new key, which is only part of the link. The link can be opened once, until `expires_at`.
+    /// Fails with AmbiguousDocumentName if several documents have this name, in which case `create_share_link_by_id` must be used.
+    pub async fn create_share_link(&mut self, document_name: &str, expires_at: SystemTime) -> Result<ShareLink, VaultError> {
+        let document_id = self.get_id_of_document_by_name(document_name).await?;
+        self.create_share_link_by_id(&document_id, expires_at).await
+    }
+
+    /// Creates a link to a copy of a document, designated by its ID, like `create_share_link`.
+    pub async fn create_share_link_by_id(&mut self, document_id: &DocumentID, expires_at: SystemTime) -> Result<ShareLink, VaultError> {
+        let document = self.download_by_id(document_id).await?;
+        let key = dryocsecretbox::Key::gen();
+        let expires_at = expires_at.duration_since(UNIX_EPOCH).map_err(|_| InputError)?.as_secs();
+        let link_id = self.server.create_share_link(document_id, &document.encrypt(&key), expires_at).await?;
+        Ok(ShareLink::new(link_id, key))
+    }
+
+    /// Lists the pending invitations of other organizations to access their documents
+    pub async fn pending_shares(&mut self) -> Result<Vec<PendingShare>, VaultError> {
+        let invitations = self.server.list_share_invitations().await?;
+        let mut pending_shares = Vec::with_capacity(invitations.len());
+        for invitation in invitations {
+            let document_name = self.encryptor_decryptor.decrypt_document_name(&invitation.name_and_key.data, &invitation.name_and_key.key)?;
+            pending_shares.push(PendingShare {
+                document_id: invitation.document_id,
+                document_name,
+                from_organization_name: invitation.from_organization_name,
+                permission: invitation.permission,
+                expires_at: invitation.expires_at.map(|expires_at| UNIX_EPOCH + Duration::from_secs(expires_at)),
+                created_at: UNIX_EPOCH + Duration::from_secs(invitation.created_at),
+            });
+        }
+        Ok(pending_shares)
+    }
+
+    /// Accepts an invitation to access a document, and adds the document to the search index
+    pub async fn accept_share(&mut self, document_id: &DocumentID) -> Result<(), VaultError> {
+        self.server.accept_share_invitation(document_id).await?;
+        let document = self.download_by_id(document_id).await?;
+        let version = self.document_version(document_id).unwrap_or_default();
+        self.update_search_index(|search_index| search_index.add_document(document_id, version, &document)).await;
+        Ok(())
+    }
+
+    pub async fn decline_share(&mut self, document_id: &DocumentID) -> Result<(), VaultError> {
+        self.server.decline_share_invitation(document_id).await
+    }
+
+    /// Returns the organizations that have access to a document, including this one, with their permission.
+    /// Fails with AmbiguousDocumentName if several documents have this name, in which case `owners_by_id` must be used.
+    pub async fn owners(&mut self, document_name: &str) -> Result<Vec<DocumentOwner>, VaultError> {
+        let document_id = self.get_id_of_document_by_name(document_name).await?;
+        self.owners_by_id(&document_id).await
+    }
+
+    /// Returns the organizations that have access to a document, designated by its ID, with their permission.
+    pub async fn owners_by_id(&mut self, document_id: &DocumentID) -> Result<Vec<DocumentOwner>, VaultError> {
+        self.server.get_document_owners(document_id).await
+    }
+
+    /// Removes the access of an other organization to a document, and rotates the document key.
+    /// Fails with AmbiguousDocumentName if several documents have this name, in which case `remove_owner_by_id` must be used.
+    pub async fn remove_owner(&mut self, document_name: &str, other_organization_name: &str) -> Result<(), VaultError> {
+        let document_id = self.get_id_of_document_by_name(document_name).await?;
+        self.remove_owner_by_id(&document_id, other_organization_name).await
+    }
+
+    /// The document is encrypted with a new key, which is encrypted for the remaining owners, so that the other organization
+    /// can not decrypt the next versions of the document with the key it knew.
+    /// If the rotation fails, the access is still removed, and calling this method again retries the rotation.
+    /// Fails with VersionConflict if the document was updated while it was re-encrypted.
+    pub async fn remove_owner_by_id(&mut self, document_id: &DocumentID, other_organization_name: &str) -> Result<(), VaultError> {
+        let remaining_owners = self.server.remove_owner(document_id, other_organization_name).await?;
+
+        let encrypted_document = self.server.get_document(document_id).await?;
+        let encrypted_document_key = self.server.get_document_key(document_id).await?;
+        let mut owners_public_keys = HashMap::new();
+        for owner in remaining_owners {
+            let public_key = self.server.get_public_key_of_organization(&owner).await?;
+            owners_public_keys.insert(owner, public_key);
+        }
+        let (new_encrypted_document, new_encrypted_keys) =
+            self.encryptor_decryptor.rotate_document_key(&encrypted_document, &encrypted_document_key, &owners_public_keys)?;
+        self.document_index.invalidate(document_id);
+        self.server.rotate_document_key(document_id, &new_encrypted_document, &new_encrypted_keys, encrypted_document.version).await
+    }
+
+    /// Deletes a document and removes it from the search index. The document is still accessible by the other owners.
+    /// Fails with AmbiguousDocumentName if several documents have this name, in which case `delete_by_id` must be used.
+    pub async fn delete(&mut self, document_name: &str) -> Result<(), VaultError> {
+        let document_id = self.get_id_of_document_by_name(document_name).await?;
+        self.delete_by_id(&document_id).await
+    }
+
+    /// Deletes a document, designated by its ID, and removes it from the search index.
+    pub async fn delete_by_id(&mut self, document_id: &DocumentID) -> Result<(), VaultError> {
+        self.server.delete_document(document_id).await?;
+        self.document_index.invalidate(document_id);
+        self.update_search_index(|search_index| search_index.remove_document(document_id)).await;
+        Ok(())
+    }
+}
+
+/// Folders.
+///
+/// The path of a document is its name, so the folders are encrypted with the names, and the server only sees document IDs.
+impl<A: AsyncServerConnection + Clone + 'static> AsyncController<A> {
+    /// Lists the direct subfolders and documents of a folder. `""` designates the root folder.
+    pub async fn list_folder(&mut self, folder: &str) -> Result<FolderContent, VaultError> {
+        let mut folders = BTreeSet::new();
+        let mut documents = Vec::new();
+        for document_info in self.list_documents().await? {
+            match relative_path(&document_info.name, folder).map(|path| path.split_once(PATH_SEPARATOR)) {
+                Some(Some((subfolder, _))) => {
+                    folders.insert(subfolder.to_string());
+                }
+                Some(None) => documents.push(document_info),
+                None => {}
+            }
+        }
+        Ok(FolderContent { folders: folders.into_iter().collect(), documents })
+    }
+
+    /// Moves a document to a new path. Fails with an InputError if a document already has this path.
+    pub async fn move_document(&mut self, path: &str, new_path: &str) -> Result<(), VaultError> {
+        let documents = self.list_documents().await?;
+        if documents.iter().any(|document_info| document_info.name == new_path) {
+            return Err(InputError);
+        }
+        let document_ids: Vec<DocumentID> = documents.into_iter()
+            .filter(|document_info| document_info.name == path)
+            .map(|document_info| document_info.id)
+            .collect();
+        let document_id = unique_document_id(&document_ids)?;
+
+        let mut document = self.download_by_id(&document_id).await?;
+        document.name = new_path.to_string();
+        let expected_version = self.base_version(&document_id).await?;
+        let version = self.store_new_version(&document_id, &document, expected_version).await?;
+        self.update_search_index(|search_index| search_index.add_document(&document_id, version, &document)).await;
+        Ok(())
+    }
+
+    /// Moves a folder, with its documents and subfolders, to a new path.
+    /// The root folder can not be moved, and a folder can not be moved inside itself.
+    /// Fails with an InputError, before moving any document, if a document already has the new path of a moved document.
+    pub async fn move_folder(&mut self, folder: &str, new_folder: &str) -> Result<(), VaultError> {
+        let (folder, new_folder) = (normalize_path(folder), normalize_path(new_folder));
+        if folder.is_empty() || new_folder == folder || relative_path(&new_folder, &folder).is_some() {
+            return Err(InputError);
+        }
+
+        let (folder_documents, other_documents): (Vec<_>, Vec<_>) = self.list_documents().await?
+            .into_iter()
+            .partition(|document_info| relative_path(&document_info.name, &folder).is_some());
+        let other_paths: HashSet<String> = other_documents.into_iter().map(|document_info| document_info.name).collect();
+        let has_collision = folder_documents.iter().any(|document_info| {
+            let path_in_folder = relative_path(&document_info.name, &folder).unwrap_or_default();
+            other_paths.contains(&join_path(&new_folder, path_in_folder))
+        });
+        if has_collision {
+            return Err(InputError);
+        }
+
+        let document_ids: Vec<DocumentID> = folder_documents.into_iter().map(|document_info| document_info.id).collect();
+        let mut moved_documents = Vec::new();
+        let mut result = Ok(());
+        for document_id in document_ids {
+            let moved_document = async {
+                let mut document = self.download_by_id(&document_id).await?;
+                let path_in_folder = relative_path(&document.name, &folder).unwrap_or_default().to_string();
+                document.name = join_path(&new_folder, &path_in_folder);
+                let expected_version = self.base_version(&document_id).await?;
+                self.store_new_version(&document_id, &document, expected_version).await.map(|version| (version, document))
+            }.await;
+            match moved_document {
+                Ok((version, document)) => moved_documents.push((document_id, version, document)),
+                Err(error) => {
+                    result = Err(error);
+                    break;
+                }
+            }
+        }
+
+        self.update_search_index(|search_index| {
+            for (document_id, version, document) in &moved_documents {
+                search_index.add_document(document_id, *version, document);
+            }
+        }).await;
+        result
+    }
+
+    /// Deletes a folder, with its documents and subfolders. The root folder can not be deleted.
+    /// Like `delete`, the documents are still accessible by their other owners.
+    pub async fn delete_folder(&mut self, folder: &str) -> Result<(), VaultError> {
+        let folder = normalize_path(folder);
+        if folder.is_empty() {
+            return Err(InputError);
+        }
+
+        let mut deleted_document_ids = Vec::new();
+        let mut result = Ok(());
+        for document_id in self.folder_document_ids(&folder).await? {
+            if let Err(error) = self.server.delete_document(&document_id).await {
+                result = Err(error);
+                break;
+            }
+            self.document_index.invalidate(&document_id);
+            deleted_document_ids.push(document_id);
+        }
+
+        self.update_search_index(|search_index| {
+            for document_id in &deleted_document_ids {
+                search_index.remove_document(document_id);
+            }
+        }).await;
+        result
+    }
+
+    /// Returns the IDs of the documents of a folder and its subfolders
+    async fn folder_document_ids(&mut self, folder: &str) -> Result<Vec<DocumentID>, VaultError> {
+        Ok(
+            self.list_documents().await?
+                .into_iter()
+                .filter(|document_info| relative_path(&document_info.name, folder).is_some())
+                .map(|document_info| document_info.id)
+                .collect()
+        )
+    }
+}
+
+/// Groups.
+///
+/// A group of users of the organization has its own key pair, and owns documents like an organization: documents can be
+/// shared with the group under the name `<organization>.<group>`. Its documents are accessed from a group session,
+/// opened by `open_group`, which only the sessions unlocked by at least one member of the group may open.
+impl<A: AsyncServerConnection + Clone + 'static> AsyncController<A> {
+    /// Creates a group of users of the organization. Its private key is encrypted with the organization public key,
+    /// and with the keys of the members who unlocked the session. The other members get theirs when they open the group.
+    pub async fn create_group(&mut self, group_name: &str, members: &[&str]) -> Result<(), VaultError> {
+        let group_name = validate_and_standardize_name(group_name)?;
+        let members = members.iter()
+            .map(|member| validate_and_standardize_name(member))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let key_pair = dryocbox::KeyPair::gen();
+        let encrypted_private_key = self.encryptor_decryptor.encrypt_group_private_key(&key_pair.secret_key)?;
+        let member_keys: HashMap<_, _> = self.user_keys.iter()
+            .filter(|(user_name, _)| members.contains(user_name))
+            .map(|(user_name, user_key)| (user_name.clone(), SymEncryptedData::encrypt(key_pair.secret_key.as_ref(), user_key)))
+            .collect();
+        self.server.create_group(&group_name, &members, &key_pair.public_key, &encrypted_private_key, &member_keys).await
+    }
+
+    /// Lists the groups that have one of the users who unlocked the session as a member
+    pub async fn list_groups(&mut self) -> Result<Vec<GroupInfo>, VaultError> {
+        Ok(
+            self.server.list_groups().await?
+                .into_iter()
+                .map(|group| GroupInfo { name: group.name, members: group.members })
+                .collect()
+        )
+    }
+
+    /// Opens a session of a group, to access the documents of the group
+    pub async fn open_group(&mut self, group_name: &str) -> Result<AsyncController<A>, VaultError> {
+        let group_name = validate_and_standardize_name(group_name)?;
+        let group = self.server.list_groups().await?
+            .into_iter()
+            .find(|group| group.name == group_name)
+            .ok_or(InputError)?;
+
+        let member_private_key = group.member_keys.iter()
+            .find_map(|(user_name, member_key)| member_key.decrypt(self.user_keys.get(user_name)?).ok());
+        let private_key: dryocbox::SecretKey = match member_private_key {
+            Some(private_key) => <[u8; dryoc::constants::CRYPTO_BOX_SECRETKEYBYTES]>::try_from(private_key).map_err(|_| CryptographyError)?.into(),
+            None => self.encryptor_decryptor.decrypt_group_private_key(&group.encrypted_private_key)?,
+        };
+
+        for (user_name, user_key) in &self.user_keys {
+            if group.members.contains(user_name) && !group.member_keys.contains_key(user_name) {
+                let member_key = SymEncryptedData::encrypt(private_key.as_ref(), user_key);
+                self.server.set_group_member_key(&group_name, user_name, &member_key).await?;
+            }
+        }
+
+        let encrypted_token = self.server.open_group_session(&group_name).await?;
+        let encryptor_decryptor =
+            OrganizationEncryptorDecryptor::new(dryocbox::KeyPair { public_key: group.public_key, secret_key: private_key });
+        let token = encryptor_decryptor.decrypt_token(&encrypted_token)?;
+
+        let mut server = self.server.clone();
+        server.set_token(&token);
+
+        Ok(AsyncController {
+            server, organization_name: group_owner_name(&self.organization_name, &group_name), encryptor_decryptor,
+            user_keys: self.user_keys.clone(), read_versions: HashMap::new(), document_index: DocumentIndex::default(),
+            search_index_outdated: false, token_revoked: false,
+        })
+    }
+
+    pub async fn add_group_member(&mut self, group_name: &str, user_name: &str) -> Result<(), VaultError> {
+        self.server.add_group_member(group_name, user_name).await
+    }
+
+    /// Removes a member of a group. The documents of the group are not re-encrypted: the key pair of the group stays the same.
+    pub async fn remove_group_member(&mut self, group_name: &str, user_name: &str) -> Result<(), VaultError> {
+        self.server.remove_group_member(group_name, user_name).await
+    }
+}
+
+/// Iterates over the documents of a session. A page of documents is fetched from the server when the previous one has been consumed.
+pub struct AsyncDocumentIterator<'a, A: AsyncServerConnection + Clone + 'static> {
+    controller: &'a mut AsyncController<A>,
+    page_request: DocumentPageRequest,
+    page: VecDeque<Result<DocumentInfo, VaultError>>,
+    is_last_page: bool,
+}
+
+impl<'a, A: AsyncServerConnection + Clone + 'static> AsyncDocumentIterator<'a, A> {
+    /// Returns the next document, or `None` once all the documents have been returned.
+    /// The iteration stops after an error of the server.
+    pub async fn next(&mut self) -> Option<Result<DocumentInfo, VaultError>> {
+        if self.page.is_empty() && !self.is_last_page {
+            match self.controller.server.list_documents_page(&self.page_request).await {
+                Ok(page) => {
+                    self.is_last_page = page.next_cursor.is_none();
+                    self.page_request.cursor = page.next_cursor;
+                    self.page.extend(
+                        page.documents.iter().map(|document_info| self.controller.decrypt_document_info(document_info))
+                    );
+                }
+                Err(error) => {
+                    self.is_last_page = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+        self.page.pop_front()
+    }
+}
+
+// Drop cannot wait for the revocation request, so it is sent from a task of the current runtime, if any
+impl<A: AsyncServerConnection + Clone + 'static> Drop for AsyncController<A> {
+    fn drop(&mut self) {
+        if self.token_revoked {
+            return;
+        }
+        if let Ok(runtime) = Handle::try_current() {
+            let mut server = self.server.clone();
+            runtime.spawn(async move {
+                if server.revoke_token().await.is_err() {
+                    eprintln!("Error: could not revoke sessions token, the server responded with an error")
+                }
+            });
+        }
+    }
+}
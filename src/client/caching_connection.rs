@@ -0,0 +1,401 @@
+//! Optional local cache of the documents downloaded from the server, for offline reads
+//!
+//! `AsyncCachingConnection` wraps a connection to the server. It stores the encrypted documents and document keys
+//! it downloads, and the encrypted responses needed to unlock the vault, in the client files.
+//! They stay encrypted as on the server, so the cache reveals no more than the server storage does.
+//!
+//! When the server is unreachable, the read requests are answered from the cache, provided that the organization
+//! was unlocked online within the validity period of the cache. Once this period has elapsed, the cache of the organization
+//! is deleted. The cached entries of a document are deleted when it is updated or deleted.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use data_encoding::BASE32;
+use dryoc::{dryocbox, pwhash};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::client::client_config::CLIENT_FILES_LOCATION;
+use crate::client::http_connection::AsyncHttpConnection;
+use crate::data::{BatchResults, DocumentID, DocumentOwner, DocumentPageRequest, EncryptedDocument, EncryptedDocumentInfo, EncryptedDocumentKey, EncryptedDocumentPage, EncryptedGroup, EncryptedGroupPrivateKey, EncryptedSearchIndex, EncryptedShareInvitation, EncryptedToken, Permission, ShareLinkID, Token, UserShare};
+use crate::error::VaultError;
+use crate::error::VaultError::ServerUnreachable;
+use crate::server::serde_json_disk::{load, save};
+use crate::server_connection::{AsyncServerConnection, BlockingServerConnection, DocumentEventReceiver};
+use crate::symmetric_encryption_helper::SymEncryptedData;
+
+pub const CACHE_DIRECTORY_NAME: &str = "cache";
+
+type UnlockVaultResult = (UserShare, UserShare, pwhash::Config, dryocbox::PublicKey, EncryptedToken);
+
+/// Blocking HTTPS connection to the server, with an optional local cache
+pub type CachingHttpConnection = BlockingServerConnection<AsyncCachingConnection<AsyncHttpConnection>>;
+
+impl CachingHttpConnection {
+    /// See `AsyncCachingConnection::new`
+    pub fn new(server_port: u16, validity: Option<Duration>) -> Result<CachingHttpConnection, VaultError> {
+        let runtime = Arc::new(Self::create_runtime());
+        let connection = runtime.block_on(AsyncCachingConnection::new(server_port, validity))?;
+        Ok(Self::with_runtime(connection, runtime))
+    }
+}
+
+/// Connection that answers the read requests from the local cache when the server is unreachable
+#[derive(Debug, Clone)]
+pub struct AsyncCachingConnection<A: AsyncServerConnection = AsyncHttpConnection> {
+    connection: A,
+    /// `None` when the cache is disabled, in which case the requests are only forwarded to the server
+    cache: Option<LocalCache>,
+}
+
+impl AsyncCachingConnection<AsyncHttpConnection> {
+    /// Creates an HTTPS connection to the server, whose cache is stored in the client files and remains readable
+    /// during `validity` after each online unlock. The cache is disabled if `validity` is `None`.
+    ///
+    /// When the cache is enabled, the connection is created even if the server is unreachable.
+    pub async fn new(server_port: u16, validity: Option<Duration>) -> Result<Self, VaultError> {
+        let connection = match AsyncHttpConnection::new(server_port).await {
+            Err(ServerUnreachable) if validity.is_some() => AsyncHttpConnection::new_without_version_check(server_port)?,
+            result => result?,
+        };
+        Ok(Self::wrap(connection, PathBuf::from(CLIENT_FILES_LOCATION).join(CACHE_DIRECTORY_NAME), validity))
+    }
+}
+
+impl<A: AsyncServerConnection> AsyncCachingConnection<A> {
+    /// Wraps a connection, with a cache stored in `cache_location`. The cache is disabled if `validity` is `None`.
+    pub fn wrap(connection: A, cache_location: PathBuf, validity: Option<Duration>) -> Self {
+        let cache = validity.map(|validity| LocalCache {
+            location: cache_location,
+            validity,
+            organization_directory: None,
+            next_owner_directory: None,
+            owner_directory: None,
+        });
+        AsyncCachingConnection { connection, cache }
+    }
+
+    /// Stores the value returned by the server in the cache file returned by `path`, if any.
+    /// If the server is unreachable, returns the cached value instead. If the server returns an error, deletes the cached value.
+    fn read_through<T: Serialize + DeserializeOwned>(&self, result: Result<T, VaultError>, path: impl FnOnce(&LocalCache) -> Option<PathBuf>)
+                                                     -> Result<T, VaultError> {
+        let Some((cache, path)) = self.cache.as_ref().and_then(|cache| Some((cache, path(cache)?))) else {
+            return result;
+        };
+
+        match result {
+            Ok(value) => {
+                // The cache is only an optimization for offline reads, so failing to write it does not fail the request
+                save(&value, &path, true).ok();
+                Ok(value)
+            }
+            Err(ServerUnreachable) if cache.is_usable_offline() => load(&path).map_err(|_| ServerUnreachable),
+            Err(error) => {
+                fs::remove_file(&path).ok();
+                Err(error)
+            }
+        }
+    }
+
+    /// Deletes the cache files returned by `paths`, whose content is outdated
+    fn invalidate(&self, paths: impl FnOnce(&LocalCache) -> Vec<Option<PathBuf>>) {
+        if let Some(cache) = &self.cache {
+            for path in paths(cache).into_iter().flatten() {
+                fs::remove_file(path).ok();
+            }
+        }
+    }
+
+    /// Deletes the cached document and key, when a document is changed.
+    /// The cached document list is kept until the next online listing: offline, its outdated entries are just not found.
+    fn invalidate_document(&self, document_id: &DocumentID) {
+        self.invalidate(|cache| vec![cache.document_path(document_id), cache.document_key_path(document_id)]);
+    }
+}
+
+/// Location of the cache files of the organization and of the session owner, which is the organization or one of its groups
+#[derive(Debug, Clone)]
+struct LocalCache {
+    location: PathBuf,
+    validity: Duration,
+    /// Directory of the organization whose vault was last unlocked, which contains the directories of its groups
+    organization_directory: Option<PathBuf>,
+    /// Directory of the owner whose session token is set next
+    next_owner_directory: Option<PathBuf>,
+    /// Directory of the owner of the current session
+    owner_directory: Option<PathBuf>,
+}
+
+impl LocalCache {
+    fn set_organization(&mut self, organization_name: &str) {
+        let organization_directory = self.location.join(organization_name);
+        self.next_owner_directory = Some(organization_directory.clone());
+        self.organization_directory = Some(organization_directory);
+    }
+
+    fn last_online_unlock_path(&self) -> Option<PathBuf> {
+        Some(self.organization_directory.as_ref()?.join("last_online_unlock"))
+    }
+
+    /// The user shares are returned in the order of the user names, which is thus part of the file name
+    fn unlock_path(&self, user_name1: &str, user_name2: &str) -> Option<PathBuf> {
+        Some(self.unlock_directory()?.join(format!("{user_name1}.{user_name2}")))
+    }
+
+    fn unlock_directory(&self) -> Option<PathBuf> {
+        Some(self.organization_directory.as_ref()?.join("unlock"))
+    }
+
+    /// Paths of the cached unlock responses of every pair of users that includes `user_name`
+    fn user_unlock_paths(&self, user_name: &str) -> Vec<Option<PathBuf>> {
+        let Some(entries) = self.unlock_directory().and_then(|directory| fs::read_dir(directory).ok()) else {
+            return Vec::new();
+        };
+        entries.flatten()
+            .filter(|entry| entry.file_name().to_str()
+                .is_some_and(|file_name| file_name.split('.').any(|name| name.eq_ignore_ascii_case(user_name))))
+            .map(|entry| Some(entry.path()))
+            .collect()
+    }
+
+    fn document_list_path(&self) -> Option<PathBuf> {
+        Some(self.owner_directory.as_ref()?.join("document_list"))
+    }
+
+    fn search_index_path(&self) -> Option<PathBuf> {
+        Some(self.owner_directory.as_ref()?.join("search_index"))
+    }
+
+    fn document_path(&self, document_id: &DocumentID) -> Option<PathBuf> {
+        Some(self.owner_directory.as_ref()?.join("documents").join(BASE32.encode(document_id)))
+    }
+
+    fn document_key_path(&self, document_id: &DocumentID) -> Option<PathBuf> {
+        Some(self.owner_directory.as_ref()?.join("keys").join(BASE32.encode(document_id)))
+    }
+
+    /// The cache is usable offline during its validity period after the last online unlock of the organization.
+    /// Once this period has elapsed, the cache of the organization is deleted.
+    fn is_usable_offline(&self) -> bool {
+        let (Some(organization_directory), Some(last_online_unlock_path)) = (&self.organization_directory, self.last_online_unlock_path()) else {
+            return false;
+        };
+        let last_online_unlock = load::<u64>(&last_online_unlock_path).ok().map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds));
+        let is_valid = last_online_unlock
+            .and_then(|last_online_unlock| SystemTime::now().duration_since(last_online_unlock).ok())
+            .is_some_and(|age| age <= self.validity);
+        if !is_valid {
+            fs::remove_dir_all(organization_directory).ok();
+        }
+        is_valid
+    }
+
+    fn record_online_unlock(&self) {
+        if let Some(path) = self.last_online_unlock_path() {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            save(&now, &path, true).ok();
+        }
+    }
+}
+
+#[async_trait]
+impl<A: AsyncServerConnection> AsyncServerConnection for AsyncCachingConnection<A> {
+    async fn create_organization(&mut self, organization_name: &str, users_data: &HashMap<String, UserShare>, public_key: &dryocbox::PublicKey, argon2_config: &pwhash::Config,
+                                 client_certificate_authorities: &[Vec<u8>])
+                                 -> Result<(), VaultError> {
+        self.connection.create_organization(organization_name, users_data, public_key, argon2_config, client_certificate_authorities).await
+    }
+
+    async fn unlock_vault(&mut self, organization_name: &str, user_name1: &str, user_name2: &str)
+                          -> Result<UnlockVaultResult, VaultError> {
+        if let Some(cache) = &mut self.cache {
+            cache.set_organization(organization_name);
+        }
+        let result = self.connection.unlock_vault(organization_name, user_name1, user_name2).await;
+        if let (Ok(_), Some(cache)) = (&result, &self.cache) {
+            cache.record_online_unlock();
+        }
+        self.read_through(result, |cache| cache.unlock_path(user_name1, user_name2))
+    }
+
+    fn set_token(&mut self, token: &Token) {
+        self.connection.set_token(token);
+        if let Some(cache) = &mut self.cache {
+            cache.owner_directory = cache.next_owner_directory.clone();
+        }
+    }
+
+    async fn revoke_user(&mut self, user_name: &str) -> Result<(), VaultError> {
+        self.connection.revoke_user(user_name).await?;
+        // The revoked user must no longer be able to unlock the vault offline
+        self.invalidate(|cache| cache.user_unlock_paths(user_name));
+        Ok(())
+    }
+
+    async fn revoke_token(&mut self) -> Result<(), VaultError> {
+        self.connection.revoke_token().await
+    }
+
+    async fn new_document(&mut self, encrypted_document: &EncryptedDocument, encrypted_key: &EncryptedDocumentKey)
+                          -> Result<DocumentID, VaultError> {
+        self.connection.new_document(encrypted_document, encrypted_key).await
+    }
+
+    async fn list_documents(&mut self) -> Result<Vec<EncryptedDocumentInfo>, VaultError> {
+        let result = self.connection.list_documents().await;
+        self.read_through(result, LocalCache::document_list_path)
+    }
+
+    async fn list_documents_page(&mut self, page_request: &DocumentPageRequest) -> Result<EncryptedDocumentPage, VaultError> {
+        self.connection.list_documents_page(page_request).await
+    }
+
+    async fn get_document_key(&mut self, document_id: &DocumentID) -> Result<EncryptedDocumentKey, VaultError> {
+        let result = self.connection.get_document_key(document_id).await;
+        self.read_through(result, |cache| cache.document_key_path(document_id))
+    }
+
+    async fn get_document(&mut self, document_id: &DocumentID) -> Result<EncryptedDocument, VaultError> {
+        let result = self.connection.get_document(document_id).await;
+        self.read_through(result, |cache| cache.document_path(document_id))
+    }
+
+    async fn update_document(&mut self, document_id: &DocumentID, encrypted_document: &EncryptedDocument,
+                             expected_version: Option<u64>)
+                             -> Result<u64, VaultError> {
+        self.invalidate_document(document_id);
+        self.connection.update_document(document_id, encrypted_document, expected_version).await
+    }
+
+    async fn delete_document(&mut self, document_id: &DocumentID) -> Result<(), VaultError> {
+        self.invalidate_document(document_id);
+        self.connection.delete_document(document_id).await
+    }
+
+    async fn get_public_key_of_organization(&mut self, organization_name: &str) -> Result<dryocbox::PublicKey, VaultError> {
+        self.connection.get_public_key_of_organization(organization_name).await
+    }
+
+    async fn add_owner(&mut self, document_id: &DocumentID, other_organization_name: &str, encrypted_document_key: &EncryptedDocumentKey,
+                       permission: Permission, expires_at: Option<u64>) -> Result<(), VaultError> {
+        self.connection.add_owner(document_id, other_organization_name, encrypted_document_key, permission, expires_at).await
+    }
+
+    async fn list_share_invitations(&mut self) -> Result<Vec<EncryptedShareInvitation>, VaultError> {
+        self.connection.list_share_invitations().await
+    }
+
+    async fn accept_share_invitation(&mut self, document_id: &DocumentID) -> Result<(), VaultError> {
+        self.connection.accept_share_invitation(document_id).await
+    }
+
+    async fn decline_share_invitation(&mut self, document_id: &DocumentID) -> Result<(), VaultError> {
+        self.connection.decline_share_invitation(document_id).await
+    }
+
+    async fn get_document_owners(&mut self, document_id: &DocumentID) -> Result<Vec<DocumentOwner>, VaultError> {
+        self.connection.get_document_owners(document_id).await
+    }
+
+    async fn remove_owner(&mut self, document_id: &DocumentID, other_organization_name: &str) -> Result<Vec<String>, VaultError> {
+        self.connection.remove_owner(document_id, other_organization_name).await
+    }
+
+    async fn rotate_document_key(&mut self, document_id: &DocumentID, encrypted_document: &EncryptedDocument,
+                                 encrypted_keys: &HashMap<String, EncryptedDocumentKey>, expected_version: u64)
+                                 -> Result<(), VaultError> {
+        self.invalidate_document(document_id);
+        self.connection.rotate_document_key(document_id, encrypted_document, encrypted_keys, expected_version).await
+    }
+
+    async fn new_documents(&mut self, documents: &[(EncryptedDocument, EncryptedDocumentKey)])
+                           -> Result<BatchResults<DocumentID>, VaultError> {
+        self.connection.new_documents(documents).await
+    }
+
+    async fn get_documents(&mut self, document_ids: &[DocumentID])
+                           -> Result<BatchResults<(EncryptedDocument, EncryptedDocumentKey)>, VaultError> {
+        let results = match self.connection.get_documents(document_ids).await {
+            Ok(results) if results.len() == document_ids.len() => results,
+            Err(ServerUnreachable) if self.cache.as_ref().is_some_and(LocalCache::is_usable_offline) =>
+                vec![Err(ServerUnreachable); document_ids.len()],
+            result => return result,
+        };
+
+        // Each document is cached as if it was downloaded alone
+        Ok(
+            document_ids.iter().zip(results)
+                .map(|(document_id, result)| {
+                    let (document, key) = match result {
+                        Ok((document, key)) => (Ok(document), Ok(key)),
+                        Err(error) => (Err(error.clone()), Err(error)),
+                    };
+                    Ok((
+                        self.read_through(document, |cache| cache.document_path(document_id))?,
+                        self.read_through(key, |cache| cache.document_key_path(document_id))?,
+                    ))
+                })
+                .collect()
+        )
+    }
+
+    async fn get_search_index(&mut self) -> Result<Option<EncryptedSearchIndex>, VaultError> {
+        let result = self.connection.get_search_index().await;
+        self.read_through(result, LocalCache::search_index_path)
+    }
+
+    async fn set_search_index(&mut self, search_index: &EncryptedSearchIndex) -> Result<(), VaultError> {
+        self.invalidate(|cache| vec![cache.search_index_path()]);
+        self.connection.set_search_index(search_index).await
+    }
+
+    async fn create_group(&mut self, group_name: &str, members: &[String], public_key: &dryocbox::PublicKey,
+                          encrypted_private_key: &EncryptedGroupPrivateKey, member_keys: &HashMap<String, SymEncryptedData>)
+                          -> Result<(), VaultError> {
+        self.connection.create_group(group_name, members, public_key, encrypted_private_key, member_keys).await
+    }
+
+    async fn list_groups(&mut self) -> Result<Vec<EncryptedGroup>, VaultError> {
+        self.connection.list_groups().await
+    }
+
+    async fn open_group_session(&mut self, group_name: &str) -> Result<EncryptedToken, VaultError> {
+        let encrypted_token = self.connection.open_group_session(group_name).await?;
+        if let Some(cache) = &mut self.cache {
+            cache.next_owner_directory = cache.organization_directory.as_ref()
+                .map(|organization_directory| organization_directory.join("groups").join(group_name));
+        }
+        Ok(encrypted_token)
+    }
+
+    async fn add_group_member(&mut self, group_name: &str, user_name: &str) -> Result<(), VaultError> {
+        self.connection.add_group_member(group_name, user_name).await
+    }
+
+    async fn remove_group_member(&mut self, group_name: &str, user_name: &str) -> Result<(), VaultError> {
+        self.connection.remove_group_member(group_name, user_name).await
+    }
+
+    async fn set_group_member_key(&mut self, group_name: &str, user_name: &str, encrypted_private_key: &SymEncryptedData)
+                                  -> Result<(), VaultError> {
+        self.connection.set_group_member_key(group_name, user_name, encrypted_private_key).await
+    }
+
+    async fn create_share_link(&mut self, document_id: &DocumentID, encrypted_document: &EncryptedDocument, expires_at: u64)
+                               -> Result<ShareLinkID, VaultError> {
+        self.connection.create_share_link(document_id, encrypted_document, expires_at).await
+    }
+
+    async fn open_share_link(&mut self, link_id: &ShareLinkID) -> Result<EncryptedDocument, VaultError> {
+        self.connection.open_share_link(link_id).await
+    }
+
+    async fn subscribe_events(&mut self) -> Result<DocumentEventReceiver, VaultError> {
+        self.connection.subscribe_events().await
+    }
+}
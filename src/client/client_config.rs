@@ -13,6 +13,13 @@ pub const CLIENT_FILES_LOCATION: &str = "client_files";
 pub struct ClientConfig {
     pub server_hostname: String,
     pub server_port: u16,
+    /// PEM client certificate presented to the server, for organizations that use mutual TLS
+    pub client_certificate_file: Option<PathBuf>,
+    /// PEM PKCS#8 private key associated with `client_certificate_file`
+    pub client_certificate_key_file: Option<PathBuf>,
+    /// Enables the local cache of the downloaded documents, which remains readable while the server is unreachable
+    /// for this number of hours after the last time the organization was unlocked online
+    pub offline_cache_validity_hours: Option<u64>,
 }
 
 impl Default for ClientConfig {
@@ -20,6 +27,9 @@ impl Default for ClientConfig {
         Self {
             server_hostname: "localhost".to_string(),
             server_port: 1234,
+            client_certificate_file: None,
+            client_certificate_key_file: None,
+            offline_cache_validity_hours: None,
         }
     }
 }
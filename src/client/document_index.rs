@@ -0,0 +1,192 @@
+//! In-memory index of the documents of a session, by name.
+//!
+//! Finding a document by name requires decrypting the names of the listed documents, and each name is encrypted with a document
+//! key that must first be decrypted with the organization private key. The index keeps the decrypted names and keys, so that
+//! each document key is only decrypted again when the document changes.
+
+use std::collections::HashMap;
+
+use dryoc::dryocsecretbox;
+
+use crate::client::encryptor_decryptor::{OrganizationEncryptorDecryptor, unique_document_id};
+use crate::data::{DocumentID, EncryptedDocumentInfo, EncryptedDocumentKey};
+use crate::error::VaultError;
+
+#[derive(PartialEq, Debug)]
+struct IndexedDocument {
+    name: String,
+    key: dryocsecretbox::Key,
+    /// Version of the document whose name is indexed. The key is rotated along with a new version.
+    version: u64,
+    /// Encrypted key, as listed by the server when the document was indexed
+    encrypted_key: EncryptedDocumentKey,
+}
+
+/// Decrypted names and keys of the documents listed by the server, by document ID
+#[derive(PartialEq, Debug, Default)]
+pub struct DocumentIndex {
+    documents: HashMap<DocumentID, IndexedDocument>,
+}
+
+impl DocumentIndex {
+    /// Updates the index with the documents currently listed by the server. Only the documents that are new, or that changed
+    /// since they were indexed, are decrypted. The documents whose name can not be decrypted are left out.
+    pub fn refresh(&mut self, encryptor_decryptor: &OrganizationEncryptorDecryptor, encrypted_documents_info: &[EncryptedDocumentInfo]) {
+        let mut documents = HashMap::with_capacity(encrypted_documents_info.len());
+        for document_info in encrypted_documents_info {
+            let indexed_document = match self.documents.remove(&document_info.document_id) {
+                Some(indexed_document)
+                if indexed_document.version == document_info.version && indexed_document.encrypted_key == document_info.name_and_key.key =>
+                    indexed_document,
+                _ => match encryptor_decryptor.decrypt_document_name_and_key(&document_info.name_and_key) {
+                    Ok((name, key)) => IndexedDocument {
+                        name,
+                        key,
+                        version: document_info.version,
+                        encrypted_key: document_info.name_and_key.key.clone(),
+                    },
+                    Err(_) => continue,
+                },
+            };
+            documents.insert(document_info.document_id.clone(), indexed_document);
+        }
+        self.documents = documents;
+    }
+
+    /// Returns the ID of the document named `document_name`.
+    /// Fails with AmbiguousDocumentName if several documents have this name.
+    pub fn find_document_id(&self, document_name: &str) -> Result<DocumentID, VaultError> {
+        let document_ids: Vec<DocumentID> = self.documents.iter()
+            .filter(|(_, indexed_document)| indexed_document.name == document_name)
+            .map(|(document_id, _)| document_id.clone())
+            .collect();
+        unique_document_id(&document_ids)
+    }
+
+    /// Maps each document name to the ids of the documents that have this name
+    pub fn document_ids_by_name(&self) -> HashMap<String, Vec<DocumentID>> {
+        let mut document_ids_by_name: HashMap<String, Vec<DocumentID>> = HashMap::new();
+        for (document_id, indexed_document) in &self.documents {
+            document_ids_by_name.entry(indexed_document.name.clone()).or_default().push(document_id.clone());
+        }
+        document_ids_by_name
+    }
+
+    pub fn document_name(&self, document_id: &DocumentID) -> Option<&str> {
+        self.documents.get(document_id).map(|indexed_document| indexed_document.name.as_str())
+    }
+
+    /// Returns the key of a document if the indexed version is `version`, so that the key is known to be up to date
+    pub fn document_key(&self, document_id: &DocumentID, version: u64) -> Option<&dryocsecretbox::Key> {
+        self.documents.get(document_id)
+            .filter(|indexed_document| indexed_document.version == version)
+            .map(|indexed_document| &indexed_document.key)
+    }
+
+    /// Returns the name and key of a document if the indexed version is `version`
+    pub fn document_name_and_key(&self, document_id: &DocumentID, version: u64) -> Option<(&str, &dryocsecretbox::Key)> {
+        self.documents.get(document_id)
+            .filter(|indexed_document| indexed_document.version == version)
+            .map(|indexed_document| (indexed_document.name.as_str(), &indexed_document.key))
+    }
+
+    /// Returns the indexed key of a document, whatever its version. Right after a refresh, it is the current key of the document.
+    pub fn latest_document_key(&self, document_id: &DocumentID) -> Option<&dryocsecretbox::Key> {
+        self.documents.get(document_id).map(|indexed_document| &indexed_document.key)
+    }
+
+    /// Returns the indexed version of a document
+    pub fn document_version(&self, document_id: &DocumentID) -> Option<u64> {
+        self.documents.get(document_id).map(|indexed_document| indexed_document.version)
+    }
+
+    /// Records a new version of a document stored by this session, based on `previous_version`. Its key is only known to be
+    /// unchanged if the indexed version is `previous_version`, otherwise the document is decrypted again at the next refresh.
+    pub fn update_document(&mut self, document_id: &DocumentID, name: &str, previous_version: u64, version: u64) {
+        match self.documents.get_mut(document_id) {
+            Some(indexed_document) if indexed_document.version == previous_version => {
+                indexed_document.name = name.to_string();
+                indexed_document.version = version;
+            }
+            _ => self.invalidate(document_id),
+        }
+    }
+
+    /// Removes a document that was deleted, or whose key was rotated
+    pub fn invalidate(&mut self, document_id: &DocumentID) {
+        self.documents.remove(document_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dryoc::dryocbox;
+
+    use crate::data::{Document, EncryptedDocumentNameAndKey};
+    use crate::error::VaultError::{AmbiguousDocumentName, DocumentNotFound};
+
+    use super::*;
+
+    fn listed_document(encryptor_decryptor: &OrganizationEncryptorDecryptor, id: u8, name: &str, version: u64) -> EncryptedDocumentInfo {
+        let (encrypted_document, encrypted_key) = encryptor_decryptor
+            .generate_document_key_and_encrypt_document(&Document { name: name.to_string(), content: String::new() })
+            .unwrap();
+        EncryptedDocumentInfo {
+            document_id: vec![id],
+            name_and_key: EncryptedDocumentNameAndKey { data: encrypted_document.name, key: encrypted_key },
+            metadata: None,
+            size: 0,
+            created_at: 0,
+            modified_at: 0,
+            version,
+        }
+    }
+
+    #[test]
+    fn incremental_refresh() {
+        let encryptor_decryptor = OrganizationEncryptorDecryptor::new(dryocbox::KeyPair::gen());
+        let mut documents = vec![
+            listed_document(&encryptor_decryptor, 1, "first", 0),
+            listed_document(&encryptor_decryptor, 2, "second", 0),
+            listed_document(&encryptor_decryptor, 3, "first", 0),
+        ];
+        let mut index = DocumentIndex::default();
+        index.refresh(&encryptor_decryptor, &documents);
+
+        assert_eq!(index.find_document_id("second"), Ok(vec![2]));
+        assert_eq!(index.find_document_id("first"), Err(AmbiguousDocumentName));
+        assert_eq!(index.find_document_id("third"), Err(DocumentNotFound));
+        assert_eq!(index.document_ids_by_name()["second"], vec![vec![2]]);
+        assert!(index.document_key(&vec![2], 0).is_some());
+        assert!(index.document_key(&vec![2], 1).is_none());
+        assert_eq!(index.document_name_and_key(&vec![2], 0).map(|(name, _)| name), Some("second"));
+        assert!(index.document_name_and_key(&vec![2], 1).is_none());
+        assert_eq!(index.latest_document_key(&vec![2]), index.document_key(&vec![2], 0));
+
+        // An unchanged document is not decrypted again, so a name that can not be decrypted any more is still indexed
+        let other_encryptor_decryptor = OrganizationEncryptorDecryptor::new(dryocbox::KeyPair::gen());
+        documents.remove(2);
+        documents.push(listed_document(&encryptor_decryptor, 4, "fourth", 0));
+        index.refresh(&other_encryptor_decryptor, &documents);
+        assert_eq!(index.find_document_id("first"), Ok(vec![1]));
+        assert_eq!(index.find_document_id("second"), Ok(vec![2]));
+        assert_eq!(index.find_document_id("fourth"), Err(DocumentNotFound));
+
+        // A new version is decrypted again
+        documents[1] = listed_document(&encryptor_decryptor, 2, "renamed", 1);
+        index.refresh(&encryptor_decryptor, &documents);
+        assert_eq!(index.find_document_id("second"), Err(DocumentNotFound));
+        assert_eq!(index.find_document_id("renamed"), Ok(vec![2]));
+        assert_eq!(index.find_document_id("fourth"), Ok(vec![4]));
+
+        // A version stored by the session only keeps the key if it is based on the indexed version
+        assert_eq!(index.document_version(&vec![2]), Some(1));
+        index.update_document(&vec![2], "moved", 1, 2);
+        assert_eq!(index.document_name(&vec![2]), Some("moved"));
+        assert!(index.document_key(&vec![2], 2).is_some());
+        index.update_document(&vec![2], "moved again", 1, 3);
+        assert_eq!(index.document_name(&vec![2]), None);
+        index.invalidate(&vec![4]);
+        assert_eq!(index.find_document_id("fourth"), Err(DocumentNotFound));
+    }
+}
@@ -0,0 +1,119 @@
+//! Folders of the documents.
+//!
+//! The name of a document is its path, e.g. `legal/contracts/2026/acme.pdf`. As the name is encrypted, the server does not
+//! know the folders: they only exist as prefixes of the document paths, so a folder is empty only when it does not exist.
+//! The root folder is the empty path.
+
+use data_encoding::BASE32;
+
+use crate::data::DocumentID;
+
+pub const PATH_SEPARATOR: char = '/';
+
+/// Removes the empty components of a path, e.g. the leading, trailing and repeated separators
+pub fn normalize_path(path: &str) -> String {
+    path.split(PATH_SEPARATOR)
+        .filter(|component| !component.is_empty())
+        .collect::<Vec<_>>()
+        .join(&PATH_SEPARATOR.to_string())
+}
+
+/// Resolves a path entered relatively to `current_folder`, as in a shell: a path that starts with the separator is absolute,
+/// and `..` designates the parent folder
+pub fn resolve_path(current_folder: &str, path: &str) -> String {
+    let mut components: Vec<&str> = if path.starts_with(PATH_SEPARATOR) {
+        Vec::new()
+    } else {
+        current_folder.split(PATH_SEPARATOR).filter(|component| !component.is_empty()).collect()
+    };
+
+    for component in path.split(PATH_SEPARATOR) {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            component => components.push(component),
+        }
+    }
+    components.join(&PATH_SEPARATOR.to_string())
+}
+
+/// Returns the ID of a document entered by the user, if it designates the document by its ID, encoded in base32 and
+/// prefixed with '#', instead of its name
+pub fn entered_document_id(entered_document: &str) -> Option<DocumentID> {
+    BASE32.decode(entered_document.strip_prefix('#')?.as_bytes()).ok()
+}
+
+/// Joins a folder and a path relative to it
+pub fn join_path(folder: &str, relative_path: &str) -> String {
+    normalize_path(&format!("{folder}{PATH_SEPARATOR}{relative_path}"))
+}
+
+/// Returns whether a relative path stays inside the folder it is relative to once used as a local path, i.e. it is not empty
+/// and none of its components is empty, `.` or `..`. The names of shared documents are chosen by other organizations,
+/// so they must be checked before they are written to the local file system.
+pub fn is_contained_path(path: &str) -> bool {
+    path.split(PATH_SEPARATOR).all(|component| !matches!(component, "" | "." | ".."))
+}
+
+/// Returns the path of `path` relative to `folder`, or `None` if `path` is not inside `folder` or one of its subfolders
+pub fn relative_path<'a>(path: &'a str, folder: &str) -> Option<&'a str> {
+    let folder = folder.trim_matches(PATH_SEPARATOR);
+    let path = path.trim_start_matches(PATH_SEPARATOR);
+    if folder.is_empty() {
+        return Some(path);
+    }
+    path.strip_prefix(folder)?
+        .strip_prefix(PATH_SEPARATOR)
+        .map(|relative_path| relative_path.trim_start_matches(PATH_SEPARATOR))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize() {
+        assert_eq!(normalize_path("/legal//contracts/"), "legal/contracts");
+        assert_eq!(normalize_path("/"), "");
+    }
+
+    #[test]
+    fn resolve() {
+        assert_eq!(resolve_path("legal", "contracts/2026"), "legal/contracts/2026");
+        assert_eq!(resolve_path("legal/contracts", ".."), "legal");
+        assert_eq!(resolve_path("legal/contracts", "../../.."), "");
+        assert_eq!(resolve_path("legal/contracts", "/finance"), "finance");
+        assert_eq!(resolve_path("", "./acme.pdf"), "acme.pdf");
+    }
+
+    #[test]
+    fn entered_id() {
+        assert_eq!(entered_document_id(&format!("#{}", BASE32.encode(&[1, 2, 3]))), Some(vec![1, 2, 3]));
+        assert_eq!(entered_document_id(&BASE32.encode(&[1, 2, 3])), None);
+        assert_eq!(entered_document_id("#legal/acme.pdf"), None);
+        assert_eq!(entered_document_id("legal/acme.pdf"), None);
+    }
+
+    #[test]
+    fn relative() {
+        assert_eq!(relative_path("legal/contracts/acme.pdf", "legal"), Some("contracts/acme.pdf"));
+        assert_eq!(relative_path("legal/contracts/acme.pdf", "/legal/contracts/"), Some("acme.pdf"));
+        assert_eq!(relative_path("legal/contracts/acme.pdf", ""), Some("legal/contracts/acme.pdf"));
+        assert_eq!(relative_path("legalese/acme.pdf", "legal"), None);
+        assert_eq!(relative_path("legal", "legal"), None);
+    }
+
+    #[test]
+    fn contained() {
+        assert!(is_contained_path("contracts/acme.pdf"));
+        assert!(is_contained_path("..acme.pdf"));
+        assert!(!is_contained_path(""));
+        assert!(!is_contained_path("contracts//acme.pdf"));
+        assert!(!is_contained_path("contracts/"));
+        assert!(!is_contained_path("./acme.pdf"));
+        assert!(!is_contained_path("../acme.pdf"));
+        assert!(!is_contained_path("contracts/../../acme.pdf"));
+    }
+}
@@ -1,15 +1,53 @@
 //! Provides functions that are used once the client has recovered its private key, to access and manipulate the documents
 
+use std::collections::HashMap;
+use std::time::{Duration, UNIX_EPOCH};
+
 use dryoc::{dryocbox, dryocsecretbox};
 use dryoc::dryocbox::DryocBox;
 use dryoc::dryocsecretbox::NewByteArray;
 
-use crate::data::{Document, DocumentID, EncryptedDocumentKey, EncryptedDocumentNameAndKey, EncryptedToken, Token};
+use crate::client::search_index::SearchIndex;
+use crate::data::{Document, DocumentID, DocumentInfo, DocumentMetadata, EncryptedDocumentInfo, EncryptedDocumentKey, EncryptedDocumentNameAndKey, EncryptedGroupPrivateKey, EncryptedSearchIndex, EncryptedToken, Token};
 use crate::data::EncryptedDocument;
 use crate::error::VaultError;
-use crate::error::VaultError::CryptographyError;
+use crate::error::VaultError::{AmbiguousDocumentName, CryptographyError, DocumentNotFound};
 use crate::symmetric_encryption_helper::SymEncryptedData;
 
+/// Returns the id of the only document of `document_ids`, the documents that have a given name
+pub fn unique_document_id(document_ids: &[DocumentID]) -> Result<DocumentID, VaultError> {
+    match document_ids {
+        [] => Err(DocumentNotFound),
+        [document_id] => Ok(document_id.clone()),
+        _ => Err(AmbiguousDocumentName),
+    }
+}
+
+/// Builds the info of a listed document whose name and key are already decrypted, by decrypting its metadata with the key
+pub fn decrypt_document_info_with_key(encrypted_document_info: &EncryptedDocumentInfo, name: &str, document_key: &dryocsecretbox::Key)
+                                      -> Result<DocumentInfo, VaultError> {
+    let metadata = match &encrypted_document_info.metadata {
+        Some(encrypted_metadata) => DocumentMetadata::decrypt(encrypted_metadata, document_key)?,
+        None => DocumentMetadata::default(),
+    };
+
+    Ok(DocumentInfo {
+        id: encrypted_document_info.document_id.clone(),
+        name: name.to_string(),
+        metadata,
+        size: encrypted_document_info.size,
+        created_at: UNIX_EPOCH + Duration::from_secs(encrypted_document_info.created_at),
+        modified_at: UNIX_EPOCH + Duration::from_secs(encrypted_document_info.modified_at),
+        version: encrypted_document_info.version,
+    })
+}
+
+/// Encrypts a document key with the public key of an organization
+pub fn encrypt_document_key_for_organization(document_key: &dryocsecretbox::Key, public_key: &dryocbox::PublicKey)
+                                             -> Result<EncryptedDocumentKey, VaultError> {
+    DryocBox::seal_to_vecbox(document_key, public_key).map_err(|_| CryptographyError)
+}
+
 /// Owns the organization key pair. Performs encryption / decryption of the data coming from / going to the server.
 #[derive(PartialEq, Debug)]
 pub struct OrganizationEncryptorDecryptor {
@@ -21,16 +59,10 @@ impl OrganizationEncryptorDecryptor {
         OrganizationEncryptorDecryptor { key_pair }
     }
 
-    /// Using a list of document ids and corresponding encrypted document names coming from the server,
-    /// searches for the document id of the document named `document_name`
-    pub fn find_document_id_from_name(&self, encrypted_document_names: &Vec<(DocumentID, EncryptedDocumentNameAndKey)>, document_name: &str) -> Option<DocumentID> {
-        encrypted_document_names
-            .iter()
-            .filter(|(.., name_and_key)|
-                self.decrypt_document_name(&name_and_key.data, &name_and_key.key) == Ok(document_name.to_string()))
-            .map(|(id, ..)| id)
-            .cloned()
-            .next()
+    /// Decrypts the name and the metadata of a document listed by the server
+    pub fn decrypt_document_info(&self, encrypted_document_info: &EncryptedDocumentInfo) -> Result<DocumentInfo, VaultError> {
+        let (name, document_key) = self.decrypt_document_name_and_key(&encrypted_document_info.name_and_key)?;
+        decrypt_document_info_with_key(encrypted_document_info, &name, &document_key)
     }
 
     /// Chooses a random document key, encrypts the document with the document key and encrypts the document key with the organization public key
@@ -49,6 +81,14 @@ impl OrganizationEncryptorDecryptor {
         String::from_utf8(encrypted_name.decrypt(&document_key)?).map_err(|_| CryptographyError)
     }
 
+    /// Decrypts the name of a document listed by the server, along with its key
+    pub fn decrypt_document_name_and_key(&self, name_and_key: &EncryptedDocumentNameAndKey)
+                                         -> Result<(String, dryocsecretbox::Key), VaultError> {
+        let document_key = self.decrypt_document_key(&name_and_key.key)?;
+        let name = String::from_utf8(name_and_key.data.decrypt(&document_key)?).map_err(|_| CryptographyError)?;
+        Ok((name, document_key))
+    }
+
     pub fn decrypt_document(&self, encrypted_document: &EncryptedDocument, encrypted_document_key: &EncryptedDocumentKey)
                             -> Result<Document, VaultError> {
         let document_key = self.decrypt_document_key(encrypted_document_key)?;
@@ -62,12 +102,69 @@ impl OrganizationEncryptorDecryptor {
         Ok(document.encrypt(&document_key))
     }
 
+    pub fn encrypt_metadata_with_key(&self, metadata: &DocumentMetadata, encrypted_document_key: &EncryptedDocumentKey)
+                                     -> Result<SymEncryptedData, VaultError> {
+        let document_key = self.decrypt_document_key(encrypted_document_key)?;
+        metadata.encrypt(&document_key)
+    }
+
     /// Decrypts a document key and encrypts it with the public key of an other organization
     pub fn encrypt_document_key_for_other_organization(&self, encrypted_document_key: &EncryptedDocumentKey,
                                                        other_organization_public_key: &dryocbox::PublicKey)
                                                        -> Result<EncryptedDocumentKey, VaultError> {
         let document_key = self.decrypt_document_key(encrypted_document_key)?;
-        DryocBox::seal_to_vecbox(&document_key, other_organization_public_key).map_err(|_| CryptographyError)
+        encrypt_document_key_for_organization(&document_key, other_organization_public_key)
+    }
+
+    /// Encrypts the search index with a new random key, which is encrypted with the organization public key
+    pub fn encrypt_search_index(&self, search_index: &SearchIndex) -> Result<EncryptedSearchIndex, VaultError> {
+        let index_key = dryocsecretbox::Key::gen();
+        let encrypted_index_key = DryocBox::seal_to_vecbox(&index_key, &self.key_pair.public_key)
+            .map_err(|_| CryptographyError)?;
+        let serialized_search_index = serde_json::to_vec(search_index).map_err(|_| CryptographyError)?;
+
+        Ok(EncryptedSearchIndex { key: encrypted_index_key, data: SymEncryptedData::encrypt(&serialized_search_index, &index_key) })
+    }
+
+    pub fn decrypt_search_index(&self, encrypted_search_index: &EncryptedSearchIndex) -> Result<SearchIndex, VaultError> {
+        let index_key = self.decrypt_document_key(&encrypted_search_index.key)?;
+        serde_json::from_slice(&encrypted_search_index.data.decrypt(&index_key)?).map_err(|_| CryptographyError)
+    }
+
+    /// Chooses a new random document key, encrypts the document with it, and encrypts it for each organization of `owners_public_keys`
+    pub fn rotate_document_key(&self, encrypted_document: &EncryptedDocument, encrypted_document_key: &EncryptedDocumentKey,
+                               owners_public_keys: &HashMap<String, dryocbox::PublicKey>)
+                               -> Result<(EncryptedDocument, HashMap<String, EncryptedDocumentKey>), VaultError> {
+        let document_key = self.decrypt_document_key(encrypted_document_key)?;
+        let document = encrypted_document.decrypt(&document_key)?;
+        let metadata = encrypted_document.metadata.as_ref()
+            .map(|encrypted_metadata| DocumentMetadata::decrypt(encrypted_metadata, &document_key))
+            .transpose()?;
+
+        let new_document_key = dryocsecretbox::Key::gen();
+        let mut new_encrypted_document = document.encrypt(&new_document_key);
+        new_encrypted_document.metadata = metadata.map(|metadata| metadata.encrypt(&new_document_key)).transpose()?;
+        let new_encrypted_keys = owners_public_keys.iter()
+            .map(|(owner, public_key)| Ok((
+                owner.clone(),
+                DryocBox::seal_to_vecbox(&new_document_key, public_key).map_err(|_| CryptographyError)?,
+            )))
+            .collect::<Result<_, VaultError>>()?;
+
+        Ok((new_encrypted_document, new_encrypted_keys))
+    }
+
+    /// Encrypts the private key of a group of the organization with the organization public key
+    pub fn encrypt_group_private_key(&self, group_private_key: &dryocbox::SecretKey) -> Result<EncryptedGroupPrivateKey, VaultError> {
+        DryocBox::seal_to_vecbox(group_private_key, &self.key_pair.public_key).map_err(|_| CryptographyError)
+    }
+
+    pub fn decrypt_group_private_key(&self, encrypted_group_private_key: &EncryptedGroupPrivateKey) -> Result<dryocbox::SecretKey, VaultError> {
+        let group_private_key_vec = encrypted_group_private_key.unseal_to_vec(&self.key_pair).map_err(|_| CryptographyError)?;
+
+        Ok(
+            <[u8; dryoc::constants::CRYPTO_BOX_SECRETKEYBYTES]>::try_from(group_private_key_vec).map_err(|_| CryptographyError)?.into()
+        )
     }
 
     pub fn decrypt_token(&self, encrypted_token: &EncryptedToken) -> Result<Token, VaultError> {
@@ -86,6 +183,8 @@ impl OrganizationEncryptorDecryptor {
 
 #[cfg(test)]
 mod tests {
+    use crate::data::EncryptedDocumentNameAndKey;
+
     use super::*;
 
     fn test_document() -> Document {
@@ -125,6 +224,31 @@ mod tests {
         assert_eq!(decrypted_name, test_document().name);
     }
 
+    #[test]
+    fn document_info_decryption() {
+        let encryptor_decryptor = mock_encryptor_decryptor();
+        let metadata = DocumentMetadata { content_type: Some("text/plain".to_string()), original_filename: Some("test.txt".to_string()) };
+
+        let (encrypted_document, encrypted_key) =
+            encryptor_decryptor.generate_document_key_and_encrypt_document(&test_document()).unwrap();
+        let encrypted_document_info = EncryptedDocumentInfo {
+            document_id: vec![1],
+            name_and_key: EncryptedDocumentNameAndKey { data: encrypted_document.name, key: encrypted_key.clone() },
+            metadata: Some(encryptor_decryptor.encrypt_metadata_with_key(&metadata, &encrypted_key).unwrap()),
+            size: 42,
+            created_at: 1,
+            modified_at: 2,
+            version: 3,
+        };
+        let document_info = encryptor_decryptor.decrypt_document_info(&encrypted_document_info).unwrap();
+
+        assert_eq!(document_info.name, test_document().name);
+        assert_eq!(document_info.metadata, metadata);
+        assert_eq!(document_info.size, 42);
+        assert_eq!(document_info.modified_at, UNIX_EPOCH + Duration::from_secs(2));
+        assert_eq!(document_info.version, 3);
+    }
+
     #[test]
     fn encryption_then_update_then_decryption() {
         let encryptor_decryptor = mock_encryptor_decryptor();
@@ -158,6 +282,41 @@ mod tests {
         assert_eq!(decrypted_document, test_document())
     }
 
+    #[test]
+    fn search_index_encryption_then_decryption() {
+        let encryptor_decryptor = mock_encryptor_decryptor();
+        let mut search_index = SearchIndex::default();
+        search_index.add_document(&vec![1], 0, &test_document());
+
+        let encrypted_search_index = encryptor_decryptor.encrypt_search_index(&search_index).unwrap();
+
+        assert_eq!(encryptor_decryptor.decrypt_search_index(&encrypted_search_index).unwrap(), search_index);
+        assert!(mock_encryptor_decryptor().decrypt_search_index(&encrypted_search_index).is_err());
+    }
+
+    #[test]
+    fn document_key_rotation() {
+        let encryptor_decryptor1 = mock_encryptor_decryptor();
+        let encryptor_decryptor2 = mock_encryptor_decryptor();
+        let metadata = DocumentMetadata { content_type: Some("text/plain".to_string()), original_filename: None };
+
+        let (mut encrypted_document, encrypted_key) =
+            encryptor_decryptor1.generate_document_key_and_encrypt_document(&test_document()).unwrap();
+        encrypted_document.metadata = Some(encryptor_decryptor1.encrypt_metadata_with_key(&metadata, &encrypted_key).unwrap());
+        let owners_public_keys = HashMap::from([
+            ("first".to_string(), encryptor_decryptor1.key_pair.public_key.clone()),
+            ("second".to_string(), encryptor_decryptor2.key_pair.public_key.clone()),
+        ]);
+        let (new_encrypted_document, new_encrypted_keys) =
+            encryptor_decryptor1.rotate_document_key(&encrypted_document, &encrypted_key, &owners_public_keys).unwrap();
+
+        assert_eq!(encryptor_decryptor2.decrypt_document(&new_encrypted_document, &new_encrypted_keys["second"]).unwrap(), test_document());
+        let new_document_key = encryptor_decryptor1.decrypt_document_key(&new_encrypted_keys["first"]).unwrap();
+        assert_eq!(DocumentMetadata::decrypt(new_encrypted_document.metadata.as_ref().unwrap(), &new_document_key).unwrap(), metadata);
+        // The previous key can not decrypt the new version
+        assert!(encryptor_decryptor1.decrypt_document(&new_encrypted_document, &encrypted_key).is_err());
+    }
+
     #[test]
     fn decrypt_token() {
         let encryptor_decryptor = mock_encryptor_decryptor();
@@ -1,136 +1,618 @@
 //! HTTPS interface used by the client to communicate with the server
+//!
+//! `AsyncHttpConnection` sends the requests asynchronously. `HttpConnection` is its blocking counterpart.
 
 use std::collections::HashMap;
+use std::fs;
 use std::path::PathBuf;
+use std::sync::Arc;
 
+use async_trait::async_trait;
 use dryoc::dryocbox::PublicKey;
 use dryoc::pwhash;
 use reqwest;
-use reqwest::blocking::{Client, Response};
+use reqwest::{Client, Method, RequestBuilder, Response};
+use reqwest::{Identity, StatusCode};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use tokio::sync::mpsc;
 
 use crate::client::client_config::{CLIENT_FILES_LOCATION, ClientConfig};
-use crate::data::{DocumentID, EncryptedDocument, EncryptedDocumentKey, EncryptedDocumentNameAndKey, EncryptedToken, Token, UserShare};
+use crate::data::{BatchResults, DocumentEvent, DocumentID, DocumentOwner, DocumentPageRequest, EncryptedDocumentPage, EncryptedDocument, EncryptedDocumentInfo, EncryptedDocumentKey, EncryptedGroup, EncryptedGroupPrivateKey, EncryptedSearchIndex, EncryptedShareInvitation, EncryptedToken, Permission, ShareLinkID, Token, UserShare};
 use crate::error::VaultError;
-use crate::error::VaultError::ServerError;
-use crate::server::http_server::{ADD_OWNER_ENDPOINT, CREATE_ORGANIZATION_ENDPOINT, DELETE_DOCUMENT_ENDPOINT, GET_DOCUMENT_ENDPOINT, GET_DOCUMENT_KEY_ENDPOINT, GET_PUBLIC_KEY_ENDPOINT, LIST_DOCUMENTS_ENDPOINT, NEW_DOCUMENT_ENDPOINT, REVOKE_TOKEN_ENDPOINT, REVOKE_USER_ENDPOINT, UNLOCK_VAULT_ENDPOINT, UPDATE_DOCUMENT_ENDPOINT};
-use crate::server_connection::ServerConnection;
+use crate::error::VaultError::{FileError, IncompatibleServerVersion, InputError, ServerError, ServerUnreachable, SessionExpired, VersionConflict};
+use crate::http_api::*;
+use crate::server_connection::{AsyncServerConnection, BlockingServerConnection, DocumentEventReceiver};
+use crate::symmetric_encryption_helper::SymEncryptedData;
 use crate::utils;
 
 pub const ROOT_CERTIFICATE_FILE_NAME: &str = "root_certificate.pem";
 
-fn http_client_configured_for_tls() -> Client {
+/// Reads the root certificate of the server from the client files. Fails with a FileError if it is missing or invalid.
+fn root_certificate_from_client_files() -> Result<reqwest::Certificate, VaultError> {
     let root_certificate_path = PathBuf::from(CLIENT_FILES_LOCATION).join(ROOT_CERTIFICATE_FILE_NAME);
-    let der_certificate = utils::get_certificate_der_from_pem_file(&root_certificate_path)
-        .expect("Could not read client certificate");
-    let certificate = reqwest::Certificate::from_der(&der_certificate)
-        .expect("Could not read client certificate");
-    Client::builder()
-        .add_root_certificate(certificate)
-        .tls_built_in_root_certs(false)// As we use our own CA, it is useless to trust built in CAs
-        .build().expect("Could not create http client")
+    let der_certificate = utils::get_certificate_der_from_pem_file(&root_certificate_path).map_err(|_| FileError)?;
+    reqwest::Certificate::from_der(&der_certificate).map_err(|_| FileError)
 }
 
-#[derive(Debug)]
-pub struct HttpConnection {
+/// Builds a client that only trusts `root_certificate` if any, or else the built-in root certificates
+fn http_client_configured_for_tls(root_certificate: Option<reqwest::Certificate>, client_identity: Option<Identity>)
+                                  -> Result<Client, VaultError> {
+    let client_builder = match root_certificate {
+        // As we use our own CA, it is useless to trust built in CAs
+        Some(root_certificate) => Client::builder().add_root_certificate(root_certificate).tls_built_in_root_certs(false),
+        None => Client::builder(),
+    };
+
+    match client_identity {
+        Some(client_identity) => client_builder.identity(client_identity),
+        None => client_builder,
+    }
+        .build().map_err(|_| InputError)
+}
+
+/// Reads the client certificate and key files specified in the client config, if any.
+/// Fails with an InputError if the certificate is configured without its key, or if the files can not be read.
+fn client_identity_from_config(client_config: &ClientConfig) -> Result<Option<Identity>, VaultError> {
+    let Some(certificate_path) = client_config.client_certificate_file.as_ref() else {
+        return Ok(None);
+    };
+    let key_path = client_config.client_certificate_key_file.as_ref().ok_or(InputError)?;
+
+    let certificate_pem = fs::read(certificate_path).map_err(|_| InputError)?;
+    let key_pem = fs::read(key_path).map_err(|_| InputError)?;
+    Identity::from_pkcs8_pem(&certificate_pem, &key_pem).map(Some).map_err(|_| InputError)
+}
+
+/// Blocking HTTPS connection to the server
+pub type HttpConnection = BlockingServerConnection<AsyncHttpConnection>;
+
+impl HttpConnection {
+    /// Creates a connection that presents the client certificate specified in the client config, if any.
+    ///
+    /// Returns an IncompatibleServerVersion error if the server does not support the protocol version of this client.
+    pub fn new(server_port: u16) -> Result<HttpConnection, VaultError> {
+        let runtime = Arc::new(Self::create_runtime());
+        let connection = runtime.block_on(AsyncHttpConnection::new(server_port))?;
+        Ok(Self::with_runtime(connection, runtime))
+    }
+
+    /// Creates a connection that presents the given PEM client certificate and PKCS#8 key to the server
+    pub fn with_client_identity(server_port: u16, certificate_pem: &[u8], key_pem: &[u8]) -> Result<HttpConnection, VaultError> {
+        let runtime = Arc::new(Self::create_runtime());
+        let connection = runtime.block_on(AsyncHttpConnection::with_client_identity(server_port, certificate_pem, key_pem))?;
+        Ok(Self::with_runtime(connection, runtime))
+    }
+
+    /// Creates a connection to the server of a share link, without client certificate.
+    /// The server is authenticated with the given PEM root certificate, or else with the built-in root certificates.
+    pub fn with_server_address(server_hostname: &str, server_port: u16, root_certificate_pem: Option<&[u8]>) -> Result<HttpConnection, VaultError> {
+        let runtime = Arc::new(Self::create_runtime());
+        let connection = runtime.block_on(AsyncHttpConnection::with_server_address(server_hostname, server_port, root_certificate_pem))?;
+        Ok(Self::with_runtime(connection, runtime))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AsyncHttpConnection {
     server_url: reqwest::Url,
     http_client: Client,
+    token: Option<Token>,
 }
 
-impl HttpConnection {
-    pub fn new(server_port: u16) -> HttpConnection {
-        let server_hostname = ClientConfig::get().server_hostname;
-        let mut server_url = reqwest::Url::parse(&format!("https://{server_hostname}")).expect("Could not parse server hostname");
-        server_url.set_port(Some(server_port)).expect("Could not set server port");
+impl AsyncHttpConnection {
+    /// Creates a connection that presents the client certificate specified in the client config, if any.
+    ///
+    /// Returns an IncompatibleServerVersion error if the server does not support the protocol version of this client.
+    pub async fn new(server_port: u16) -> Result<AsyncHttpConnection, VaultError> {
+        let connection = Self::new_without_version_check(server_port)?;
+        connection.check_server_version().await?;
+        Ok(connection)
+    }
+
+    /// Creates a connection like `new`, without contacting the server, so that it can be created while the server is unreachable
+    pub fn new_without_version_check(server_port: u16) -> Result<AsyncHttpConnection, VaultError> {
+        let client_config = ClientConfig::get();
+        let client_identity = client_identity_from_config(&client_config)?;
+        Self::without_version_check(&client_config.server_hostname, server_port, Some(root_certificate_from_client_files()?), client_identity)
+    }
 
-        HttpConnection {
+    /// Creates a connection that presents the given PEM client certificate and PKCS#8 key to the server
+    pub async fn with_client_identity(server_port: u16, certificate_pem: &[u8], key_pem: &[u8]) -> Result<AsyncHttpConnection, VaultError> {
+        let client_identity = Identity::from_pkcs8_pem(certificate_pem, key_pem).map_err(|_| InputError)?;
+        let connection = Self::without_version_check(&ClientConfig::get().server_hostname, server_port,
+                                                     Some(root_certificate_from_client_files()?), Some(client_identity))?;
+        connection.check_server_version().await?;
+        Ok(connection)
+    }
+
+    /// Creates a connection to the server of a share link, without client certificate. The recipient of a share link may
+    /// have no client files, so the server is authenticated with the given PEM root certificate, or else with the built-in
+    /// root certificates.
+    pub async fn with_server_address(server_hostname: &str, server_port: u16, root_certificate_pem: Option<&[u8]>)
+                                     -> Result<AsyncHttpConnection, VaultError> {
+        let root_certificate = root_certificate_pem
+            .map(|root_certificate_pem| reqwest::Certificate::from_pem(root_certificate_pem).map_err(|_| InputError))
+            .transpose()?;
+        let connection = Self::without_version_check(server_hostname, server_port, root_certificate, None)?;
+        connection.check_server_version().await?;
+        Ok(connection)
+    }
+
+    fn without_version_check(server_hostname: &str, server_port: u16, root_certificate: Option<reqwest::Certificate>,
+                             client_identity: Option<Identity>) -> Result<AsyncHttpConnection, VaultError> {
+        let mut server_url = reqwest::Url::parse(&format!("https://{server_hostname}")).map_err(|_| InputError)?;
+        server_url.set_port(Some(server_port)).map_err(|_| InputError)?;
+
+        Ok(AsyncHttpConnection {
             server_url,
-            http_client: http_client_configured_for_tls(),
+            http_client: http_client_configured_for_tls(root_certificate, client_identity)?,
+            token: None,
+        })
+    }
+
+    async fn check_server_version(&self) -> Result<(), VaultError> {
+        let mut url = self.server_url.clone();
+        url.set_path(VERSION_ENDPOINT);
+        let response = self.http_client.get(url).send().await.map_err(send_error)?;
+
+        // A server that predates API versioning does not know the version endpoint
+        if response.status() == StatusCode::NOT_FOUND {
+            return check_protocol_versions(None);
+        }
+        if !response.status().is_success() {
+            return Err(ServerError);
         }
+
+        let version: VersionResponse = response.json().await.map_err(|_| ServerError)?;
+        check_protocol_versions(Some(version.protocol_versions))
+    }
+
+    /// Builds a POST request to `endpoint`, authenticated with the token if one is set
+    fn post(&self, endpoint: &str) -> RequestBuilder {
+        self.request(Method::POST, endpoint)
     }
 
-    fn send_payload_and_get_response<A: Serialize>(&self, payload: A, endpoint: &str) -> Result<Response, VaultError> {
+    fn request(&self, method: Method, endpoint: &str) -> RequestBuilder {
         let mut url = self.server_url.clone();
         url.set_path(endpoint);
-        let response = self.http_client.post(url).json(&payload).send().map_err(|_| ServerError)?;
+        let request = self.http_client.request(method, url);
+        match &self.token {
+            Some(token) => request.bearer_auth(TOKEN_ENCODING.encode(token)),
+            None => request,
+        }
+    }
+
+    async fn send_request(request: RequestBuilder) -> Result<Response, VaultError> {
+        let response = request.send().await.map_err(send_error)?;
         if response.status().is_success() {
             Ok(response)
+        } else if response.status() == StatusCode::CONFLICT {
+            Err(VersionConflict)
+        } else if response.status() == StatusCode::UNAUTHORIZED {
+            Err(SessionExpired)
         } else {
             Err(ServerError)
         }
     }
 
-    fn send_payload<A: Serialize>(&self, payload: A, endpoint: &str) -> Result<(), VaultError> {
-        self.send_payload_and_get_response(payload, endpoint)?;
+    async fn send_payload_and_get_response<A: Serialize>(&self, payload: A, endpoint: &str) -> Result<Response, VaultError> {
+        Self::send_request(self.post(endpoint).json(&payload)).await
+    }
+
+    async fn send_payload<A: Serialize>(&self, payload: A, endpoint: &str) -> Result<(), VaultError> {
+        self.send_payload_and_get_response(payload, endpoint).await?;
         Ok(())
     }
 
-    fn send_payload_and_deserialize_json_response<A: Serialize, B: DeserializeOwned>(&self, payload: A, endpoint: &str) -> Result<B, VaultError> {
-        self.send_payload_and_get_response(payload, endpoint)?.json().map_err(|_| ServerError)
+    async fn send_payload_and_deserialize_json_response<A: Serialize, B: DeserializeOwned>(&self, payload: A, endpoint: &str) -> Result<B, VaultError> {
+        self.send_payload_and_get_response(payload, endpoint).await?.json().await.map_err(|_| ServerError)
     }
 }
 
 
-impl ServerConnection for HttpConnection {
-    fn create_organization(&mut self, organization_name: &str, users_data: &HashMap<String, UserShare>, public_key: &PublicKey, argon2_config: &pwhash::Config)
+/// Converts the error of a request that got no response. Only a failed connection or a timeout means that the server is
+/// unreachable, so that a cached response may be used instead; any other failure, e.g. a refused TLS handshake, is a ServerError.
+fn send_error(error: reqwest::Error) -> VaultError {
+    if error.is_connect() || error.is_timeout() {
+        ServerUnreachable
+    } else {
+        ServerError
+    }
+}
+
+/// Checks that the server supports the protocol version of this client, among the versions it announced.
+/// `None` means that the server does not know the version endpoint, so it only supports the protocol that predates versioning.
+fn check_protocol_versions(server_protocol_versions: Option<Vec<u32>>) -> Result<(), VaultError> {
+    match server_protocol_versions {
+        Some(versions) if versions.contains(&PROTOCOL_VERSION) => Ok(()),
+        Some(versions) => Err(IncompatibleServerVersion(versions)),
+        None => Err(IncompatibleServerVersion(Vec::new())),
+    }
+}
+
+/// Removes the complete server-sent events from the beginning of `buffer`, and returns the document events they contain.
+/// The events that are not document events, such as the keep-alive comments, are skipped.
+fn take_document_events(buffer: &mut Vec<u8>) -> Vec<DocumentEvent> {
+    let mut events = Vec::new();
+    while let Some(end) = buffer.windows(2).position(|window| window == b"\n\n") {
+        let server_sent_event: Vec<u8> = buffer.drain(..end + 2).collect();
+        let server_sent_event = String::from_utf8_lossy(&server_sent_event);
+        let data: Vec<&str> = server_sent_event
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|data| data.strip_prefix(' ').unwrap_or(data))
+            .collect();
+        if let Ok(event) = serde_json::from_str(&data.join("\n")) {
+            events.push(event);
+        }
+    }
+    events
+}
+
+#[async_trait]
+impl AsyncServerConnection for AsyncHttpConnection {
+    async fn create_organization(&mut self, organization_name: &str, users_data: &HashMap<String, UserShare>, public_key: &PublicKey, argon2_config: &pwhash::Config,
+                           client_certificate_authorities: &[Vec<u8>])
                            -> Result<(), VaultError> {
-        self.send_payload((organization_name, users_data, public_key, argon2_config), CREATE_ORGANIZATION_ENDPOINT)
+        self.send_payload(
+            CreateOrganizationRequest {
+                organization_name: organization_name.to_string(),
+                users_data: users_data.clone(),
+                public_key: public_key.clone(),
+                argon2_config: argon2_config.clone(),
+                client_certificate_authorities: client_certificate_authorities.to_vec(),
+            },
+            CREATE_ORGANIZATION_ENDPOINT,
+        ).await
     }
 
-    fn unlock_vault(&mut self, organization_name: &str, user_name1: &str, user_name2: &str)
+    async fn unlock_vault(&mut self, organization_name: &str, user_name1: &str, user_name2: &str)
                     -> Result<(UserShare, UserShare, pwhash::Config, PublicKey, EncryptedToken), VaultError> {
-        self.send_payload_and_deserialize_json_response((organization_name, user_name1, user_name2), UNLOCK_VAULT_ENDPOINT)
+        let response: UnlockVaultResponse = self.send_payload_and_deserialize_json_response(
+            UnlockVaultRequest {
+                organization_name: organization_name.to_string(),
+                user_name1: user_name1.to_string(),
+                user_name2: user_name2.to_string(),
+            },
+            UNLOCK_VAULT_ENDPOINT,
+        ).await?;
+        Ok((response.user_share1, response.user_share2, response.argon2_config, response.public_key, response.encrypted_token))
+    }
+
+    fn set_token(&mut self, token: &Token) {
+        self.token = Some(token.clone());
+    }
+
+    async fn revoke_user(&mut self, user_name: &str) -> Result<(), VaultError> {
+        self.send_payload(RevokeUserRequest { user_name: user_name.to_string() }, REVOKE_USER_ENDPOINT).await
     }
 
-    fn revoke_user(&mut self, token: &Token, user_name: &str) -> Result<(), VaultError> {
-        self.send_payload((token, user_name), REVOKE_USER_ENDPOINT)
+    async fn revoke_token(&mut self) -> Result<(), VaultError> {
+        Self::send_request(self.post(REVOKE_TOKEN_ENDPOINT)).await?;
+        Ok(())
     }
 
-    fn revoke_token(&mut self, token: &Token) -> Result<(), VaultError> {
-        self.send_payload(token, REVOKE_TOKEN_ENDPOINT)
+    async fn new_document(&mut self, encrypted_document: &EncryptedDocument, encrypted_key: &EncryptedDocumentKey) -> Result<DocumentID, VaultError> {
+        let response: NewDocumentResponse = self.send_payload_and_deserialize_json_response(
+            NewDocumentRequest {
+                encrypted_document: encrypted_document.clone(),
+                encrypted_key: encrypted_key.clone(),
+            },
+            NEW_DOCUMENT_ENDPOINT,
+        ).await?;
+        Ok(response.document_id)
     }
 
-    fn new_document(&mut self, token: &Token, encrypted_document: &EncryptedDocument, encrypted_key: &EncryptedDocumentKey) -> Result<(), VaultError> {
-        self.send_payload((token, encrypted_document, encrypted_key), NEW_DOCUMENT_ENDPOINT)
+    async fn list_documents(&mut self) -> Result<Vec<EncryptedDocumentInfo>, VaultError> {
+        let response: ListDocumentsResponse =
+            Self::send_request(self.post(LIST_DOCUMENTS_ENDPOINT)).await?.json().await.map_err(|_| ServerError)?;
+        Ok(response.documents)
     }
 
-    fn list_documents(&mut self, token: &Token) -> Result<Vec<(DocumentID, EncryptedDocumentNameAndKey)>, VaultError> {
-        self.send_payload_and_deserialize_json_response(token, LIST_DOCUMENTS_ENDPOINT)
+    async fn list_documents_page(&mut self, page_request: &DocumentPageRequest) -> Result<EncryptedDocumentPage, VaultError> {
+        let query = ListDocumentsQuery {
+            order: Some(page_request.order),
+            descending: Some(page_request.descending),
+            cursor: page_request.cursor.clone(),
+            page_size: Some(page_request.page_size),
+        };
+        let response: ListDocumentsResponse =
+            Self::send_request(self.post(LIST_DOCUMENTS_ENDPOINT).query(&query)).await?.json().await.map_err(|_| ServerError)?;
+        Ok(EncryptedDocumentPage { documents: response.documents, next_cursor: response.next_cursor })
     }
 
-    fn get_document_key(&mut self, token: &Token, document_id: &DocumentID) -> Result<EncryptedDocumentKey, VaultError> {
-        self.send_payload_and_deserialize_json_response((token, document_id), GET_DOCUMENT_KEY_ENDPOINT)
+    async fn get_document_key(&mut self, document_id: &DocumentID) -> Result<EncryptedDocumentKey, VaultError> {
+        let response: GetDocumentKeyResponse = self.send_payload_and_deserialize_json_response(
+            DocumentRequest { document_id: document_id.clone() },
+            GET_DOCUMENT_KEY_ENDPOINT,
+        ).await?;
+        Ok(response.encrypted_key)
     }
 
-    fn get_document(&mut self, token: &Token, document_id: &DocumentID) -> Result<EncryptedDocument, VaultError> {
-        self.send_payload_and_deserialize_json_response((token, document_id), GET_DOCUMENT_ENDPOINT)
+    async fn get_document(&mut self, document_id: &DocumentID) -> Result<EncryptedDocument, VaultError> {
+        let response: GetDocumentResponse = self.send_payload_and_deserialize_json_response(
+            DocumentRequest { document_id: document_id.clone() },
+            GET_DOCUMENT_ENDPOINT,
+        ).await?;
+        Ok(response.encrypted_document)
     }
 
-    fn update_document(&mut self, token: &Token, document_id: &DocumentID, encrypted_document: &EncryptedDocument) -> Result<(), VaultError> {
-        self.send_payload((token, document_id, encrypted_document), UPDATE_DOCUMENT_ENDPOINT)
+    async fn update_document(&mut self, document_id: &DocumentID, encrypted_document: &EncryptedDocument, expected_version: Option<u64>)
+                             -> Result<u64, VaultError> {
+        let response: UpdateDocumentResponse = self.send_payload_and_deserialize_json_response(
+            UpdateDocumentRequest {
+                document_id: document_id.clone(),
+                encrypted_document: encrypted_document.clone(),
+                expected_version,
+            },
+            UPDATE_DOCUMENT_ENDPOINT,
+        ).await?;
+        Ok(response.version)
     }
 
-    fn delete_document(&mut self, token: &Token, document_id: &DocumentID) -> Result<(), VaultError> {
-        self.send_payload((token, document_id), DELETE_DOCUMENT_ENDPOINT)
+    async fn delete_document(&mut self, document_id: &DocumentID) -> Result<(), VaultError> {
+        self.send_payload(DocumentRequest { document_id: document_id.clone() }, DELETE_DOCUMENT_ENDPOINT).await
     }
 
-    fn get_public_key_of_organization(&mut self, organization_name: &str) -> Result<PublicKey, VaultError> {
-        self.send_payload_and_deserialize_json_response(organization_name, GET_PUBLIC_KEY_ENDPOINT)
+    async fn get_public_key_of_organization(&mut self, organization_name: &str) -> Result<PublicKey, VaultError> {
+        let response: GetPublicKeyResponse = self.send_payload_and_deserialize_json_response(
+            GetPublicKeyRequest { organization_name: organization_name.to_string() },
+            GET_PUBLIC_KEY_ENDPOINT,
+        ).await?;
+        Ok(response.public_key)
     }
 
-    fn add_owner(&mut self,
-                 token: &Token,
+    async fn add_owner(&mut self,
                  document_id: &DocumentID,
                  other_organization_name: &str,
                  encrypted_document_key: &EncryptedDocumentKey,
+                 permission: Permission,
+                 expires_at: Option<u64>,
     ) -> Result<(), VaultError> {
-        self.send_payload((token, document_id, other_organization_name, encrypted_document_key), ADD_OWNER_ENDPOINT)
+        self.send_payload(
+            AddOwnerRequest {
+                document_id: document_id.clone(),
+                other_organization_name: other_organization_name.to_string(),
+                encrypted_document_key: encrypted_document_key.clone(),
+                permission,
+                expires_at,
+            },
+            ADD_OWNER_ENDPOINT,
+        ).await
+    }
+
+    async fn list_share_invitations(&mut self) -> Result<Vec<EncryptedShareInvitation>, VaultError> {
+        let response: ListShareInvitationsResponse =
+            Self::send_request(self.post(LIST_SHARE_INVITATIONS_ENDPOINT)).await?.json().await.map_err(|_| ServerError)?;
+        Ok(response.invitations)
+    }
+
+    async fn accept_share_invitation(&mut self, document_id: &DocumentID) -> Result<(), VaultError> {
+        self.send_payload(DocumentRequest { document_id: document_id.clone() }, ACCEPT_SHARE_INVITATION_ENDPOINT).await
+    }
+
+    async fn decline_share_invitation(&mut self, document_id: &DocumentID) -> Result<(), VaultError> {
+        self.send_payload(DocumentRequest { document_id: document_id.clone() }, DECLINE_SHARE_INVITATION_ENDPOINT).await
+    }
+
+    async fn get_document_owners(&mut self, document_id: &DocumentID) -> Result<Vec<DocumentOwner>, VaultError> {
+        let response: GetDocumentOwnersResponse = self.send_payload_and_deserialize_json_response(
+            DocumentRequest { document_id: document_id.clone() },
+            GET_DOCUMENT_OWNERS_ENDPOINT,
+        ).await?;
+        Ok(response.owners)
+    }
+
+    async fn remove_owner(&mut self, document_id: &DocumentID, other_organization_name: &str) -> Result<Vec<String>, VaultError> {
+        let response: RemoveOwnerResponse = self.send_payload_and_deserialize_json_response(
+            RemoveOwnerRequest {
+                document_id: document_id.clone(),
+                other_organization_name: other_organization_name.to_string(),
+            },
+            REMOVE_OWNER_ENDPOINT,
+        ).await?;
+        Ok(response.remaining_owners)
+    }
+
+    async fn rotate_document_key(&mut self, document_id: &DocumentID, encrypted_document: &EncryptedDocument,
+                                 encrypted_keys: &HashMap<String, EncryptedDocumentKey>, expected_version: u64)
+                                 -> Result<(), VaultError> {
+        self.send_payload(
+            RotateDocumentKeyRequest {
+                document_id: document_id.clone(),
+                encrypted_document: encrypted_document.clone(),
+                encrypted_keys: encrypted_keys.clone(),
+                expected_version,
+            },
+            ROTATE_DOCUMENT_KEY_ENDPOINT,
+        ).await
+    }
+
+    async fn new_documents(&mut self, documents: &[(EncryptedDocument, EncryptedDocumentKey)])
+                           -> Result<BatchResults<DocumentID>, VaultError> {
+        let response: NewDocumentsResponse = self.send_payload_and_deserialize_json_response(
+            NewDocumentsRequest {
+                documents: documents.iter()
+                    .map(|(encrypted_document, encrypted_key)| NewDocumentRequest {
+                        encrypted_document: encrypted_document.clone(),
+                        encrypted_key: encrypted_key.clone(),
+                    })
+                    .collect(),
+            },
+            NEW_DOCUMENTS_ENDPOINT,
+        ).await?;
+        Ok(response.results)
+    }
+
+    async fn get_documents(&mut self, document_ids: &[DocumentID])
+                           -> Result<BatchResults<(EncryptedDocument, EncryptedDocumentKey)>, VaultError> {
+        let response: GetDocumentsResponse = self.send_payload_and_deserialize_json_response(
+            GetDocumentsRequest { document_ids: document_ids.to_vec() },
+            GET_DOCUMENTS_ENDPOINT,
+        ).await?;
+        Ok(
+            response.results
+                .into_iter()
+                .map(|result| result.map(|downloaded_document| (downloaded_document.encrypted_document, downloaded_document.encrypted_key)))
+                .collect()
+        )
+    }
+    async fn get_search_index(&mut self) -> Result<Option<EncryptedSearchIndex>, VaultError> {
+        let response: GetSearchIndexResponse =
+            Self::send_request(self.post(GET_SEARCH_INDEX_ENDPOINT)).await?.json().await.map_err(|_| ServerError)?;
+        Ok(response.search_index)
+    }
+
+    async fn set_search_index(&mut self, search_index: &EncryptedSearchIndex) -> Result<(), VaultError> {
+        self.send_payload(SetSearchIndexRequest { search_index: search_index.clone() }, SET_SEARCH_INDEX_ENDPOINT).await
+    }
+
+    async fn create_group(&mut self, group_name: &str, members: &[String], public_key: &PublicKey,
+                          encrypted_private_key: &EncryptedGroupPrivateKey, member_keys: &HashMap<String, SymEncryptedData>)
+                          -> Result<(), VaultError> {
+        self.send_payload(
+            CreateGroupRequest {
+                group_name: group_name.to_string(),
+                members: members.to_vec(),
+                public_key: public_key.clone(),
+                encrypted_private_key: encrypted_private_key.clone(),
+                member_keys: member_keys.clone(),
+            },
+            CREATE_GROUP_ENDPOINT,
+        ).await
+    }
+
+    async fn list_groups(&mut self) -> Result<Vec<EncryptedGroup>, VaultError> {
+        let response: ListGroupsResponse =
+            Self::send_request(self.post(LIST_GROUPS_ENDPOINT)).await?.json().await.map_err(|_| ServerError)?;
+        Ok(response.groups)
+    }
+
+    async fn open_group_session(&mut self, group_name: &str) -> Result<EncryptedToken, VaultError> {
+        let response: OpenGroupSessionResponse = self.send_payload_and_deserialize_json_response(
+            OpenGroupSessionRequest { group_name: group_name.to_string() },
+            OPEN_GROUP_SESSION_ENDPOINT,
+        ).await?;
+        Ok(response.encrypted_token)
+    }
+
+    async fn add_group_member(&mut self, group_name: &str, user_name: &str) -> Result<(), VaultError> {
+        self.send_payload(
+            GroupMemberRequest { group_name: group_name.to_string(), user_name: user_name.to_string() },
+            ADD_GROUP_MEMBER_ENDPOINT,
+        ).await
+    }
+
+    async fn remove_group_member(&mut self, group_name: &str, user_name: &str) -> Result<(), VaultError> {
+        self.send_payload(
+            GroupMemberRequest { group_name: group_name.to_string(), user_name: user_name.to_string() },
+            REMOVE_GROUP_MEMBER_ENDPOINT,
+        ).await
+    }
+
+    async fn set_group_member_key(&mut self, group_name: &str, user_name: &str, encrypted_private_key: &SymEncryptedData)
+                                  -> Result<(), VaultError> {
+        self.send_payload(
+            SetGroupMemberKeyRequest {
+                group_name: group_name.to_string(),
+                user_name: user_name.to_string(),
+                encrypted_private_key: encrypted_private_key.clone(),
+            },
+            SET_GROUP_MEMBER_KEY_ENDPOINT,
+        ).await
+    }
+
+    async fn create_share_link(&mut self, document_id: &DocumentID, encrypted_document: &EncryptedDocument, expires_at: u64)
+                               -> Result<ShareLinkID, VaultError> {
+        let response: CreateShareLinkResponse = self.send_payload_and_deserialize_json_response(
+            CreateShareLinkRequest {
+                document_id: document_id.clone(),
+                encrypted_document: encrypted_document.clone(),
+                expires_at,
+            },
+            CREATE_SHARE_LINK_ENDPOINT,
+        ).await?;
+        Ok(response.link_id)
+    }
+
+    async fn open_share_link(&mut self, link_id: &ShareLinkID) -> Result<EncryptedDocument, VaultError> {
+        let response: OpenShareLinkResponse = self.send_payload_and_deserialize_json_response(
+            OpenShareLinkRequest { link_id: link_id.clone() },
+            OPEN_SHARE_LINK_ENDPOINT,
+        ).await?;
+        Ok(response.encrypted_document)
+    }
+
+    /// The events are read by a task of the current runtime, which stops when the receiver is dropped
+    async fn subscribe_events(&mut self) -> Result<DocumentEventReceiver, VaultError> {
+        let mut response = Self::send_request(self.request(Method::GET, EVENTS_ENDPOINT)).await?;
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            let mut buffer = Vec::new();
+            loop {
+                let chunk = tokio::select! {
+                    _ = sender.closed() => return,
+                    chunk = response.chunk() => chunk,
+                };
+                let Ok(Some(chunk)) = chunk else {
+                    return;
+                };
+                buffer.extend_from_slice(&chunk);
+                for event in take_document_events(&mut buffer) {
+                    if sender.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(receiver)
     }
 }
 
-impl Clone for HttpConnection {
-    fn clone(&self) -> Self {
-        HttpConnection { server_url: self.server_url.clone(), http_client: self.http_client.clone() }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn document_events() {
+        let mut buffer = b"data: {\"Created\":{\"document_id\":[1,2]}}\n\n:\n\ndata: \"EventsMissed\"\n\ndata: {\"Deleted\"".to_vec();
+
+        assert_eq!(
+            take_document_events(&mut buffer),
+            vec![DocumentEvent::Created { document_id: vec![1, 2] }, DocumentEvent::EventsMissed]
+        );
+        assert_eq!(buffer, b"data: {\"Deleted\"");
+
+        buffer.extend_from_slice(b":{\"document_id\":[3]}}\n\n");
+        assert_eq!(take_document_events(&mut buffer), vec![DocumentEvent::Deleted { document_id: vec![3] }]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn protocol_versions() {
+        assert_eq!(check_protocol_versions(Some(vec![PROTOCOL_VERSION])), Ok(()));
+        assert_eq!(check_protocol_versions(Some(vec![PROTOCOL_VERSION + 1, PROTOCOL_VERSION])), Ok(()));
+        // Disjoint version lists
+        assert_eq!(check_protocol_versions(Some(vec![PROTOCOL_VERSION + 1])), Err(IncompatibleServerVersion(vec![PROTOCOL_VERSION + 1])));
+        // Server without the version endpoint
+        assert_eq!(check_protocol_versions(None), Err(IncompatibleServerVersion(Vec::new())));
+    }
+
+    #[test]
+    fn invalid_client_identity_config() {
+        assert!(matches!(client_identity_from_config(&ClientConfig::default()), Ok(None)));
+
+        let without_key = ClientConfig { client_certificate_file: Some(PathBuf::from("certificate.pem")), ..ClientConfig::default() };
+        assert!(matches!(client_identity_from_config(&without_key), Err(InputError)));
+
+        let unreadable_files = ClientConfig {
+            client_certificate_file: Some(PathBuf::from("missing certificate.pem")),
+            client_certificate_key_file: Some(PathBuf::from("missing key.pem")),
+            ..ClientConfig::default()
+        };
+        assert!(matches!(client_identity_from_config(&unreadable_files), Err(InputError)));
     }
-}
\ No newline at end of file
+}
@@ -41,13 +41,17 @@ pub fn create_protected_key_pair(user_credentials: &HashMap<String, String>,
     Ok((user_shares, key_pair.public_key))
 }
 
-/// Retrieves a private key using the the encrypted shares and the user passwords
-pub fn retrieve_private_key(
-    password1: &str, user_share1: &UserShare, password2: &str, user_share2: &UserShare,
-    argon_config: &pwhash::Config)
+/// Derives the key of a user from its password. It encrypts the share of the user, and the private keys of the groups of the user.
+pub fn user_key(password: &str, user_share: &UserShare, argon_config: &pwhash::Config) -> Result<dryocsecretbox::Key, VaultError> {
+    get_key_from_password(password, &user_share.salt, argon_config)
+}
+
+/// Retrieves a private key using the the encrypted shares and the keys derived from the user passwords by `user_key`
+pub fn retrieve_private_key_with_user_keys(
+    user_key1: &dryocsecretbox::Key, user_share1: &UserShare, user_key2: &dryocsecretbox::Key, user_share2: &UserShare)
     -> Result<dryocbox::SecretKey, VaultError> {
-    let share1 = decrypt_share_with_password(user_share1, password1, argon_config)?;
-    let share2 = decrypt_share_with_password(user_share2, password2, argon_config)?;
+    let share1 = decrypt_share(user_share1, user_key1)?;
+    let share2 = decrypt_share(user_share2, user_key2)?;
 
     let recovered_secret = sharks::Sharks(NB_USERS_REQUIRED_TO_RETRIEVE_PRIVATE_KEY).recover([&share1, &share2]).map_err(|_| CryptographyError)?;
 
@@ -68,9 +72,8 @@ fn get_key_from_password(password: &str, salt: &pwhash::Salt, argon_config: &pwh
     )
 }
 
-fn decrypt_share_with_password(share: &UserShare, password: &str, argon_config: &pwhash::Config) -> Result<sharks::Share, VaultError> {
-    let user_key = get_key_from_password(&password, &share.salt, argon_config)?;
-    let decrypted = share.encrypted_private_key_share.decrypt(&user_key)?;
+fn decrypt_share(share: &UserShare, user_key: &dryocsecretbox::Key) -> Result<sharks::Share, VaultError> {
+    let decrypted = share.encrypted_private_key_share.decrypt(user_key)?;
 
     sharks::Share::try_from(decrypted.as_slice()).map_err(|_| CryptographyError)
 }
@@ -95,12 +98,13 @@ mod tests {
 
         let (user_shares, public_key) = create_protected_key_pair(&user_credentials, &argon_config).unwrap();
 
-        let secret_key = retrieve_private_key(
-            user_credentials.get("Chell").unwrap(),
-            user_shares.get("Chell").unwrap(),
-            user_credentials.get("Cave").unwrap(),
-            user_shares.get("Cave").unwrap(),
-            &argon_config,
+        let chell_share = user_shares.get("Chell").unwrap();
+        let cave_share = user_shares.get("Cave").unwrap();
+        let secret_key = retrieve_private_key_with_user_keys(
+            &user_key(user_credentials.get("Chell").unwrap(), chell_share, &argon_config).unwrap(),
+            chell_share,
+            &user_key(user_credentials.get("Cave").unwrap(), cave_share, &argon_config).unwrap(),
+            cave_share,
         ).unwrap();
 
         let message = b"The cake is a lie !".to_vec();
@@ -111,4 +115,20 @@ mod tests {
 
         assert_eq!(message, decrypted_message)
     }
+
+    #[test]
+    fn wrong_password() {
+        let mut user_credentials: HashMap<String, String> = HashMap::new();
+        user_credentials.insert(String::from("GLaDos"), String::from("pa89fjqp3f"));
+        user_credentials.insert(String::from("Chell"), String::from("japo288asfd"));
+
+        let argon_config = pwhash::Config::default().with_memlimit(10000).with_opslimit(1);
+        let (user_shares, _) = create_protected_key_pair(&user_credentials, &argon_config).unwrap();
+        let glados_share = user_shares.get("GLaDos").unwrap();
+        let chell_share = user_shares.get("Chell").unwrap();
+
+        let glados_key = user_key("pa89fjqp3f", glados_share, &argon_config).unwrap();
+        let wrong_key = user_key("wrong password", chell_share, &argon_config).unwrap();
+        assert!(retrieve_private_key_with_user_keys(&glados_key, glados_share, &wrong_key, chell_share).is_err());
+    }
 }
\ No newline at end of file
@@ -1,6 +1,14 @@
 mod key_pair;
 mod encryptor_decryptor;
+mod document_index;
 pub mod session_controller;
+pub mod async_session_controller;
+pub mod search_index;
+pub mod document_path;
 pub mod http_connection;
 pub mod organization_creation;
 pub mod client_config;
+pub mod share_link;
+pub mod caching_connection;
+pub mod sync;
+pub mod agent;
@@ -5,23 +5,26 @@ use std::time::Instant;
 
 use dryoc::pwhash;
 use dryoc::pwhash::VecPwHash;
+use tokio::task;
 use zxcvbn::zxcvbn;
 
 use crate::client::key_pair::create_protected_key_pair;
 use crate::error::VaultError;
-use crate::error::VaultError::{CryptographyError, NotEnoughUsers, PasswordNotStrong};
-use crate::server_connection::ServerConnection;
+use crate::error::VaultError::{CryptographyError, InputError, NotEnoughUsers, PasswordNotStrong};
+use crate::server_connection::{AsyncServerConnection, ServerConnection};
+use crate::utils::get_certificates_der_from_pem;
 use crate::validation::validate_and_standardize_name;
 
 /// Used to prepare the data needed to create an organization and to send it to the server.
 /// 
 /// Once an instance of OrganizationBuilder is created, at least two users must be added by calling add_user.
-/// The organization request can then be sent to the server with create_organization.
+/// The organization request can then be sent to the server with create_organization, or create_organization_async.
 #[derive(Clone)]
 pub struct OrganizationBuilder {
     organization_name: String,
     argon_config: pwhash::Config,
     user_credentials: HashMap<String, String>,
+    client_certificate_authorities: Vec<Vec<u8>>,
 }
 
 impl OrganizationBuilder {
@@ -31,6 +34,7 @@ impl OrganizationBuilder {
             organization_name: organization_name.to_string(),
             argon_config: argon_config.clone(),
             user_credentials: HashMap::new(),
+            client_certificate_authorities: Vec::new(),
         })
     }
 
@@ -49,6 +53,16 @@ impl OrganizationBuilder {
         }
     }
 
+    /// Adds the PEM certificates of a client certificate authority.
+    ///
+    /// If at least one certificate authority is added, the server will only unlock the vault and accept authenticated requests
+    /// for the organization over TLS connections that present a client certificate issued by one of them.
+    pub fn add_client_certificate_authority(mut self, pem_certificates: &[u8]) -> Result<Self, VaultError> {
+        let mut der_certificates = get_certificates_der_from_pem(pem_certificates).map_err(|_| InputError)?;
+        self.client_certificate_authorities.append(&mut der_certificates);
+        Ok(self)
+    }
+
     /// Creates the organization key pair, protects the private key with the user passwords and sends an organization creation request to the server.
     pub fn create_organization<A: ServerConnection>(self, server: &mut A) -> Result<(), VaultError> {
         if self.user_credentials.len() < 2 {
@@ -57,7 +71,20 @@ impl OrganizationBuilder {
 
         let (encrypted_user_shares, public_key) =
             create_protected_key_pair(&self.user_credentials, &self.argon_config)?;
-        server.create_organization(&self.organization_name, &encrypted_user_shares, &public_key, &self.argon_config)
+        server.create_organization(&self.organization_name, &encrypted_user_shares, &public_key, &self.argon_config, &self.client_certificate_authorities)
+    }
+
+    /// Asynchronous counterpart of `create_organization`. The key pair is created on a blocking thread.
+    pub async fn create_organization_async<A: AsyncServerConnection>(self, server: &mut A) -> Result<(), VaultError> {
+        if self.user_credentials.len() < 2 {
+            return Err(NotEnoughUsers);
+        }
+
+        let (user_credentials, argon_config) = (self.user_credentials, self.argon_config.clone());
+        let (encrypted_user_shares, public_key) =
+            task::spawn_blocking(move || create_protected_key_pair(&user_credentials, &argon_config))
+                .await.map_err(|_| CryptographyError)??;
+        server.create_organization(&self.organization_name, &encrypted_user_shares, &public_key, &self.argon_config, &self.client_certificate_authorities).await
     }
 }
 
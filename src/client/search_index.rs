@@ -0,0 +1,128 @@
+//! Full-text search index of the documents of an organization.
+//!
+//! The index is built by the client from the decrypted documents, and stored encrypted on the server,
+//! so that the server never learns the words of the documents nor the searched keywords.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use data_encoding::BASE32;
+use serde::{Deserialize, Serialize};
+
+use crate::data::{Document, DocumentID};
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+struct IndexedDocument {
+    name: String,
+    /// Version of the document when it was indexed. The indexes stored by older clients have none, so their entries are stale.
+    #[serde(default)]
+    version: Option<u64>,
+    words: BTreeSet<String>,
+}
+
+/// Maps each indexed document to its name, its version and the words of its name and content
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct SearchIndex {
+    /// Indexed documents, by BASE32 encoded document ID
+    documents: HashMap<String, IndexedDocument>,
+}
+
+/// Splits a text into lowercase alphanumeric words
+fn words(text: &str) -> BTreeSet<String> {
+    text.split(|character: char| !character.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+impl SearchIndex {
+    /// Indexes a document at `version`, replacing its previous version if it was already indexed
+    pub fn add_document(&mut self, document_id: &DocumentID, version: u64, document: &Document) {
+        let mut document_words = words(&document.name);
+        document_words.append(&mut words(&document.content));
+
+        self.documents.insert(
+            BASE32.encode(document_id),
+            IndexedDocument { name: document.name.clone(), version: Some(version), words: document_words },
+        );
+    }
+
+    pub fn remove_document(&mut self, document_id: &DocumentID) {
+        self.documents.remove(&BASE32.encode(document_id));
+    }
+
+    /// Returns the version at which a document was indexed, if it was indexed with its version
+    pub fn document_version(&self, document_id: &DocumentID) -> Option<u64> {
+        self.documents.get(&BASE32.encode(document_id))?.version
+    }
+
+    /// Removes the documents that are not in `document_ids`, e.g. the deleted documents and the ones whose access expired.
+    /// Returns whether some documents were removed.
+    pub fn retain_documents(&mut self, document_ids: &[DocumentID]) -> bool {
+        let document_ids: HashSet<String> = document_ids.iter().map(|document_id| BASE32.encode(document_id)).collect();
+        let indexed_document_count = self.documents.len();
+        self.documents.retain(|document_id, _| document_ids.contains(document_id));
+        self.documents.len() != indexed_document_count
+    }
+
+    /// Returns the names of the documents that contain all the words of `query`, sorted by name
+    pub fn search(&self, query: &str) -> Vec<String> {
+        let query_words = words(query);
+        if query_words.is_empty() {
+            return Vec::new();
+        }
+
+        let mut document_names: Vec<String> = self.documents.values()
+            .filter(|document| query_words.is_subset(&document.words))
+            .map(|document| document.name.clone())
+            .collect();
+        document_names.sort();
+        document_names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(name: &str, content: &str) -> Document {
+        Document { name: name.to_string(), content: content.to_string() }
+    }
+
+    #[test]
+    fn search() {
+        let mut search_index = SearchIndex::default();
+        search_index.add_document(&vec![1], 0, &document("Test chamber 1", "The cake is a lie."));
+        search_index.add_document(&vec![2], 0, &document("Test chamber 2", "Thinking with portals"));
+
+        assert_eq!(search_index.search("CAKE"), vec!["Test chamber 1".to_string()]);
+        assert_eq!(search_index.search("chamber, test"), vec!["Test chamber 1".to_string(), "Test chamber 2".to_string()]);
+        assert_eq!(search_index.search("cake portals"), Vec::<String>::new());
+        assert_eq!(search_index.search(""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn update_and_remove() {
+        let mut search_index = SearchIndex::default();
+        search_index.add_document(&vec![1], 0, &document("Test chamber", "The cake is a lie."));
+        search_index.add_document(&vec![1], 1, &document("Test chamber", "There is no cake."));
+
+        assert!(search_index.search("lie").is_empty());
+        assert_eq!(search_index.search("cake"), vec!["Test chamber".to_string()]);
+        assert_eq!(search_index.document_version(&vec![1]), Some(1));
+
+        search_index.remove_document(&vec![1]);
+        assert!(search_index.search("cake").is_empty());
+        assert_eq!(search_index.document_version(&vec![1]), None);
+    }
+
+    #[test]
+    fn retain_documents() {
+        let mut search_index = SearchIndex::default();
+        search_index.add_document(&vec![1], 0, &document("Test chamber 1", "The cake is a lie."));
+        search_index.add_document(&vec![2], 0, &document("Test chamber 2", "There is no cake."));
+
+        assert!(!search_index.retain_documents(&[vec![1], vec![2], vec![3]]));
+        assert!(search_index.retain_documents(&[vec![2]]));
+        assert_eq!(search_index.search("cake"), vec!["Test chamber 2".to_string()]);
+    }
+}
@@ -1,119 +1,287 @@
 //! Provides functions that must be called from the user interface to access the vault
+//!
+//! `Controller` is a blocking wrapper around `AsyncController`. It must not be used from an asynchronous context.
 
-use dryoc::dryocbox;
+use std::sync::Arc;
+use std::time::SystemTime;
 
-use crate::client::encryptor_decryptor::OrganizationEncryptorDecryptor;
-use crate::client::key_pair::retrieve_private_key;
-use crate::data::{Document, DocumentID, EncryptedDocumentNameAndKey, Token};
+use tokio::runtime::Runtime;
+
+use crate::client::async_session_controller::{AsyncController, AsyncDocumentIterator};
+use crate::client::share_link::ShareLink;
+use crate::data::{Document, DocumentID, DocumentInfo, DocumentMetadata, DocumentOrder, DocumentOwner, FolderContent, GroupInfo, PendingShare, Permission};
 use crate::error::VaultError;
-use crate::error::VaultError::{DocumentNotFound};
-use crate::server_connection::ServerConnection;
-use crate::validation::validate_and_standardize_name;
+use crate::server_connection::{AsyncServerConnection, BlockingServerConnection, DocumentEventReceiver};
 
 /// A controller instance represents a client session.
 /// A new controller must first be built with `unlock_vault_for_organization`, in order to retrieve the organization private key.
 /// The controller is then used to manipulate documents.
-#[derive(Debug, PartialEq)]
-pub struct Controller<A: ServerConnection + Clone> {
-    server: A,
-    encryptor_decryptor: OrganizationEncryptorDecryptor,
-    token: Token,
+#[derive(Debug)]
+pub struct Controller<A: AsyncServerConnection + Clone + 'static> {
+    controller: AsyncController<A>,
+    runtime: Arc<Runtime>,
 }
 
-impl<A: ServerConnection + Clone> Controller<A> {
+impl<A: AsyncServerConnection + Clone + 'static> Controller<A> {
     /// Retrieves the organization private key and decrypts the authentication token
-    pub fn unlock_vault_for_organization(server: &mut A, organization_name: &str,
+    pub fn unlock_vault_for_organization(server: &mut BlockingServerConnection<A>, organization_name: &str,
                                          username1: &str, password1: &str,
                                          username2: &str, password2: &str)
                                          -> Result<Self, VaultError> {
-        let organization_name = validate_and_standardize_name(organization_name)?;
-        let username1 = validate_and_standardize_name(username1)?;
-        let username2 = validate_and_standardize_name(username2)?;
-
-        let (user_share1, user_share2, argon_config, public_key, encrypted_token) =
-            server.unlock_vault(&organization_name, &username1, &username2)?;
-        let private_key = retrieve_private_key(password1, &user_share1, password2, &user_share2, &argon_config)?;
+        let runtime = server.runtime().clone();
+        let mut async_server = server.async_connection().clone();
+        let controller = runtime.block_on(AsyncController::unlock_vault_for_organization(
+            &mut async_server, organization_name, username1, password1, username2, password2,
+        ))?;
+        Ok(Controller { controller, runtime })
+    }
 
-        let encryptor_decryptor =
-            OrganizationEncryptorDecryptor::new(dryocbox::KeyPair { public_key, secret_key: private_key });
-        let token = encryptor_decryptor.decrypt_token(&encrypted_token)?;
+    pub fn revoke_user(&mut self, username: &str) -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.revoke_user(username))
+    }
 
-        Ok(Controller { server: server.clone(), encryptor_decryptor, token })
+    /// Returns the organization of the session, or `<organization>.<group>` for a group session
+    pub fn organization_name(&self) -> &str {
+        self.controller.organization_name()
     }
 
-    pub fn revoke_user(&mut self, username: &str) -> Result<(), VaultError> {
-        self.server.revoke_user(&self.token, username)
+    /// Returns the names of the users who unlocked the session, in alphabetical order
+    pub fn user_names(&self) -> Vec<String> {
+        self.controller.user_names()
     }
 
     /// Logs the client out
     pub fn revoke_token(&mut self) -> Result<(), VaultError> {
-        self.server.revoke_token(&self.token)
+        self.runtime.block_on(self.controller.revoke_token())
     }
 
-
-    /// Uploads a new document
+    /// Uploads a new document and adds it to the search index
     pub fn upload(&mut self, document: &Document) -> Result<(), VaultError> {
-        let (encrypted_document, encrypted_key) =
-            self.encryptor_decryptor.generate_document_key_and_encrypt_document(document)?;
-        self.server.new_document(&self.token, &encrypted_document, &encrypted_key)
+        self.runtime.block_on(self.controller.upload(document))
+    }
+
+    /// Uploads a new document along with its metadata, which is encrypted with the document key
+    pub fn upload_with_metadata(&mut self, document: &Document, metadata: &DocumentMetadata) -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.upload_with_metadata(document, metadata))
+    }
+
+    /// Returns the names of the documents whose name or content contain all the words of `query`.
+    /// The search runs on the decrypted index, so that the server does not learn the searched words.
+    /// The index is rebuilt first if it is outdated.
+    pub fn search(&mut self, query: &str) -> Result<Vec<String>, VaultError> {
+        self.runtime.block_on(self.controller.search(query))
+    }
+
+    /// Rebuilds the search index from the content of all the documents of the organization
+    pub fn rebuild_search_index(&mut self) -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.rebuild_search_index())
+    }
+
+    /// Returns whether an update of the search index failed in this session, since the index was last rebuilt
+    pub fn is_search_index_outdated(&self) -> bool {
+        self.controller.is_search_index_outdated()
+    }
+
+    /// Subscribes to the changes of the documents of the session that happen from now on.
+    /// The events are received with `blocking_recv`, and stop when the session ends.
+    pub fn subscribe_events(&mut self) -> Result<DocumentEventReceiver, VaultError> {
+        self.runtime.block_on(self.controller.subscribe_events())
     }
 
     pub fn list_document_names(&mut self) -> Result<Vec<String>, VaultError> {
-        let encrypted_document_names = self.server.list_documents(&self.token)?;
-        encrypted_document_names
-            .iter()
-            .map(|(.., EncryptedDocumentNameAndKey { data, key })|
-                self.encryptor_decryptor.decrypt_document_name(data, key))
-            .collect()
+        self.runtime.block_on(self.controller.list_document_names())
     }
 
+    /// Lists the documents with their ID, name, metadata, size and timestamps
+    pub fn list_documents(&mut self) -> Result<Vec<DocumentInfo>, VaultError> {
+        self.runtime.block_on(self.controller.list_documents())
+    }
 
-    fn get_id_of_document_by_name(&mut self, document_name: &str) -> Result<DocumentID, VaultError> {
-        let document_list = self.server.list_documents(&self.token)?;
-        self.encryptor_decryptor.find_document_id_from_name(&document_list, document_name).ok_or(DocumentNotFound)
+    /// Returns an iterator over the documents in the requested order, that fetches them from the server page by page
+    pub fn documents(&mut self, order: DocumentOrder, descending: bool) -> DocumentIterator<'_, A> {
+        DocumentIterator { iterator: self.controller.documents(order, descending), runtime: self.runtime.clone() }
     }
 
-    /// Downloads a document
-    pub fn download(&mut self, document_name: &str) -> Result<Document, VaultError> {
-        let document_id = self.get_id_of_document_by_name(document_name)?;
+    /// Uploads several documents, in batches of at most `MAX_BATCH_SIZE` documents per request, and adds them to the search index.
+    /// Returns the result of each upload, in the order of `documents`.
+    pub fn upload_many(&mut self, documents: &[Document]) -> Vec<Result<(), VaultError>> {
+        self.runtime.block_on(self.controller.upload_many(documents))
+    }
+
+    /// Downloads several documents, in batches of at most `MAX_BATCH_SIZE` documents per request.
+    /// Returns the result of each download, in the order of `document_names`.
+    pub fn download_many(&mut self, document_names: &[&str]) -> Result<Vec<Result<Document, VaultError>>, VaultError> {
+        self.runtime.block_on(self.controller.download_many(document_names))
+    }
 
-        let encrypted_document = self.server.get_document(&self.token, &document_id)?;
-        let document_key = self.server.get_document_key(&self.token, &document_id)?;
+    /// Downloads a document.
+    /// Fails with AmbiguousDocumentName if several documents have this name, in which case `download_by_id` must be used.
+    pub fn download(&mut self, document_name: &str) -> Result<Document, VaultError> {
+        self.runtime.block_on(self.controller.download(document_name))
+    }
 
-        self.encryptor_decryptor.decrypt_document(&encrypted_document, &document_key)
+    /// Downloads a document by ID, and records its version as the one on which its next update is based.
+    pub fn download_by_id(&mut self, document_id: &DocumentID) -> Result<Document, VaultError> {
+        self.runtime.block_on(self.controller.download_by_id(document_id))
     }
 
-    /// Updates a document
+    /// Updates a document and its entry in the search index.
+    /// Fails with AmbiguousDocumentName if several documents have this name, in which case `update_by_id` must be used.
     pub fn update(&mut self, document_name: &str, new_document: &Document) -> Result<(), VaultError> {
-        let document_id = self.get_id_of_document_by_name(document_name)?;
+        self.runtime.block_on(self.controller.update(document_name, new_document))
+    }
+
+    /// Returns the version of a document that was downloaded or stored last in this session, on which its next update is based
+    pub fn document_version(&self, document_id: &DocumentID) -> Option<u64> {
+        self.controller.document_version(document_id)
+    }
+
+    /// Updates a document, designated by its ID, and its entry in the search index.
+    pub fn update_by_id(&mut self, document_id: &DocumentID, new_document: &Document) -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.update_by_id(document_id, new_document))
+    }
+
+    /// Updates a document, designated by its ID, if it is still at `version`. Fails with VersionConflict otherwise.
+    pub fn update_by_id_at_version(&mut self, document_id: &DocumentID, new_document: &Document, version: u64) -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.update_by_id_at_version(document_id, new_document, version))
+    }
+
+    /// Invites an other organization to access a document with `permission`, until `expires_at` if any.
+    /// The other organization gets access once it accepts the invitation with `accept_share`. Only the owners of a document may share it.
+    /// Fails with AmbiguousDocumentName if several documents have this name, in which case `share_by_id` must be used.
+    pub fn share(&mut self, document_name: &str, other_organization_name: &str, permission: Permission, expires_at: Option<SystemTime>)
+                 -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.share(document_name, other_organization_name, permission, expires_at))
+    }
+
+    /// Invites an other organization to access a document, designated by its ID, like `share`.
+    pub fn share_by_id(&mut self, document_id: &DocumentID, other_organization_name: &str, permission: Permission, expires_at: Option<SystemTime>)
+                       -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.share_by_id(document_id, other_organization_name, permission, expires_at))
+    }
+
+    /// Creates a link to a copy of a document, for a recipient that has no organization on the server.
+    /// The link can be opened once, until `expires_at`.
+    pub fn create_share_link(&mut self, document_name: &str, expires_at: SystemTime) -> Result<ShareLink, VaultError> {
+        self.runtime.block_on(self.controller.create_share_link(document_name, expires_at))
+    }
 
-        let document_key = self.server.get_document_key(&self.token, &document_id)?;
-        let new_document_encrypted = self.encryptor_decryptor.encrypt_document_with_key(new_document, &document_key)?;
-        self.server.update_document(&self.token, &document_id, &new_document_encrypted)
+    /// Creates a link to a copy of a document, designated by its ID, like `create_share_link`.
+    pub fn create_share_link_by_id(&mut self, document_id: &DocumentID, expires_at: SystemTime) -> Result<ShareLink, VaultError> {
+        self.runtime.block_on(self.controller.create_share_link_by_id(document_id, expires_at))
     }
 
-    /// Allows an other organization to become an owner of a document
-    pub fn share(&mut self, document_name: &str, other_organization_name: &str) -> Result<(), VaultError> {
-        let document_id = self.get_id_of_document_by_name(document_name)?;
+    /// Lists the pending invitations of other organizations to access their documents
+    pub fn pending_shares(&mut self) -> Result<Vec<PendingShare>, VaultError> {
+        self.runtime.block_on(self.controller.pending_shares())
+    }
+
+    /// Accepts an invitation to access a document, and adds the document to the search index
+    pub fn accept_share(&mut self, document_id: &DocumentID) -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.accept_share(document_id))
+    }
+
+    pub fn decline_share(&mut self, document_id: &DocumentID) -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.decline_share(document_id))
+    }
 
-        let encrypted_document_key = self.server.get_document_key(&self.token, &document_id)?;
-        let other_organization_public_key = self.server.get_public_key_of_organization(other_organization_name)?;
-        let new_encrypted_document_key =
-            self.encryptor_decryptor.encrypt_document_key_for_other_organization(&encrypted_document_key, &other_organization_public_key)?;
-        self.server.add_owner(&self.token, &document_id, other_organization_name, &new_encrypted_document_key)
+    /// Returns the organizations that have access to a document, including this one, with their permission.
+    /// Fails with AmbiguousDocumentName if several documents have this name, in which case `owners_by_id` must be used.
+    pub fn owners(&mut self, document_name: &str) -> Result<Vec<DocumentOwner>, VaultError> {
+        self.runtime.block_on(self.controller.owners(document_name))
     }
 
-    /// Deletes a document. The document is still accessible by the other owners.
+    /// Returns the organizations that have access to a document, designated by its ID, with their permission.
+    pub fn owners_by_id(&mut self, document_id: &DocumentID) -> Result<Vec<DocumentOwner>, VaultError> {
+        self.runtime.block_on(self.controller.owners_by_id(document_id))
+    }
+
+    /// Removes the access of an other organization to a document, and rotates the document key.
+    /// Fails with AmbiguousDocumentName if several documents have this name, in which case `remove_owner_by_id` must be used.
+    pub fn remove_owner(&mut self, document_name: &str, other_organization_name: &str) -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.remove_owner(document_name, other_organization_name))
+    }
+
+    /// Removes the access of an other organization to a document, designated by its ID, and rotates the document key.
+    pub fn remove_owner_by_id(&mut self, document_id: &DocumentID, other_organization_name: &str) -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.remove_owner_by_id(document_id, other_organization_name))
+    }
+
+    /// Deletes a document and removes it from the search index. The document is still accessible by the other owners.
+    /// Fails with AmbiguousDocumentName if several documents have this name, in which case `delete_by_id` must be used.
     pub fn delete(&mut self, document_name: &str) -> Result<(), VaultError> {
-        let document_id = self.get_id_of_document_by_name(document_name)?;
-        self.server.delete_document(&self.token, &document_id)
+        self.runtime.block_on(self.controller.delete(document_name))
+    }
+
+    /// Deletes a document, designated by its ID, and removes it from the search index.
+    pub fn delete_by_id(&mut self, document_id: &DocumentID) -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.delete_by_id(document_id))
+    }
+
+    /// Lists the direct subfolders and documents of a folder. `""` designates the root folder.
+    pub fn list_folder(&mut self, folder: &str) -> Result<FolderContent, VaultError> {
+        self.runtime.block_on(self.controller.list_folder(folder))
+    }
+
+    /// Moves a document to a new path. Fails with an InputError if a document already has this path.
+    pub fn move_document(&mut self, path: &str, new_path: &str) -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.move_document(path, new_path))
+    }
+
+    /// Moves a folder, with its documents and subfolders, to a new path
+    pub fn move_folder(&mut self, folder: &str, new_folder: &str) -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.move_folder(folder, new_folder))
+    }
+
+    /// Deletes a folder, with its documents and subfolders. The documents are still accessible by their other owners.
+    pub fn delete_folder(&mut self, folder: &str) -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.delete_folder(folder))
+    }
+
+    /// Creates a group of users of the organization, with its own key pair
+    pub fn create_group(&mut self, group_name: &str, members: &[&str]) -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.create_group(group_name, members))
+    }
+
+    /// Lists the groups that have one of the users who unlocked the session as a member
+    pub fn list_groups(&mut self) -> Result<Vec<GroupInfo>, VaultError> {
+        self.runtime.block_on(self.controller.list_groups())
+    }
+
+    /// Opens a session of a group, to access the documents of the group
+    pub fn open_group(&mut self, group_name: &str) -> Result<Controller<A>, VaultError> {
+        let controller = self.runtime.block_on(self.controller.open_group(group_name))?;
+        Ok(Controller { controller, runtime: self.runtime.clone() })
+    }
+
+    pub fn add_group_member(&mut self, group_name: &str, user_name: &str) -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.add_group_member(group_name, user_name))
+    }
+
+    /// Removes a member of a group. The key pair of the group stays the same.
+    pub fn remove_group_member(&mut self, group_name: &str, user_name: &str) -> Result<(), VaultError> {
+        self.runtime.block_on(self.controller.remove_group_member(group_name, user_name))
+    }
+}
+
+/// Blocking wrapper around `AsyncDocumentIterator`
+pub struct DocumentIterator<'a, A: AsyncServerConnection + Clone + 'static> {
+    iterator: AsyncDocumentIterator<'a, A>,
+    runtime: Arc<Runtime>,
+}
+
+impl<'a, A: AsyncServerConnection + Clone + 'static> Iterator for DocumentIterator<'a, A> {
+    type Item = Result<DocumentInfo, VaultError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.iterator.next())
     }
 }
 
 // We implement drop to ensure that the session token is revoked when the controller is destroyed
-impl<A: ServerConnection + Clone> Drop for Controller<A> {
+impl<A: AsyncServerConnection + Clone + 'static> Drop for Controller<A> {
     fn drop(&mut self) {
-        if let Err(_) = self.revoke_token(){
+        if !self.controller.is_token_revoked() && self.revoke_token().is_err() {
             eprintln!("Error: could not revoke sessions token, the server responded with an error")
         }
     }
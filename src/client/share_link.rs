@@ -0,0 +1,95 @@
+//! One-time links to a copy of a document, for recipients that have no organization on the server
+//!
+//! The copy is encrypted with a new key, which is only written in the fragment of the link URL.
+//! HTTP clients do not send the fragment to the server, so the server never learns the key.
+
+use data_encoding::{BASE64URL_NOPAD, Encoding};
+use dryoc::dryocsecretbox;
+
+use crate::data::{Document, ShareLinkID};
+use crate::error::VaultError;
+use crate::error::VaultError::InputError;
+use crate::http_api::OPEN_SHARE_LINK_ENDPOINT;
+use crate::server_connection::{AsyncServerConnection, ServerConnection};
+
+/// Encoding of the link ID and of the key in the link URL
+const LINK_ENCODING: Encoding = BASE64URL_NOPAD;
+const LINK_ID_QUERY_PARAMETER: &str = "link_id";
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct ShareLink {
+    pub link_id: ShareLinkID,
+    key: dryocsecretbox::Key,
+}
+
+impl ShareLink {
+    pub(crate) fn new(link_id: ShareLinkID, key: dryocsecretbox::Key) -> Self {
+        ShareLink { link_id, key }
+    }
+
+    /// Returns the URL of the link, e.g. `https://vault.example.com:1234/v1/open_share_link?link_id=<ID>#<key>`
+    pub fn to_url(&self, server_hostname: &str, server_port: u16) -> String {
+        format!(
+            "https://{server_hostname}:{server_port}{OPEN_SHARE_LINK_ENDPOINT}?{LINK_ID_QUERY_PARAMETER}={}#{}",
+            LINK_ENCODING.encode(&self.link_id),
+            LINK_ENCODING.encode(self.key.as_ref()),
+        )
+    }
+
+    /// Parses a link URL. Returns the hostname and the port of the server, and the link.
+    pub fn from_url(url: &str) -> Result<(String, u16, ShareLink), VaultError> {
+        let url = reqwest::Url::parse(url).map_err(|_| InputError)?;
+        if url.scheme() != "https" || url.path() != OPEN_SHARE_LINK_ENDPOINT {
+            return Err(InputError);
+        }
+        let server_hostname = url.host_str().ok_or(InputError)?.to_string();
+        let server_port = url.port_or_known_default().ok_or(InputError)?;
+
+        let (_, encoded_link_id) = url.query_pairs()
+            .find(|(name, _)| name == LINK_ID_QUERY_PARAMETER)
+            .ok_or(InputError)?;
+        let link_id = LINK_ENCODING.decode(encoded_link_id.as_bytes()).map_err(|_| InputError)?;
+        let key = LINK_ENCODING.decode(url.fragment().ok_or(InputError)?.as_bytes()).map_err(|_| InputError)?;
+        let key = <[u8; dryoc::constants::CRYPTO_SECRETBOX_KEYBYTES]>::try_from(key).map_err(|_| InputError)?.into();
+
+        Ok((server_hostname, server_port, ShareLink { link_id, key }))
+    }
+
+    /// Downloads the copy of the document and decrypts it. The link can not be opened again.
+    pub async fn open<A: AsyncServerConnection>(&self, server: &mut A) -> Result<Document, VaultError> {
+        server.open_share_link(&self.link_id).await?.decrypt(&self.key)
+    }
+
+    /// Blocking counterpart of `open`
+    pub fn open_blocking<S: ServerConnection>(&self, server: &mut S) -> Result<Document, VaultError> {
+        server.open_share_link(&self.link_id)?.decrypt(&self.key)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use dryoc::dryocsecretbox::NewByteArray;
+
+    use super::*;
+
+    #[test]
+    fn url() {
+        let share_link = ShareLink::new(vec![1, 2, 3], dryocsecretbox::Key::gen());
+        let url = share_link.to_url("vault.example.com", 1234);
+        assert!(url.starts_with("https://vault.example.com:1234/v1/open_share_link?link_id=AQID#"));
+
+        assert_eq!(ShareLink::from_url(&url).unwrap(), ("vault.example.com".to_string(), 1234, share_link));
+    }
+
+    #[test]
+    fn invalid_url() {
+        let url = ShareLink::new(vec![1, 2, 3], dryocsecretbox::Key::gen()).to_url("vault.example.com", 1234);
+
+        let (url_without_key, _) = url.split_once('#').unwrap();
+        assert!(matches!(ShareLink::from_url(url_without_key), Err(InputError)));
+        assert!(matches!(ShareLink::from_url(&url.replace("link_id", "id")), Err(InputError)));
+        assert!(matches!(ShareLink::from_url(&url.replace("https", "http")), Err(InputError)));
+        assert!(matches!(ShareLink::from_url(&format!("{url}AAAA")), Err(InputError)));
+    }
+}
@@ -0,0 +1,309 @@
+//! Synchronization of a local directory with a folder of the vault
+//!
+//! Each file of the directory tree corresponds to the document of the vault folder with the same relative path.
+//! The state of the last synchronization is stored in the directory, in `SYNC_STATE_FILE_NAME`: for each file, it records
+//! the ID and the version of its document, and a hash of its content. A file was changed locally if the hash of its content
+//! differs, and its document was changed in the vault if its ID or version differs. Only the changed side is copied to the other.
+//!
+//! When both sides were changed, the file is a conflict and is left as is, unless both have the same content.
+//! A conflict is resolved by moving the local file away: the document is then downloaded again, and the file can be merged into it.
+//! A deletion on one side is ignored if the other side was changed, so that no change is lost.
+//!
+//! A document whose path would not stay inside the directory, e.g. a shared document named `folder/../../.bashrc`, is not
+//! synchronized and is reported as an error.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use dryoc::generichash::{GenericHash, Key};
+use serde::{Deserialize, Serialize};
+
+use crate::client::document_path::{is_contained_path, join_path, normalize_path, relative_path};
+use crate::client::session_controller::Controller;
+use crate::data::{Document, DocumentID, DocumentInfo};
+use crate::error::VaultError;
+use crate::error::VaultError::{AmbiguousDocumentName, CryptographyError, FileError, InputError};
+use crate::server::serde_json_disk::{load, save};
+use crate::server_connection::AsyncServerConnection;
+
+/// Name of the file in which the state of the last synchronization is stored, at the root of the local directory
+pub const SYNC_STATE_FILE_NAME: &str = ".vault_sync";
+
+#[derive(Serialize, Deserialize)]
+struct SyncState {
+    vault_folder: String,
+    /// Synchronized files, by path relative to the directory
+    files: HashMap<String, SyncedFile>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct SyncedFile {
+    document_id: DocumentID,
+    version: u64,
+    content_hash: Vec<u8>,
+}
+
+/// Operation that synchronizes a file with its document
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SyncAction {
+    Upload,
+    Update,
+    Download,
+    DeleteFile,
+    DeleteDocument,
+    Conflict,
+}
+
+/// Result of a synchronization. The files are designated by their path relative to the directory.
+/// In dry-run mode, it lists the operations that would be performed.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Clone)]
+pub struct SyncReport {
+    /// Files uploaded as new documents
+    pub uploaded: Vec<String>,
+    /// Files whose document was updated with their content
+    pub updated: Vec<String>,
+    /// Files written with the content of their document
+    pub downloaded: Vec<String>,
+    /// Files deleted as their document was deleted
+    pub deleted_files: Vec<String>,
+    /// Documents deleted as their file was deleted
+    pub deleted_documents: Vec<String>,
+    /// Files changed both locally and in the vault, which were left as is
+    pub conflicts: Vec<String>,
+    /// Files whose synchronization failed
+    pub errors: Vec<(String, VaultError)>,
+    pub unchanged: usize,
+}
+
+impl SyncReport {
+    fn record(&mut self, path: String, action: SyncAction) {
+        match action {
+            SyncAction::Upload => self.uploaded.push(path),
+            SyncAction::Update => self.updated.push(path),
+            SyncAction::Download => self.downloaded.push(path),
+            SyncAction::DeleteFile => self.deleted_files.push(path),
+            SyncAction::DeleteDocument => self.deleted_documents.push(path),
+            SyncAction::Conflict => self.conflicts.push(path),
+        }
+    }
+}
+
+impl fmt::Display for SyncReport {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sections = [
+            ("Uploaded", &self.uploaded),
+            ("Updated", &self.updated),
+            ("Downloaded", &self.downloaded),
+            ("Deleted locally", &self.deleted_files),
+            ("Deleted from the vault", &self.deleted_documents),
+            ("Conflicts", &self.conflicts),
+        ];
+        for (title, paths) in sections {
+            for path in paths {
+                writeln!(formatter, "{title}: {path}")?;
+            }
+        }
+        for (path, error) in &self.errors {
+            writeln!(formatter, "Error: {path}: {error:?}")?;
+        }
+        write!(
+            formatter,
+            "{} uploaded, {} updated, {} downloaded, {} deleted locally, {} deleted from the vault, {} conflicts, {} errors, {} unchanged",
+            self.uploaded.len(), self.updated.len(), self.downloaded.len(), self.deleted_files.len(), self.deleted_documents.len(),
+            self.conflicts.len(), self.errors.len(), self.unchanged,
+        )
+    }
+}
+
+/// Synchronizes `local_directory` with `vault_folder`, in both directions.
+/// In dry-run mode, only reports what would be done, without changing the directory, the vault or the synchronization state.
+pub fn sync_directory<A: AsyncServerConnection + Clone + 'static>(controller: &mut Controller<A>, local_directory: &Path, vault_folder: &str, dry_run: bool)
+                                                                 -> Result<SyncReport, VaultError> {
+    let vault_folder = normalize_path(vault_folder);
+    let state_path = local_directory.join(SYNC_STATE_FILE_NAME);
+    let mut state = load::<SyncState>(&state_path).ok()
+        .filter(|state| state.vault_folder == vault_folder)
+        .unwrap_or_else(|| SyncState { vault_folder: vault_folder.clone(), files: HashMap::new() });
+
+    let mut report = SyncReport::default();
+    let mut local_files = BTreeSet::new();
+    list_local_files(local_directory, "", &mut local_files)?;
+    let mut documents: HashMap<String, Vec<DocumentInfo>> = HashMap::new();
+    for document_info in controller.list_documents()? {
+        match relative_path(&document_info.name, &vault_folder) {
+            Some(path) if is_contained_path(path) && path != SYNC_STATE_FILE_NAME => documents.entry(path.to_string()).or_default().push(document_info),
+            Some(path) => report.errors.push((path.to_string(), InputError)),
+            None => {}
+        }
+    }
+
+    let paths: BTreeSet<_> = local_files.iter().cloned()
+        .chain(documents.keys().cloned())
+        .chain(state.files.keys().cloned())
+        .collect();
+    let mut uploaded_files = Vec::new();
+    for path in paths {
+        let file = FileSync {
+            path: &path,
+            local_path: &local_directory.join(&path),
+            vault_folder: &vault_folder,
+            synced: state.files.get(&path).cloned(),
+        };
+        let local_content = local_files.contains(&path).then(|| fs::read_to_string(file.local_path));
+        let result = match (local_content.transpose(), documents.get(&path).map(Vec::as_slice)) {
+            (_, Some([_, _, ..])) => Err(AmbiguousDocumentName),
+            (Err(_), _) => Err(FileError),
+            (Ok(local_content), document_info) => file.sync(controller, local_content, document_info.and_then(<[_]>::first), dry_run),
+        };
+        match result {
+            Ok((action, synced)) => {
+                match action {
+                    Some(action) => report.record(path.clone(), action),
+                    None => report.unchanged += 1,
+                }
+                match synced {
+                    SyncedState::Known(synced) => { state.files.insert(path, synced); }
+                    SyncedState::Uploaded(content_hash) => uploaded_files.push((path, content_hash)),
+                    SyncedState::Removed => { state.files.remove(&path); }
+                    SyncedState::Unchanged => {}
+                }
+            }
+            Err(error) => report.errors.push((path, error)),
+        }
+    }
+
+    if dry_run {
+        return Ok(report);
+    }
+
+    // The IDs of the new documents are only known by listing the documents again
+    if !uploaded_files.is_empty() {
+        let document_infos = controller.list_documents()?;
+        for (path, content_hash) in uploaded_files {
+            let name = join_path(&vault_folder, &path);
+            if let [document_info] = document_infos.iter().filter(|document_info| document_info.name == name).collect::<Vec<_>>()[..] {
+                state.files.insert(path, SyncedFile { document_id: document_info.id.clone(), version: document_info.version, content_hash });
+            }
+        }
+    }
+    save(&state, &state_path, true)?;
+    Ok(report)
+}
+
+/// Synchronization state of a file after its synchronization
+enum SyncedState {
+    Known(SyncedFile),
+    /// The file was uploaded as a new document, whose ID is not known yet
+    Uploaded(Vec<u8>),
+    Removed,
+    Unchanged,
+}
+
+struct FileSync<'a> {
+    path: &'a str,
+    local_path: &'a Path,
+    vault_folder: &'a str,
+    synced: Option<SyncedFile>,
+}
+
+impl FileSync<'_> {
+    /// Chooses the action that synchronizes the file and performs it, unless in dry-run mode
+    fn sync<A: AsyncServerConnection + Clone + 'static>(&self, controller: &mut Controller<A>, local_content: Option<String>,
+                                                       document_info: Option<&DocumentInfo>, dry_run: bool)
+                                                       -> Result<(Option<SyncAction>, SyncedState), VaultError> {
+        let local_hash = local_content.as_deref().map(content_hash).transpose()?;
+        let locally_changed = match (&local_hash, &self.synced) {
+            (Some(local_hash), Some(synced)) => *local_hash != synced.content_hash,
+            (local_hash, synced) => local_hash.is_some() != synced.is_some(),
+        };
+        let changed_in_vault = match (document_info, &self.synced) {
+            (Some(document_info), Some(synced)) => document_info.id != synced.document_id || document_info.version != synced.version,
+            (document_info, synced) => document_info.is_some() != synced.is_some(),
+        };
+
+        let action = match (local_content.is_some(), document_info, locally_changed, changed_in_vault) {
+            (false, None, _, _) => return Ok((None, SyncedState::Removed)),
+            (_, _, false, false) => None,
+            (true, Some(document_info), true, true) => {
+                let document = controller.download_by_id(&document_info.id)?;
+                if Some(&document.content) == local_content.as_ref() {
+                    let synced = self.synced_file(controller, document_info, &document.content)?;
+                    return Ok((None, if dry_run { SyncedState::Unchanged } else { SyncedState::Known(synced) }));
+                }
+                Some(SyncAction::Conflict)
+            }
+            (true, None, false, true) => Some(SyncAction::DeleteFile),
+            (true, None, _, _) => Some(SyncAction::Upload),
+            (true, Some(_), true, false) => Some(SyncAction::Update),
+            (false, Some(_), true, false) => Some(SyncAction::DeleteDocument),
+            (_, Some(_), _, true) => Some(SyncAction::Download),
+        };
+        if dry_run {
+            return Ok((action, SyncedState::Unchanged));
+        }
+
+        let synced = match (action, local_content, document_info) {
+            (Some(SyncAction::Upload), Some(content), _) => {
+                controller.upload(&self.document(content))?;
+                SyncedState::Uploaded(local_hash.ok_or(FileError)?)
+            }
+            (Some(SyncAction::Update), Some(content), Some(document_info)) => {
+                controller.update_by_id_at_version(&document_info.id, &self.document(content), document_info.version)?;
+                let version = controller.document_version(&document_info.id).unwrap_or(document_info.version);
+                SyncedState::Known(SyncedFile { document_id: document_info.id.clone(), version, content_hash: local_hash.ok_or(FileError)? })
+            }
+            (Some(SyncAction::Download), _, Some(document_info)) => {
+                let document = controller.download_by_id(&document_info.id)?;
+                fs::create_dir_all(self.local_path.parent().ok_or(FileError)?).map_err(|_| FileError)?;
+                fs::write(self.local_path, &document.content).map_err(|_| FileError)?;
+                SyncedState::Known(self.synced_file(controller, document_info, &document.content)?)
+            }
+            (Some(SyncAction::DeleteDocument), _, Some(document_info)) => {
+                controller.delete_by_id(&document_info.id)?;
+                SyncedState::Removed
+            }
+            (Some(SyncAction::DeleteFile), _, _) => {
+                fs::remove_file(self.local_path).map_err(|_| FileError)?;
+                SyncedState::Removed
+            }
+            _ => SyncedState::Unchanged,
+        };
+        Ok((action, synced))
+    }
+
+    fn document(&self, content: String) -> Document {
+        Document { name: join_path(self.vault_folder, self.path), content }
+    }
+
+    /// Synchronization state of a file that has the content of its document, which was just downloaded
+    fn synced_file<A: AsyncServerConnection + Clone + 'static>(&self, controller: &Controller<A>, document_info: &DocumentInfo, content: &str)
+                                                              -> Result<SyncedFile, VaultError> {
+        Ok(SyncedFile {
+            document_id: document_info.id.clone(),
+            version: controller.document_version(&document_info.id).unwrap_or(document_info.version),
+            content_hash: content_hash(content)?,
+        })
+    }
+}
+
+fn content_hash(content: &str) -> Result<Vec<u8>, VaultError> {
+    GenericHash::hash_with_defaults_to_vec::<_, Key>(content.as_bytes(), None).map_err(|_| CryptographyError)
+}
+
+/// Adds the paths of the files of `directory` and its subdirectories, relative to the root directory, to `paths`
+fn list_local_files(directory: &Path, relative_directory: &str, paths: &mut BTreeSet<String>) -> Result<(), VaultError> {
+    for entry in fs::read_dir(directory).map_err(|_| FileError)? {
+        let entry = entry.map_err(|_| FileError)?;
+        let file_name = entry.file_name().into_string().map_err(|_| FileError)?;
+        let path = join_path(relative_directory, &file_name);
+        let file_type = entry.file_type().map_err(|_| FileError)?;
+        if file_type.is_dir() {
+            list_local_files(&entry.path(), &path, paths)?;
+        } else if file_type.is_file() && path != SYNC_STATE_FILE_NAME {
+            paths.insert(path);
+        }
+    }
+    Ok(())
+}
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+
 use dryoc::{dryocbox, pwhash};
 use dryoc::dryocbox::DryocBox;
 use serde::Deserialize;
@@ -18,14 +21,42 @@ impl Document {
         EncryptedDocument {
             name: SymEncryptedData::encrypt(&self.name.as_bytes(), &key),
             content: SymEncryptedData::encrypt(&self.content.as_bytes(), &key),
+            metadata: None,
+            version: 0,
         }
     }
 }
 
+/// Information about a document that is encrypted by the client, along with the document
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct DocumentMetadata {
+    /// MIME type of the content
+    pub content_type: Option<String>,
+    /// Name of the file the document was created from
+    pub original_filename: Option<String>,
+}
+
+impl DocumentMetadata {
+    pub fn encrypt(&self, key: &dryoc::dryocsecretbox::Key) -> Result<SymEncryptedData, VaultError> {
+        let serialized_metadata = serde_json::to_vec(self).map_err(|_| CryptographyError)?;
+        Ok(SymEncryptedData::encrypt(&serialized_metadata, key))
+    }
+
+    pub fn decrypt(encrypted_metadata: &SymEncryptedData, key: &dryoc::dryocsecretbox::Key) -> Result<Self, VaultError> {
+        serde_json::from_slice(&encrypted_metadata.decrypt(key)?).map_err(|_| CryptographyError)
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct EncryptedDocument {
     pub name: SymEncryptedData,
     pub content: SymEncryptedData,
+    /// Encrypted `DocumentMetadata`. When a document is updated without metadata, the server keeps the previous metadata.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<SymEncryptedData>,
+    /// Set by the server, which increments it at each update of the document. The version sent by the client is ignored.
+    #[serde(default)]
+    pub version: u64,
 }
 
 impl EncryptedDocument {
@@ -44,6 +75,8 @@ impl EncryptedDocument {
         Self {
             name: SymEncryptedData::create_random(),
             content: SymEncryptedData::create_random(),
+            metadata: None,
+            version: 0,
         }
     }
 }
@@ -54,6 +87,167 @@ pub struct EncryptedDocumentNameAndKey {
     pub key: EncryptedDocumentKey,
 }
 
+/// Description of a document, as listed by the server
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct EncryptedDocumentInfo {
+    pub document_id: DocumentID,
+    pub name_and_key: EncryptedDocumentNameAndKey,
+    /// Encrypted `DocumentMetadata`, if the document has some
+    #[serde(default)]
+    pub metadata: Option<SymEncryptedData>,
+    /// Size of the encrypted content, in bytes
+    #[serde(default)]
+    pub size: u64,
+    /// Creation time, in seconds since the Unix epoch
+    #[serde(default)]
+    pub created_at: u64,
+    /// Last modification time, in seconds since the Unix epoch
+    #[serde(default)]
+    pub modified_at: u64,
+    /// Version of the document, incremented at each update
+    #[serde(default)]
+    pub version: u64,
+}
+
+/// Timestamp by which the documents are ordered when they are listed by pages
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentOrder {
+    #[default]
+    CreatedAt,
+    ModifiedAt,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct DocumentPageRequest {
+    pub order: DocumentOrder,
+    /// Most recent documents first
+    pub descending: bool,
+    /// `next_cursor` of the previous page, `None` for the first page
+    pub cursor: Option<String>,
+    /// Maximum number of documents in the page
+    pub page_size: usize,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct EncryptedDocumentPage {
+    pub documents: Vec<EncryptedDocumentInfo>,
+    /// Cursor of the next page, `None` if this page is the last one
+    pub next_cursor: Option<String>,
+}
+
+/// Decrypted description of a document
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct DocumentInfo {
+    pub id: DocumentID,
+    pub name: String,
+    pub metadata: DocumentMetadata,
+    /// Size of the encrypted content, in bytes
+    pub size: u64,
+    pub created_at: SystemTime,
+    pub modified_at: SystemTime,
+    /// Version of the document, incremented at each update
+    pub version: u64,
+}
+
+/// Search index of an organization, encrypted with a random key that is itself encrypted with the organization public key.
+/// The server stores it as an opaque blob.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct EncryptedSearchIndex {
+    pub key: EncryptedDocumentKey,
+    pub data: SymEncryptedData,
+}
+
+/// Access level of an organization to a document. Each level includes the rights of the lower ones:
+/// readers may download the document, editors may also update it, and owners may also share it and remove the access of the
+/// other organizations.
+#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+pub enum Permission {
+    Reader,
+    Editor,
+    Owner,
+}
+
+/// Invitation to become an owner of a document, as listed by the server
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct EncryptedShareInvitation {
+    pub document_id: DocumentID,
+    pub from_organization_name: String,
+    /// Encrypted name of the document, and document key encrypted for the invited organization
+    pub name_and_key: EncryptedDocumentNameAndKey,
+    /// Permission that the invited organization gets when it accepts
+    pub permission: Permission,
+    /// Time after which the access ends, in seconds since the Unix epoch
+    pub expires_at: Option<u64>,
+    /// Time of the invitation, in seconds since the Unix epoch
+    pub created_at: u64,
+}
+
+/// Decrypted invitation to become an owner of a document
+#[derive(PartialEq, Debug, Clone)]
+pub struct PendingShare {
+    pub document_id: DocumentID,
+    pub document_name: String,
+    pub from_organization_name: String,
+    pub permission: Permission,
+    pub expires_at: Option<SystemTime>,
+    pub created_at: SystemTime,
+}
+
+/// Organization that holds a key for a document
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct DocumentOwner {
+    pub organization_name: String,
+    pub permission: Permission,
+    /// Time after which the organization loses its access, `None` if the access does not expire
+    #[serde(default)]
+    pub expires_at: Option<SystemTime>,
+}
+
+/// Group of users of an organization, as listed by the server.
+/// A group owns documents under the name `<organization>.<group>`, like an organization.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct EncryptedGroup {
+    pub name: String,
+    pub members: Vec<String>,
+    pub public_key: dryocbox::PublicKey,
+    /// Group private key, encrypted with the organization public key
+    pub encrypted_private_key: EncryptedGroupPrivateKey,
+    /// Group private key encrypted with the key derived from the password of each member, by user name.
+    /// The server only returns the keys of the users who unlocked the session.
+    pub member_keys: HashMap<String, SymEncryptedData>,
+}
+
+/// Group of users of an organization
+#[derive(PartialEq, Debug, Clone)]
+pub struct GroupInfo {
+    pub name: String,
+    pub members: Vec<String>,
+}
+
+/// Change of the documents of an organization, notified to its sessions
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub enum DocumentEvent {
+    /// The organization uploaded a document
+    Created { document_id: DocumentID },
+    /// A document of the organization was updated, by the organization or by an other owner
+    Updated { document_id: DocumentID },
+    /// The organization no longer has access to a document
+    Deleted { document_id: DocumentID },
+    /// An other organization invited the organization to access a document
+    Shared { document_id: DocumentID, from_organization_name: String },
+    /// Some events were dropped, as the session did not read them fast enough. The documents should be listed again.
+    EventsMissed,
+}
+
+/// Content of a folder: its direct subfolders and documents
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct FolderContent {
+    /// Names of the subfolders, sorted
+    pub folders: Vec<String>,
+    pub documents: Vec<DocumentInfo>,
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct UserShare {
     pub salt: pwhash::Salt,
@@ -80,8 +274,16 @@ pub type Token = Vec<u8>;
 
 pub const TOKEN_LENGTH_BYTES: usize = 32;
 
+pub type ShareLinkID = Vec<u8>;
+
+pub const SHARE_LINK_ID_LENGTH_BYTES: usize = 32;
+
 pub type EncryptedToken = dryocbox::VecBox;
 pub type EncryptedDocumentKey = dryocbox::VecBox;
+pub type EncryptedGroupPrivateKey = dryocbox::VecBox;
+
+/// Result of each item of a batch request, in the order of the request
+pub type BatchResults<A> = Vec<Result<A, VaultError>>;
 
 pub fn random_encrypted_document_key() -> EncryptedDocumentKey {
     DryocBox::seal_to_vecbox("a".as_bytes(), &dryocbox::KeyPair::gen().public_key)
@@ -1,13 +1,27 @@
-#[derive(Debug, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+/// Errors are serializable, so that the server can report the result of each item of a batch request
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub enum VaultError {
     ServerError,
+    /// The connection to the server could not be established, or the request timed out
+    ServerUnreachable,
+    /// The server rejected the session token, e.g. because the session expired or was revoked. The vault must be unlocked again.
+    SessionExpired,
     FileError,
     ValidationError,
     PasswordNotStrong(Option<String>),
     NotEnoughUsers,
     DocumentNotFound,
+    /// Several documents have the name used to designate a document. The document must be designated by its ID instead.
+    AmbiguousDocumentName,
     CryptographyError,
     InputError,
+    /// The document was updated since the version on which the update is based
+    VersionConflict,
+    /// The server does not support the protocol version of the client.
+    /// Contains the protocol versions supported by the server, empty if the server predates API versioning.
+    IncompatibleServerVersion(Vec<u32>),
 }
 
 impl From<&Option<zxcvbn::feedback::Feedback>> for VaultError {
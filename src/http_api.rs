@@ -0,0 +1,317 @@
+//! Definition of the HTTPS API shared by the server and the client: endpoints and JSON bodies of the requests and responses.
+//!
+//! All the endpoints except `VERSION_ENDPOINT` are prefixed with the protocol version.
+//! The bodies use named fields, so that optional fields can be added without breaking the existing clients.
+//!
+//! Authenticated requests carry the session token in the `Authorization` header, using the bearer scheme.
+//! The token is encoded with `TOKEN_ENCODING`.
+
+use std::collections::HashMap;
+
+use dryoc::{dryocbox, pwhash};
+use data_encoding::{BASE64, Encoding};
+use serde::{Deserialize, Serialize};
+
+use crate::data::{DocumentID, DocumentOrder, DocumentOwner, EncryptedDocument, EncryptedDocumentInfo, EncryptedDocumentKey, EncryptedGroup, EncryptedGroupPrivateKey, EncryptedSearchIndex, EncryptedShareInvitation, EncryptedToken, Permission, ShareLinkID, UserShare};
+use crate::symmetric_encryption_helper::SymEncryptedData;
+use crate::error::VaultError;
+
+/// Encoding of the token in the `Authorization` header
+pub const TOKEN_ENCODING: Encoding = BASE64;
+
+/// Version of the protocol implemented by this crate
+pub const PROTOCOL_VERSION: u32 = 1;
+
+pub const VERSION_ENDPOINT: &str = "/version";
+
+pub const CREATE_ORGANIZATION_ENDPOINT: &str = "/v1/create_organization";
+pub const UNLOCK_VAULT_ENDPOINT: &str = "/v1/unlock_vault";
+pub const REVOKE_USER_ENDPOINT: &str = "/v1/revoke_user";
+pub const REVOKE_TOKEN_ENDPOINT: &str = "/v1/revoke_token";
+pub const NEW_DOCUMENT_ENDPOINT: &str = "/v1/new_document";
+pub const LIST_DOCUMENTS_ENDPOINT: &str = "/v1/list_documents";
+pub const GET_DOCUMENT_KEY_ENDPOINT: &str = "/v1/get_document_key";
+pub const GET_DOCUMENT_ENDPOINT: &str = "/v1/get_document";
+pub const UPDATE_DOCUMENT_ENDPOINT: &str = "/v1/update_document";
+pub const DELETE_DOCUMENT_ENDPOINT: &str = "/v1/delete_document";
+pub const GET_PUBLIC_KEY_ENDPOINT: &str = "/v1/get_public_key_of_organization";
+pub const ADD_OWNER_ENDPOINT: &str = "/v1/add_owner";
+pub const NEW_DOCUMENTS_ENDPOINT: &str = "/v1/new_documents";
+pub const GET_DOCUMENTS_ENDPOINT: &str = "/v1/get_documents";
+pub const LIST_SHARE_INVITATIONS_ENDPOINT: &str = "/v1/list_share_invitations";
+pub const ACCEPT_SHARE_INVITATION_ENDPOINT: &str = "/v1/accept_share_invitation";
+pub const DECLINE_SHARE_INVITATION_ENDPOINT: &str = "/v1/decline_share_invitation";
+pub const GET_DOCUMENT_OWNERS_ENDPOINT: &str = "/v1/get_document_owners";
+pub const REMOVE_OWNER_ENDPOINT: &str = "/v1/remove_owner";
+pub const ROTATE_DOCUMENT_KEY_ENDPOINT: &str = "/v1/rotate_document_key";
+pub const GET_SEARCH_INDEX_ENDPOINT: &str = "/v1/get_search_index";
+pub const SET_SEARCH_INDEX_ENDPOINT: &str = "/v1/set_search_index";
+pub const CREATE_GROUP_ENDPOINT: &str = "/v1/create_group";
+pub const LIST_GROUPS_ENDPOINT: &str = "/v1/list_groups";
+pub const OPEN_GROUP_SESSION_ENDPOINT: &str = "/v1/open_group_session";
+pub const ADD_GROUP_MEMBER_ENDPOINT: &str = "/v1/add_group_member";
+pub const REMOVE_GROUP_MEMBER_ENDPOINT: &str = "/v1/remove_group_member";
+pub const SET_GROUP_MEMBER_KEY_ENDPOINT: &str = "/v1/set_group_member_key";
+pub const CREATE_SHARE_LINK_ENDPOINT: &str = "/v1/create_share_link";
+pub const OPEN_SHARE_LINK_ENDPOINT: &str = "/v1/open_share_link";
+/// Server-sent events stream of the changes of the documents of the organization, whose data are JSON `DocumentEvent`s
+pub const EVENTS_ENDPOINT: &str = "/v1/events";
+
+/// Maximum number of documents in a batch request
+pub const MAX_BATCH_SIZE: usize = 100;
+
+/// Maximum number of documents in a page of the document listing
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct VersionResponse {
+    /// Protocol versions that the server is able to serve
+    pub protocol_versions: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateOrganizationRequest {
+    pub organization_name: String,
+    pub users_data: HashMap<String, UserShare>,
+    pub public_key: dryocbox::PublicKey,
+    pub argon2_config: pwhash::Config,
+    /// DER certificates
+    #[serde(default)]
+    pub client_certificate_authorities: Vec<Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct UnlockVaultRequest {
+    pub organization_name: String,
+    pub user_name1: String,
+    pub user_name2: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UnlockVaultResponse {
+    pub user_share1: UserShare,
+    pub user_share2: UserShare,
+    pub argon2_config: pwhash::Config,
+    pub public_key: dryocbox::PublicKey,
+    pub encrypted_token: EncryptedToken,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct RevokeUserRequest {
+    pub user_name: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct NewDocumentRequest {
+    pub encrypted_document: EncryptedDocument,
+    pub encrypted_key: EncryptedDocumentKey,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct NewDocumentResponse {
+    pub document_id: DocumentID,
+}
+
+/// Query parameters of `LIST_DOCUMENTS_ENDPOINT`.
+/// Without `page_size`, all the documents are returned; otherwise, they are returned by pages in the requested order.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
+pub struct ListDocumentsQuery {
+    pub order: Option<DocumentOrder>,
+    pub descending: Option<bool>,
+    pub cursor: Option<String>,
+    pub page_size: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct ListDocumentsResponse {
+    pub documents: Vec<EncryptedDocumentInfo>,
+    /// Cursor of the next page, if the documents are listed by pages and this page is not the last one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// Body of the requests that concern a single document
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct DocumentRequest {
+    pub document_id: DocumentID,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct GetDocumentKeyResponse {
+    pub encrypted_key: EncryptedDocumentKey,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct GetDocumentResponse {
+    pub encrypted_document: EncryptedDocument,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct UpdateDocumentRequest {
+    pub document_id: DocumentID,
+    pub encrypted_document: EncryptedDocument,
+    /// Version of the document on which the update is based. Without it, the update replaces any version.
+    #[serde(default)]
+    pub expected_version: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct UpdateDocumentResponse {
+    /// Version of the updated document
+    pub version: u64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct GetPublicKeyRequest {
+    pub organization_name: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct GetPublicKeyResponse {
+    pub public_key: dryocbox::PublicKey,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct AddOwnerRequest {
+    pub document_id: DocumentID,
+    pub other_organization_name: String,
+    pub encrypted_document_key: EncryptedDocumentKey,
+    pub permission: Permission,
+    /// Time after which the access ends, in seconds since the Unix epoch
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct ListShareInvitationsResponse {
+    pub invitations: Vec<EncryptedShareInvitation>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct GetDocumentOwnersResponse {
+    pub owners: Vec<DocumentOwner>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct RemoveOwnerRequest {
+    pub document_id: DocumentID,
+    pub other_organization_name: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct RemoveOwnerResponse {
+    /// Organizations that still own the document, for which the client must encrypt the new document key
+    pub remaining_owners: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct RotateDocumentKeyRequest {
+    pub document_id: DocumentID,
+    /// Document encrypted with the new key
+    pub encrypted_document: EncryptedDocument,
+    /// New document key, encrypted for each owner
+    pub encrypted_keys: HashMap<String, EncryptedDocumentKey>,
+    /// Version of the document that was encrypted with the new key
+    pub expected_version: u64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct NewDocumentsRequest {
+    pub documents: Vec<NewDocumentRequest>,
+}
+
+/// Result of each upload, in the order of the request
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct NewDocumentsResponse {
+    pub results: Vec<Result<DocumentID, VaultError>>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct GetDocumentsRequest {
+    pub document_ids: Vec<DocumentID>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct DownloadedDocument {
+    pub encrypted_document: EncryptedDocument,
+    pub encrypted_key: EncryptedDocumentKey,
+}
+
+/// Result of each download, in the order of the request
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct GetDocumentsResponse {
+    pub results: Vec<Result<DownloadedDocument, VaultError>>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct GetSearchIndexResponse {
+    /// `None` if the organization has not stored any search index yet
+    pub search_index: Option<EncryptedSearchIndex>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct SetSearchIndexRequest {
+    pub search_index: EncryptedSearchIndex,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreateGroupRequest {
+    pub group_name: String,
+    pub members: Vec<String>,
+    pub public_key: dryocbox::PublicKey,
+    pub encrypted_private_key: EncryptedGroupPrivateKey,
+    pub member_keys: HashMap<String, SymEncryptedData>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct ListGroupsResponse {
+    pub groups: Vec<EncryptedGroup>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct OpenGroupSessionRequest {
+    pub group_name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OpenGroupSessionResponse {
+    /// Token of the group session, encrypted with the group public key
+    pub encrypted_token: EncryptedToken,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct GroupMemberRequest {
+    pub group_name: String,
+    pub user_name: String,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct SetGroupMemberKeyRequest {
+    pub group_name: String,
+    pub user_name: String,
+    pub encrypted_private_key: SymEncryptedData,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct CreateShareLinkRequest {
+    pub document_id: DocumentID,
+    /// Copy of the document, encrypted with the key of the link
+    pub encrypted_document: EncryptedDocument,
+    /// In seconds since the Unix epoch
+    pub expires_at: u64,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct CreateShareLinkResponse {
+    pub link_id: ShareLinkID,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct OpenShareLinkRequest {
+    pub link_id: ShareLinkID,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct OpenShareLinkResponse {
+    pub encrypted_document: EncryptedDocument,
+}
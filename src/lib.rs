@@ -1,6 +1,7 @@
 pub mod symmetric_encryption_helper;
 pub mod data;
 pub mod server_connection;
+pub mod http_api;
 pub mod client;
 pub mod server;
 pub mod utils;
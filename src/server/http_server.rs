@@ -1,68 +1,179 @@
 //! Functions that make the HTTPS server run. The server only accepts TLS 1.3 connections.
 
-use std::collections::HashMap;
+use std::{fs, io};
+use std::convert::Infallible;
+use std::future::Future;
 use std::net::{Ipv4Addr, SocketAddr};
 use std::net::IpAddr::V4;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
 
-use axum::{Json, Router, routing::post};
-use axum::extract::State;
-use axum_server::tls_rustls::RustlsConfig;
-use dryoc::{dryocbox, pwhash};
+use axum::{Extension, Json, Router, routing::{get, post}};
+use axum::async_trait;
+use axum::extract::{DefaultBodyLimit, FromRef, FromRequestParts, Query, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::request::Parts;
+use axum::middleware::AddExtension;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum_server::accept::Accept;
+use axum_server::tls_rustls::{RustlsAcceptor, RustlsConfig};
+use futures_util::stream::{self, Stream};
 use reqwest::StatusCode;
 use rustls::{Certificate, PrivateKey, ServerConfig};
+use rustls::server::AllowAnyAnonymousOrAuthenticatedClient;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::broadcast::error::RecvError;
+use tokio_rustls::server::TlsStream;
+use tower_layer::Layer;
 
-use crate::data::{DocumentID, EncryptedDocument, EncryptedDocumentKey, EncryptedDocumentNameAndKey, EncryptedToken, Token, UserShare};
+use crate::data::{DocumentEvent, DocumentPageRequest, Token};
 use crate::error::VaultError;
+use crate::error::VaultError::{FileError, ServerError, VersionConflict};
+use crate::http_api::*;
 use crate::server::local_server::LocalServer;
 use crate::server::server_config::SERVER_FILES_LOCATION;
-use crate::server_connection::ServerConnection;
 use crate::utils;
 
-pub const CREATE_ORGANIZATION_ENDPOINT: &str = "/create_organization";
-pub const UNLOCK_VAULT_ENDPOINT: &str = "/unlock_vault";
-pub const REVOKE_USER_ENDPOINT: &str = "/revoke_user";
-pub const REVOKE_TOKEN_ENDPOINT: &str = "/revoke_token";
-pub const NEW_DOCUMENT_ENDPOINT: &str = "/new_document";
-pub const LIST_DOCUMENTS_ENDPOINT: &str = "/list_documents";
-pub const GET_DOCUMENT_KEY_ENDPOINT: &str = "/get_document_key";
-pub const GET_DOCUMENT_ENDPOINT: &str = "/get_document";
-pub const UPDATE_DOCUMENT_ENDPOINT: &str = "/update_document";
-pub const DELETE_DOCUMENT_ENDPOINT: &str = "/delete_document";
-pub const GET_PUBLIC_KEY_ENDPOINT: &str = "/get_public_key_of_organization";
-pub const ADD_OWNER_ENDPOINT: &str = "/add_owner";
-
 pub const SERVER_CERTIFICATE_FILE_NAME: &str = "server_certificate.pem";
 pub const SERVER_CERTIFICATE_KEY_FILE_NAME: &str = "server_certificate_key.key";
 
+/// Maximum body size of the batch upload and search index requests, larger than the default limit of the other requests
+const MAX_BATCH_REQUEST_BODY_BYTES: usize = 256 * 1024 * 1024;
+
+/// Interval at which an event stream checks that its session is still open, when no event is sent
+const EVENT_STREAM_SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Interval at which the server checks whether its certificate files have been modified
+const CERTIFICATE_WATCH_INTERVAL_SECONDS: u64 = 10;
+
+/// TLS configuration used for new connections, and the directory of the certificate and key files it is built from
+#[derive(Clone)]
+struct ServerTls {
+    config: RustlsConfig,
+    certificate_directory: PathBuf,
+}
+
+/// State shared by the request handlers
+#[derive(Clone)]
+struct HttpServerState {
+    local_server: Arc<Mutex<LocalServer>>,
+    tls: ServerTls,
+}
+
+impl FromRef<HttpServerState> for Arc<Mutex<LocalServer>> {
+    fn from_ref(state: &HttpServerState) -> Self {
+        state.local_server.clone()
+    }
+}
+
+impl FromRef<HttpServerState> for ServerTls {
+    fn from_ref(state: &HttpServerState) -> Self {
+        state.tls.clone()
+    }
+}
+
+/// Certificate chain presented by the client during the TLS handshake, empty if the client did not present any.
+///
+/// It is added to each request by `ClientCertificatesAcceptor`.
+#[derive(Clone)]
+struct ClientCertificates(Vec<Certificate>);
+
+/// Performs the TLS handshake and makes the client certificates available to the request handlers
+#[derive(Clone)]
+struct ClientCertificatesAcceptor {
+    rustls_acceptor: RustlsAcceptor,
+}
+
+impl<I, S> Accept<I, S> for ClientCertificatesAcceptor
+    where
+        I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        S: Send + 'static,
+{
+    type Stream = TlsStream<I>;
+    type Service = AddExtension<S, ClientCertificates>;
+    type Future = Pin<Box<dyn Future<Output=io::Result<(Self::Stream, Self::Service)>> + Send>>;
+
+    fn accept(&self, stream: I, service: S) -> Self::Future {
+        let rustls_acceptor = self.rustls_acceptor.clone();
+
+        Box::pin(async move {
+            let (stream, service) = rustls_acceptor.accept(stream, service).await?;
+            let client_certificates = ClientCertificates(
+                stream.get_ref().1.peer_certificates().map(|certificates| certificates.to_vec()).unwrap_or_default()
+            );
+            Ok((stream, Extension(client_certificates).layer(service)))
+        })
+    }
+}
+
+/// Session of an authenticated request.
+///
+/// Extracting it reads the token from the `Authorization` header and resolves it to the organization of the session,
+/// and records the request as an activity of the session, which postpones its expiry.
+/// Requests with a missing or unknown token, or without a client certificate accepted by the organization, are rejected
+/// before the handler runs.
+struct AuthenticatedSession {
+    organization_name: String,
+    /// Users who unlocked the session
+    user_names: Vec<String>,
+    token: Token,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthenticatedSession
+    where
+        Arc<Mutex<LocalServer>>: FromRef<S>,
+        S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let token = parts.headers.get(AUTHORIZATION)
+            .and_then(|header_value| header_value.to_str().ok())
+            .and_then(|header_value| header_value.strip_prefix("Bearer "))
+            .and_then(|encoded_token| TOKEN_ENCODING.decode(encoded_token.as_bytes()).ok())
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+        let client_certificates = parts.extensions.get::<ClientCertificates>().ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let local_server = Arc::<Mutex<LocalServer>>::from_ref(state);
+        let mut local_server = lock_local_server(&local_server)?;
+        let organization_name = local_server.organization_name_from_token(&token).ok_or(StatusCode::UNAUTHORIZED)?;
+        let user_names = local_server.user_names_from_token(&token).ok_or(StatusCode::UNAUTHORIZED)?;
+        local_server.check_client_certificate(&organization_name, &client_certificates.0)
+            .map_err(|_| StatusCode::UNAUTHORIZED)?;
+        local_server.record_session_activity(&token);
+
+        Ok(AuthenticatedSession { organization_name, user_names, token })
+    }
+}
+
+pub fn run_http_server(port: u16, data_storage_directory: PathBuf) {
+    run_http_server_with_certificate(port, data_storage_directory, PathBuf::from(SERVER_FILES_LOCATION));
+}
+
+/// Runs the server with the certificate and key files of `certificate_directory`, instead of the ones of the server files
 #[tokio::main]
-pub async fn run_http_server(port: u16, data_storage_directory: PathBuf) {
-    let config = ServerConfig::builder()
-        .with_safe_default_cipher_suites()
-        .with_safe_default_kx_groups()
-        // We only allow TLS 1.3
-        .with_protocol_versions(&[&rustls::version::TLS13]).expect("Could not configure server with TLS 1.3")
-        .with_no_client_auth()
-        .with_single_cert(
-            vec![
-                Certificate(utils::get_certificate_der_from_pem_file(
-                    &PathBuf::from(SERVER_FILES_LOCATION).join(SERVER_CERTIFICATE_FILE_NAME).into())
-                    .expect("Could not read server certificate")
-                )
-            ],
-            PrivateKey(
-                utils::get_key_der_from_pem_file(&PathBuf::from(SERVER_FILES_LOCATION).join(SERVER_CERTIFICATE_KEY_FILE_NAME).into())
-                    .expect("Could not read server key")
-            ),
-        )
-        .expect("Could not build server configuration");
-
-    let server_state = Arc::new(Mutex::new(
+pub async fn run_http_server_with_certificate(port: u16, data_storage_directory: PathBuf, certificate_directory: PathBuf) {
+    let local_server = Arc::new(Mutex::new(
         LocalServer::new(&PathBuf::from(data_storage_directory))
     ));
 
+    let client_certificate_authorities = local_server.lock().expect("Could not lock local server")
+        .all_client_certificate_authorities()
+        .expect("Could not read client certificate authorities");
+    let tls_config = RustlsConfig::from_config(Arc::new(
+        build_tls_config(&certificate_directory, &client_certificate_authorities).expect("Could not build server configuration")
+    ));
+    let tls = ServerTls { config: tls_config.clone(), certificate_directory };
+    tokio::spawn(reload_tls_config_on_certificate_change(tls.clone(), local_server.clone()));
+
+    let server_state = HttpServerState { local_server, tls };
+
     let app = Router::new()
+        .route(VERSION_ENDPOINT, get(version_handler))
         .route(CREATE_ORGANIZATION_ENDPOINT, post(create_organization_handler))
         .route(UNLOCK_VAULT_ENDPOINT, post(unlock_vault_handler))
         .route(REVOKE_USER_ENDPOINT, post(revoke_user_handler))
@@ -75,161 +186,565 @@ pub async fn run_http_server(port: u16, data_storage_directory: PathBuf) {
         .route(DELETE_DOCUMENT_ENDPOINT, post(delete_document_handler))
         .route(GET_PUBLIC_KEY_ENDPOINT, post(get_public_key_handler))
         .route(ADD_OWNER_ENDPOINT, post(add_owner_handler))
+        .route(LIST_SHARE_INVITATIONS_ENDPOINT, post(list_share_invitations_handler))
+        .route(ACCEPT_SHARE_INVITATION_ENDPOINT, post(accept_share_invitation_handler))
+        .route(DECLINE_SHARE_INVITATION_ENDPOINT, post(decline_share_invitation_handler))
+        .route(GET_DOCUMENT_OWNERS_ENDPOINT, post(get_document_owners_handler))
+        .route(REMOVE_OWNER_ENDPOINT, post(remove_owner_handler))
+        .route(ROTATE_DOCUMENT_KEY_ENDPOINT, post(rotate_document_key_handler))
+        .route(NEW_DOCUMENTS_ENDPOINT, post(new_documents_handler).layer(DefaultBodyLimit::max(MAX_BATCH_REQUEST_BODY_BYTES)))
+        .route(GET_DOCUMENTS_ENDPOINT, post(get_documents_handler))
+        .route(GET_SEARCH_INDEX_ENDPOINT, post(get_search_index_handler))
+        .route(SET_SEARCH_INDEX_ENDPOINT, post(set_search_index_handler).layer(DefaultBodyLimit::max(MAX_BATCH_REQUEST_BODY_BYTES)))
+        .route(CREATE_GROUP_ENDPOINT, post(create_group_handler))
+        .route(LIST_GROUPS_ENDPOINT, post(list_groups_handler))
+        .route(OPEN_GROUP_SESSION_ENDPOINT, post(open_group_session_handler))
+        .route(ADD_GROUP_MEMBER_ENDPOINT, post(add_group_member_handler))
+        .route(REMOVE_GROUP_MEMBER_ENDPOINT, post(remove_group_member_handler))
+        .route(SET_GROUP_MEMBER_KEY_ENDPOINT, post(set_group_member_key_handler))
+        .route(CREATE_SHARE_LINK_ENDPOINT, post(create_share_link_handler))
+        .route(OPEN_SHARE_LINK_ENDPOINT, post(open_share_link_handler))
+        .route(EVENTS_ENDPOINT, get(events_handler))
         .with_state(server_state);
 
 
-    axum_server::bind_rustls(
-        SocketAddr::new(V4(Ipv4Addr::new(0, 0, 0, 0)), port),
-        RustlsConfig::from_config(Arc::new(config)))
+    axum_server::bind(SocketAddr::new(V4(Ipv4Addr::new(0, 0, 0, 0)), port))
+        .acceptor(ClientCertificatesAcceptor { rustls_acceptor: RustlsAcceptor::new(tls_config) })
         .serve(app.into_make_service())
         .await
         .expect("Could not start server");
 }
 
+fn server_certificate_path(certificate_directory: &Path) -> PathBuf {
+    certificate_directory.join(SERVER_CERTIFICATE_FILE_NAME)
+}
+
+fn server_certificate_key_path(certificate_directory: &Path) -> PathBuf {
+    certificate_directory.join(SERVER_CERTIFICATE_KEY_FILE_NAME)
+}
+
+/// Reads the server certificate and its private key from `certificate_directory` and builds a TLS 1.3 configuration.
+///
+/// Clients may present a certificate issued by one of `client_certificate_authorities`.
+/// Whether a client certificate is required is decided per organization, when handling requests.
+fn build_tls_config(certificate_directory: &Path, client_certificate_authorities: &[Vec<u8>]) -> Result<ServerConfig, VaultError> {
+    let certificate = utils::get_certificate_der_from_pem_file(&server_certificate_path(certificate_directory)).map_err(|_| FileError)?;
+    let key = utils::get_key_der_from_pem_file(&server_certificate_key_path(certificate_directory)).map_err(|_| FileError)?;
+    let client_certificate_store = utils::certificate_store_from_der_certificates(client_certificate_authorities)
+        .map_err(|_| ServerError)?;
+
+    ServerConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        // We only allow TLS 1.3
+        .with_protocol_versions(&[&rustls::version::TLS13]).map_err(|_| ServerError)?
+        .with_client_cert_verifier(AllowAnyAnonymousOrAuthenticatedClient::new(client_certificate_store))
+        .with_single_cert(vec![Certificate(certificate)], PrivateKey(key))
+        .map_err(|_| ServerError)
+}
+
+/// Reloads the TLS configuration when the certificate or key file is modified,
+/// or when the process receives SIGHUP.
+///
+/// Existing connections, and thus the client sessions, are not affected.
+/// If the new files can not be loaded, the previous configuration is kept.
+async fn reload_tls_config_on_certificate_change(tls: ServerTls, local_server: Arc<Mutex<LocalServer>>) {
+    let mut check_interval = tokio::time::interval(Duration::from_secs(CERTIFICATE_WATCH_INTERVAL_SECONDS));
+    let mut hangup_signal = signal(SignalKind::hangup()).expect("Could not listen for SIGHUP");
+    let mut last_modification_times = certificate_files_modification_times(&tls.certificate_directory);
+
+    loop {
+        let reload_requested = tokio::select! {
+            _ = check_interval.tick() => false,
+            _ = hangup_signal.recv() => true,
+        };
+
+        let modification_times = certificate_files_modification_times(&tls.certificate_directory);
+        if !reload_requested && modification_times == last_modification_times {
+            continue;
+        }
+        last_modification_times = modification_times;
+
+        let client_certificate_authorities = match local_server.lock() {
+            Ok(local_server) => local_server.all_client_certificate_authorities(),
+            Err(_) => Err(ServerError),
+        };
+        reload_tls_config(&tls, client_certificate_authorities);
+    }
+}
+
+/// Swaps the TLS configuration used for new connections.
+/// If the new configuration can not be built, the previous one is kept.
+///
+/// The client certificate authorities are read beforehand, so that the configuration is built without holding the local server.
+fn reload_tls_config(tls: &ServerTls, client_certificate_authorities: Result<Vec<Vec<u8>>, VaultError>) {
+    let new_config = client_certificate_authorities
+        .and_then(|client_certificate_authorities| build_tls_config(&tls.certificate_directory, &client_certificate_authorities));
+
+    match new_config {
+        Ok(config) => {
+            tls.config.reload_from_config(Arc::new(config));
+            println!("TLS configuration reloaded");
+        }
+        Err(_) => eprintln!("Error: could not reload the TLS configuration, the previous one is still used"),
+    }
+}
+
+fn certificate_files_modification_times(certificate_directory: &Path) -> (Option<SystemTime>, Option<SystemTime>) {
+    let modification_time = |path: PathBuf| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    (modification_time(server_certificate_path(certificate_directory)), modification_time(server_certificate_key_path(certificate_directory)))
+}
+
+async fn version_handler() -> Json<VersionResponse> {
+    Json(VersionResponse { protocol_versions: vec![PROTOCOL_VERSION] })
+}
+
 async fn create_organization_handler(
     State(local_server): State<Arc<Mutex<LocalServer>>>,
-    Json((organization_name, users_data, public_key, argon2_config)): Json<(String, HashMap<String, UserShare>, dryocbox::PublicKey, pwhash::Config)>,
+    State(tls): State<ServerTls>,
+    Json(request): Json<CreateOrganizationRequest>,
 )
     -> Result<(), StatusCode> {
-    convert_result_to_handler_result(
-        lock_local_server(&local_server)?
-            .create_organization(&organization_name, &users_data, &public_key, &argon2_config)
-    )
+    let client_certificate_authorities = {
+        let mut local_server = lock_local_server(&local_server)?;
+        convert_result_to_handler_result(
+            local_server.create_organization(&request.organization_name, &request.users_data, &request.public_key,
+                                             &request.argon2_config, &request.client_certificate_authorities)
+        )?;
+        (!request.client_certificate_authorities.is_empty()).then(|| local_server.all_client_certificate_authorities())
+    };
+
+    // The new certificate authorities must be known by the TLS layer, so that it asks the clients of the organization for a certificate
+    if let Some(client_certificate_authorities) = client_certificate_authorities {
+        reload_tls_config(&tls, client_certificate_authorities);
+    }
+    Ok(())
 }
 
 async fn unlock_vault_handler(
     State(local_server): State<Arc<Mutex<LocalServer>>>,
-    Json((organization_name, user_name1, user_name2)): Json<(String, String, String)>,
+    Extension(client_certificates): Extension<ClientCertificates>,
+    Json(request): Json<UnlockVaultRequest>,
 )
-    -> Result<Json<(UserShare, UserShare, pwhash::Config, dryocbox::PublicKey, EncryptedToken)>, StatusCode> {
-    json_handler_result(
-        lock_local_server(&local_server)?
-            .unlock_vault(&organization_name, &user_name1, &user_name2)
-    )
+    -> Result<Json<UnlockVaultResponse>, StatusCode> {
+    let mut local_server = lock_local_server(&local_server)?;
+    convert_result_to_handler_result(
+        local_server.check_client_certificate(&request.organization_name, &client_certificates.0)
+    )?;
+    let (user_share1, user_share2, argon2_config, public_key, encrypted_token) = convert_result_to_handler_result(
+        local_server.unlock_vault(&request.organization_name, &request.user_name1, &request.user_name2)
+    )?;
+    Ok(Json(UnlockVaultResponse { user_share1, user_share2, argon2_config, public_key, encrypted_token }))
 }
 
 async fn revoke_user_handler(
     State(local_server): State<Arc<Mutex<LocalServer>>>,
-    Json((token, user_name)): Json<(Token, String)>,
+    session: AuthenticatedSession,
+    Json(request): Json<RevokeUserRequest>,
 )
     -> Result<(), StatusCode> {
     convert_result_to_handler_result(
         lock_local_server(&local_server)?
-            .revoke_user(&token, &user_name)
+            .revoke_user(&session.organization_name, &request.user_name)
     )
 }
 
 async fn revoke_token_handler(
     State(local_server): State<Arc<Mutex<LocalServer>>>,
-    Json(token): Json<Token>,
+    session: AuthenticatedSession,
 )
     -> Result<(), StatusCode> {
     convert_result_to_handler_result(
         lock_local_server(&local_server)?
-            .revoke_token(&token)
+            .revoke_token(&session.token)
     )
 }
 
 async fn new_document_handler(
     State(local_server): State<Arc<Mutex<LocalServer>>>,
-    Json((token, encrypted_document, encrypted_key)): Json<(Token, EncryptedDocument, EncryptedDocumentKey)>,
+    session: AuthenticatedSession,
+    Json(request): Json<NewDocumentRequest>,
+)
+    -> Result<Json<NewDocumentResponse>, StatusCode> {
+    let document_id = convert_result_to_handler_result(
+        lock_local_server(&local_server)?
+            .new_document(&session.organization_name, &request.encrypted_document, &request.encrypted_key)
+    )?;
+    Ok(Json(NewDocumentResponse { document_id }))
+}
+
+async fn new_documents_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+    Json(request): Json<NewDocumentsRequest>,
+)
+    -> Result<Json<NewDocumentsResponse>, StatusCode> {
+    let documents: Vec<_> = request.documents.into_iter()
+        .map(|document| (document.encrypted_document, document.encrypted_key))
+        .collect();
+    let results = convert_result_to_handler_result(
+        lock_local_server(&local_server)?
+            .new_documents(&session.organization_name, &documents)
+    )?;
+    Ok(Json(NewDocumentsResponse { results }))
+}
+
+async fn list_documents_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+    Query(query): Query<ListDocumentsQuery>,
+)
+    -> Result<Json<ListDocumentsResponse>, StatusCode> {
+    let mut local_server = lock_local_server(&local_server)?;
+
+    match query.page_size {
+        None => {
+            let documents = convert_result_to_handler_result(local_server.list_documents(&session.organization_name))?;
+            Ok(Json(ListDocumentsResponse { documents, next_cursor: None }))
+        }
+        Some(page_size) => {
+            let page_request = DocumentPageRequest {
+                order: query.order.unwrap_or_default(),
+                descending: query.descending.unwrap_or(false),
+                cursor: query.cursor,
+                page_size,
+            };
+            let page = convert_result_to_handler_result(local_server.list_documents_page(&session.organization_name, &page_request))?;
+            Ok(Json(ListDocumentsResponse { documents: page.documents, next_cursor: page.next_cursor }))
+        }
+    }
+}
+
+async fn get_document_key_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+    Json(request): Json<DocumentRequest>,
+)
+    -> Result<Json<GetDocumentKeyResponse>, StatusCode> {
+    let encrypted_key = convert_result_to_handler_result(
+        lock_local_server(&local_server)?
+            .get_document_key(&session.organization_name, &request.document_id)
+    )?;
+    Ok(Json(GetDocumentKeyResponse { encrypted_key }))
+}
+
+async fn get_document_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+    Json(request): Json<DocumentRequest>,
+)
+    -> Result<Json<GetDocumentResponse>, StatusCode> {
+    let encrypted_document = convert_result_to_handler_result(
+        lock_local_server(&local_server)?
+            .get_document(&session.organization_name, &request.document_id)
+    )?;
+    Ok(Json(GetDocumentResponse { encrypted_document }))
+}
+
+async fn get_documents_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+    Json(request): Json<GetDocumentsRequest>,
+)
+    -> Result<Json<GetDocumentsResponse>, StatusCode> {
+    let results = convert_result_to_handler_result(
+        lock_local_server(&local_server)?
+            .get_documents(&session.organization_name, &request.document_ids)
+    )?;
+    Ok(Json(GetDocumentsResponse {
+        results: results.into_iter()
+            .map(|result| result.map(|(encrypted_document, encrypted_key)| DownloadedDocument { encrypted_document, encrypted_key }))
+            .collect()
+    }))
+}
+
+async fn list_share_invitations_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+)
+    -> Result<Json<ListShareInvitationsResponse>, StatusCode> {
+    let invitations = convert_result_to_handler_result(
+        lock_local_server(&local_server)?
+            .list_share_invitations(&session.organization_name)
+    )?;
+    Ok(Json(ListShareInvitationsResponse { invitations }))
+}
+
+async fn accept_share_invitation_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+    Json(request): Json<DocumentRequest>,
 )
     -> Result<(), StatusCode> {
     convert_result_to_handler_result(
         lock_local_server(&local_server)?
-            .new_document(&token, &encrypted_document, &encrypted_key)
+            .accept_share_invitation(&session.organization_name, &request.document_id)
     )
 }
 
-async fn list_documents_handler(
+async fn decline_share_invitation_handler(
     State(local_server): State<Arc<Mutex<LocalServer>>>,
-    Json(token): Json<Token>,
+    session: AuthenticatedSession,
+    Json(request): Json<DocumentRequest>,
 )
-    -> Result<Json<Vec<(DocumentID, EncryptedDocumentNameAndKey)>>, StatusCode> {
-    json_handler_result(
+    -> Result<(), StatusCode> {
+    convert_result_to_handler_result(
         lock_local_server(&local_server)?
-            .list_documents(&token)
+            .decline_share_invitation(&session.organization_name, &request.document_id)
     )
 }
 
-async fn get_document_key_handler(
+async fn get_document_owners_handler(
     State(local_server): State<Arc<Mutex<LocalServer>>>,
-    Json((token, document_id)): Json<(Token, DocumentID)>,
+    session: AuthenticatedSession,
+    Json(request): Json<DocumentRequest>,
 )
-    -> Result<Json<EncryptedDocumentKey>, StatusCode> {
-    json_handler_result(
+    -> Result<Json<GetDocumentOwnersResponse>, StatusCode> {
+    let owners = convert_result_to_handler_result(
         lock_local_server(&local_server)?
-            .get_document_key(&token, &document_id)
-    )
+            .get_document_owners(&session.organization_name, &request.document_id)
+    )?;
+    Ok(Json(GetDocumentOwnersResponse { owners }))
 }
 
-async fn get_document_handler(
+async fn remove_owner_handler(
     State(local_server): State<Arc<Mutex<LocalServer>>>,
-    Json((token, document_id)): Json<(Token, DocumentID)>,
+    session: AuthenticatedSession,
+    Json(request): Json<RemoveOwnerRequest>,
 )
-    -> Result<Json<EncryptedDocument>, StatusCode> {
-    json_handler_result(
+    -> Result<Json<RemoveOwnerResponse>, StatusCode> {
+    let remaining_owners = convert_result_to_handler_result(
         lock_local_server(&local_server)?
-            .get_document(&token, &document_id)
+            .remove_owner(&session.organization_name, &request.document_id, &request.other_organization_name)
+    )?;
+    Ok(Json(RemoveOwnerResponse { remaining_owners }))
+}
+
+async fn rotate_document_key_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+    Json(request): Json<RotateDocumentKeyRequest>,
+)
+    -> Result<(), StatusCode> {
+    convert_result_to_handler_result(
+        lock_local_server(&local_server)?
+            .rotate_document_key(&session.organization_name, &request.document_id, &request.encrypted_document, &request.encrypted_keys,
+                                 request.expected_version)
     )
 }
 
-async fn update_document_handler(
+async fn get_search_index_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+)
+    -> Result<Json<GetSearchIndexResponse>, StatusCode> {
+    let search_index = convert_result_to_handler_result(
+        lock_local_server(&local_server)?
+            .get_search_index(&session.organization_name)
+    )?;
+    Ok(Json(GetSearchIndexResponse { search_index }))
+}
+
+async fn set_search_index_handler(
     State(local_server): State<Arc<Mutex<LocalServer>>>,
-    Json((token, document_id, encrypted_document)): Json<(Token, DocumentID, EncryptedDocument)>,
+    session: AuthenticatedSession,
+    Json(request): Json<SetSearchIndexRequest>,
 )
     -> Result<(), StatusCode> {
     convert_result_to_handler_result(
         lock_local_server(&local_server)?
-            .update_document(&token, &document_id, &encrypted_document)
+            .set_search_index(&session.organization_name, &request.search_index)
     )
 }
 
+async fn update_document_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+    Json(request): Json<UpdateDocumentRequest>,
+)
+    -> Result<Json<UpdateDocumentResponse>, StatusCode> {
+    let version = convert_result_to_handler_result(
+        lock_local_server(&local_server)?
+            .update_document(&session.organization_name, &request.document_id, &request.encrypted_document,
+                             request.expected_version)
+    )?;
+    Ok(Json(UpdateDocumentResponse { version }))
+}
+
 async fn delete_document_handler(
     State(local_server): State<Arc<Mutex<LocalServer>>>,
-    Json((token, document_id)): Json<(Token, DocumentID)>,
+    session: AuthenticatedSession,
+    Json(request): Json<DocumentRequest>,
 )
     -> Result<(), StatusCode> {
     convert_result_to_handler_result(
         lock_local_server(&local_server)?
-            .delete_document(&token, &document_id)
+            .delete_document(&session.organization_name, &request.document_id)
     )
 }
 
 async fn get_public_key_handler(
     State(local_server): State<Arc<Mutex<LocalServer>>>,
-    Json(organization_name): Json<String>,
+    Json(request): Json<GetPublicKeyRequest>,
 )
-    -> Result<Json<dryocbox::PublicKey>, StatusCode> {
-    json_handler_result(
+    -> Result<Json<GetPublicKeyResponse>, StatusCode> {
+    let public_key = convert_result_to_handler_result(
         lock_local_server(&local_server)?
-            .get_public_key_of_organization(&organization_name)
-    )
+            .get_public_key_of_organization(&request.organization_name)
+    )?;
+    Ok(Json(GetPublicKeyResponse { public_key }))
 }
 
 async fn add_owner_handler(
     State(local_server): State<Arc<Mutex<LocalServer>>>,
-    Json((token, document_id, other_organization_name, encrypted_document_key)): Json<(Token, DocumentID, String, EncryptedDocumentKey)>,
+    session: AuthenticatedSession,
+    Json(request): Json<AddOwnerRequest>,
 )
     -> Result<(), StatusCode> {
     convert_result_to_handler_result(
         lock_local_server(&local_server)?
-            .add_owner(&token, &document_id, &other_organization_name, &encrypted_document_key)
+            .add_owner(&session.organization_name, &request.document_id, &request.other_organization_name, &request.encrypted_document_key, request.permission, request.expires_at)
     )
 }
 
-fn convert_result_to_handler_result<A>(result: Result<A, VaultError>) -> Result<A, StatusCode> {
-    result.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+async fn create_group_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+    Json(request): Json<CreateGroupRequest>,
+)
+    -> Result<(), StatusCode> {
+    convert_result_to_handler_result(
+        lock_local_server(&local_server)?
+            .create_group(&session.organization_name, &request.group_name, &request.members, &request.public_key, &request.encrypted_private_key, &request.member_keys)
+    )
+}
+
+async fn list_groups_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+)
+    -> Result<Json<ListGroupsResponse>, StatusCode> {
+    let groups = convert_result_to_handler_result(
+        lock_local_server(&local_server)?
+            .list_groups(&session.organization_name, &session.user_names)
+    )?;
+    Ok(Json(ListGroupsResponse { groups }))
 }
 
-fn json_handler_result<A>(result: Result<A, VaultError>) -> Result<Json<A>, StatusCode> {
-    Ok(
-        Json(
-            convert_result_to_handler_result(result)?
-        )
+async fn open_group_session_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+    Json(request): Json<OpenGroupSessionRequest>,
+)
+    -> Result<Json<OpenGroupSessionResponse>, StatusCode> {
+    let encrypted_token = convert_result_to_handler_result(
+        lock_local_server(&local_server)?
+            .open_group_session(&session.organization_name, &session.user_names, &request.group_name)
+    )?;
+    Ok(Json(OpenGroupSessionResponse { encrypted_token }))
+}
+
+async fn add_group_member_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+    Json(request): Json<GroupMemberRequest>,
+)
+    -> Result<(), StatusCode> {
+    convert_result_to_handler_result(
+        lock_local_server(&local_server)?
+            .add_group_member(&session.organization_name, &session.user_names, &request.group_name, &request.user_name)
+    )
+}
+
+async fn remove_group_member_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+    Json(request): Json<GroupMemberRequest>,
+)
+    -> Result<(), StatusCode> {
+    convert_result_to_handler_result(
+        lock_local_server(&local_server)?
+            .remove_group_member(&session.organization_name, &session.user_names, &request.group_name, &request.user_name)
+    )
+}
+
+async fn set_group_member_key_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+    Json(request): Json<SetGroupMemberKeyRequest>,
+)
+    -> Result<(), StatusCode> {
+    convert_result_to_handler_result(
+        lock_local_server(&local_server)?
+            .set_group_member_key(&session.organization_name, &session.user_names, &request.group_name, &request.user_name, &request.encrypted_private_key)
     )
 }
 
+async fn create_share_link_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+    Json(request): Json<CreateShareLinkRequest>,
+)
+    -> Result<Json<CreateShareLinkResponse>, StatusCode> {
+    let link_id = convert_result_to_handler_result(
+        lock_local_server(&local_server)?
+            .create_share_link(&session.organization_name, &request.document_id, &request.encrypted_document, request.expires_at)
+    )?;
+    Ok(Json(CreateShareLinkResponse { link_id }))
+}
+
+/// The recipient of a share link has no session
+async fn open_share_link_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    Json(request): Json<OpenShareLinkRequest>,
+)
+    -> Result<Json<OpenShareLinkResponse>, StatusCode> {
+    let encrypted_document = convert_result_to_handler_result(
+        lock_local_server(&local_server)?
+            .open_share_link(&request.link_id)
+    )?;
+    Ok(Json(OpenShareLinkResponse { encrypted_document }))
+}
+
+/// Streams the changes of the documents of the organization of the session, until the session ends.
+/// An open stream is an activity of the session, so that a client that only listens to the events keeps its session.
+async fn events_handler(
+    State(local_server): State<Arc<Mutex<LocalServer>>>,
+    session: AuthenticatedSession,
+)
+    -> Result<Sse<impl Stream<Item=Result<Event, Infallible>>>, StatusCode> {
+    let receiver = lock_local_server(&local_server)?.subscribe_events();
+
+    let events = stream::unfold((receiver, local_server, session), |(mut receiver, local_server, session)| async move {
+        loop {
+            let received = tokio::time::timeout(EVENT_STREAM_SESSION_CHECK_INTERVAL, receiver.recv()).await;
+            {
+                let mut local_server = lock_local_server(&local_server).ok()?;
+                local_server.organization_name_from_token(&session.token)?;
+                local_server.record_session_activity(&session.token);
+            }
+
+            let event = match received {
+                Ok(Ok((organization_name, event))) if organization_name == session.organization_name => event,
+                Ok(Err(RecvError::Lagged(_))) => DocumentEvent::EventsMissed,
+                Ok(Err(RecvError::Closed)) => return None,
+                // Event of an other organization, or no event during the interval
+                _ => continue,
+            };
+            let event = Event::default().json_data(&event).ok()?;
+            return Some((Ok(event), (receiver, local_server, session)));
+        }
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+fn convert_result_to_handler_result<A>(result: Result<A, VaultError>) -> Result<A, StatusCode> {
+    result.map_err(|error| match error {
+        VersionConflict => StatusCode::CONFLICT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    })
+}
+
 fn lock_local_server(local_server: &Arc<Mutex<LocalServer>>) -> Result<MutexGuard<LocalServer>, StatusCode> {
     local_server.lock().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
-}
\ No newline at end of file
+}
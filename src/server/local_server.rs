@@ -4,38 +4,159 @@ use std::collections::HashMap;
 use std::fs;
 use std::fs::DirEntry;
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use data_encoding::BASE32;
 use dryoc::{dryocbox, pwhash, rng};
 use dryoc::dryocbox::DryocBox;
+use rustls::Certificate;
+use rustls::server::AllowAnyAuthenticatedClient;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
 
-use crate::data::{DOCUMENT_ID_LENGTH_BYTES, DocumentID, EncryptedDocumentKey, EncryptedDocumentNameAndKey, EncryptedToken, Token, UserShare};
-use crate::data::EncryptedDocument;
+use crate::data::{BatchResults, DOCUMENT_ID_LENGTH_BYTES, DocumentID, DocumentOwner, DocumentOrder, DocumentPageRequest, EncryptedDocumentInfo, EncryptedDocumentPage, EncryptedDocumentKey, EncryptedDocumentNameAndKey, EncryptedGroup, EncryptedGroupPrivateKey, EncryptedSearchIndex, EncryptedShareInvitation, EncryptedToken, Permission, SHARE_LINK_ID_LENGTH_BYTES, ShareLinkID, Token, UserShare};
+use crate::data::{DocumentEvent, EncryptedDocument};
 use crate::error::VaultError;
-use crate::error::VaultError::ServerError;
+use crate::error::VaultError::{ServerError, ValidationError, VersionConflict};
+use crate::http_api::{MAX_BATCH_SIZE, MAX_PAGE_SIZE};
 use crate::server::serde_json_disk::{load, save};
 use crate::server::session_manager::SessionManager;
-use crate::server_connection::ServerConnection;
-use crate::validation::validate_and_standardize_name;
+use crate::symmetric_encryption_helper::SymEncryptedData;
+use crate::utils::certificate_store_from_der_certificates;
+use crate::validation::{GROUP_SEPARATOR, group_owner_name, validate_and_standardize_name, validate_and_standardize_owner_name};
+
+
+/// Stored next to each document, so that the documents can be listed without reading their content
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct StoredDocumentInfo {
+    name: SymEncryptedData,
+    metadata: Option<SymEncryptedData>,
+    size: u64,
+    created_at: u64,
+    modified_at: u64,
+    #[serde(default)]
+    version: u64,
+}
+
+impl StoredDocumentInfo {
+    fn new(encrypted_document: &EncryptedDocument, created_at: u64, modified_at: u64) -> Self {
+        StoredDocumentInfo {
+            name: encrypted_document.name.clone(),
+            metadata: encrypted_document.metadata.clone(),
+            size: encrypted_document.content.ciphertext_size(),
+            created_at,
+            modified_at,
+            version: encrypted_document.version,
+        }
+    }
+}
+
+/// Document key of an organization, with the permission of the organization on the document
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct StoredDocumentKey {
+    encrypted_document_key: EncryptedDocumentKey,
+    permission: Permission,
+    /// Time after which the organization loses its access, in seconds since the Unix epoch
+    #[serde(default)]
+    expires_at: Option<u64>,
+}
+
+/// Invitation to become an owner of a document, stored in the inbox of the invited organization until it answers
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct StoredShareInvitation {
+    from_organization_name: String,
+    encrypted_document_key: EncryptedDocumentKey,
+    permission: Permission,
+    expires_at: Option<u64>,
+    created_at: u64,
+}
+
+/// Group of users of an organization. Its public key is stored like the public key of an organization.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct StoredGroup {
+    members: Vec<String>,
+    encrypted_private_key: EncryptedGroupPrivateKey,
+    member_keys: HashMap<String, SymEncryptedData>,
+}
+
+/// Copy of a document encrypted with a key that only the recipient of the link knows, which can be downloaded once
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct StoredShareLink {
+    encrypted_document: EncryptedDocument,
+    expires_at: u64,
+}
+
+/// Current time, in seconds since the Unix epoch
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0)
+}
 
+fn is_expired(expires_at: Option<u64>) -> bool {
+    expires_at.is_some_and(|expires_at| expires_at <= now())
+}
+
+/// The cursor of a page is the position of its last document: its timestamp and ID
+fn format_cursor((timestamp, document_id): &(u64, DocumentID)) -> String {
+    format!("{timestamp}.{}", BASE32.encode(document_id))
+}
+
+fn parse_cursor(cursor: &str) -> Result<(u64, DocumentID), VaultError> {
+    let (timestamp, document_id) = cursor.split_once('.').ok_or(ValidationError)?;
+    Ok((
+        timestamp.parse().map_err(|_| ValidationError)?,
+        BASE32.decode(document_id.as_bytes()).map_err(|_| ValidationError)?,
+    ))
+}
 
 pub struct LocalServer {
     data_path: PathBuf,
     sessions: SessionManager,
+    /// Changes of the documents, with the name of the organization to notify
+    events: broadcast::Sender<(String, DocumentEvent)>,
 }
 
 const ORGANIZATIONS_FOLDER_NAME: &str = "organizations";
 const PUBLIC_KEY_FILE_NAME: &str = "public_key";
 const ARGON_CONFIG_FILE_NAME: &str = "argon_config";
+const CLIENT_CERTIFICATE_AUTHORITIES_FILE_NAME: &str = "client_certificate_authorities";
 const USERS_FOLDER_NAME: &str = "users";
 const DOCUMENTS_KEYS_FOLDER_NAME: &str = "documents_keys";
 const DOCUMENTS_FOLDER_NAME: &str = "documents";
+const DOCUMENTS_INFO_FOLDER_NAME: &str = "documents_info";
+const SEARCH_INDEX_FILE_NAME: &str = "search_index";
+const SHARE_INVITATIONS_FOLDER_NAME: &str = "share_invitations";
+const GROUP_FILE_NAME: &str = "group";
+const SHARE_LINKS_FOLDER_NAME: &str = "share_links";
 
 const SESSION_TIMEOUT: u64 = 300;
+/// Number of events kept for the slowest subscriber, after which it misses events
+const EVENTS_CAPACITY: usize = 1024;
 
 impl LocalServer {
     pub fn new(data_path: &PathBuf) -> LocalServer {
-        LocalServer { data_path: data_path.clone(), sessions: SessionManager::new(SESSION_TIMEOUT) }
+        LocalServer {
+            data_path: data_path.clone(),
+            sessions: SessionManager::new(SESSION_TIMEOUT),
+            events: broadcast::channel(EVENTS_CAPACITY).0,
+        }
+    }
+
+    /// Returns the changes of the documents of all the organizations, with the name of the organization to notify.
+    /// Only the events that happen after the subscription are received.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<(String, DocumentEvent)> {
+        self.events.subscribe()
+    }
+
+    fn notify(&self, organization_name: &str, event: DocumentEvent) {
+        // Sending fails when nobody is subscribed, in which case nobody misses the event
+        let _ = self.events.send((organization_name.to_string(), event));
+    }
+
+    fn notify_owners(&self, document_id: &DocumentID, event: DocumentEvent) -> Result<(), VaultError> {
+        for owner in self.document_owners(document_id)? {
+            self.notify(&owner, event.clone());
+        }
+        Ok(())
     }
 
     fn organization_directory(&self, organization_name: &str) -> PathBuf {
@@ -50,6 +171,21 @@ impl LocalServer {
         self.organization_directory(organization_name).join(ARGON_CONFIG_FILE_NAME)
     }
 
+    /// The groups of an organization use the client certificate authorities of the organization
+    fn organization_client_certificate_authorities_path(&self, organization_name: &str) -> PathBuf {
+        let organization_name = organization_name.split(GROUP_SEPARATOR).next().unwrap_or(organization_name);
+        self.organization_directory(organization_name).join(CLIENT_CERTIFICATE_AUTHORITIES_FILE_NAME)
+    }
+
+    /// `group_owner_name` is the name returned by `group_owner_name`, under which the group owns documents
+    fn group_file_path(&self, group_owner_name: &str) -> PathBuf {
+        self.organization_directory(group_owner_name).join(GROUP_FILE_NAME)
+    }
+
+    fn organization_search_index_path(&self, organization_name: &str) -> PathBuf {
+        self.organization_directory(organization_name).join(SEARCH_INDEX_FILE_NAME)
+    }
+
     fn organization_users_directory(&self, organization_name: &str) -> PathBuf {
         self.organization_directory(organization_name).join(USERS_FOLDER_NAME)
     }
@@ -66,32 +202,167 @@ impl LocalServer {
         self.organization_document_keys_directory(organization_name).join(BASE32.encode(document_id))
     }
 
+    fn organization_share_invitations_directory(&self, organization_name: &str) -> PathBuf {
+        self.organization_directory(organization_name).join(SHARE_INVITATIONS_FOLDER_NAME)
+    }
+
+    fn share_invitation_path(&self, organization_name: &str, document_id: &DocumentID) -> PathBuf {
+        self.organization_share_invitations_directory(organization_name).join(BASE32.encode(document_id))
+    }
+
+    fn share_links_directory(&self) -> PathBuf {
+        self.data_path.as_path().join(SHARE_LINKS_FOLDER_NAME)
+    }
+
+    fn share_link_path(&self, link_id: &ShareLinkID) -> PathBuf {
+        self.share_links_directory().join(BASE32.encode(link_id))
+    }
+
     fn document_path(&self, document_id: &DocumentID) -> PathBuf {
         self.data_path.as_path().join(DOCUMENTS_FOLDER_NAME).join(BASE32.encode(document_id))
     }
 
+    fn document_info_path(&self, document_id: &DocumentID) -> PathBuf {
+        self.data_path.as_path().join(DOCUMENTS_INFO_FOLDER_NAME).join(BASE32.encode(document_id))
+    }
+
+    /// Loads the stored information about a document.
+    /// For documents stored before the information was tracked, it is rebuilt from the document and its file modification time.
+    fn load_document_info(&self, document_id: &DocumentID) -> Result<StoredDocumentInfo, VaultError> {
+        let info_path = self.document_info_path(document_id);
+        if info_path.exists() {
+            return load(&info_path);
+        }
+
+        let document_path = self.document_path(document_id);
+        let encrypted_document: EncryptedDocument = load(&document_path)?;
+        let modified_at = fs::metadata(&document_path).and_then(|metadata| metadata.modified())
+            .map_err(|_| ServerError)?
+            .duration_since(UNIX_EPOCH).map_err(|_| ServerError)?
+            .as_secs();
+        Ok(StoredDocumentInfo::new(&encrypted_document, modified_at, modified_at))
+    }
+
+
+    /// Loads the document key of an organization, `None` if the organization has no access to the document.
+    /// The keys stored before the permissions existed only contain the encrypted key, and grant the owner permission.
+    /// An expired key is deleted, so that the access ends even if the organization never uses it again.
+    fn load_document_key(&self, organization_name: &str, document_id: &DocumentID) -> Result<Option<StoredDocumentKey>, VaultError> {
+        let document_key_path = self.organization_document_key_path(organization_name, document_id);
+        if !document_key_path.exists() {
+            return Ok(None);
+        }
 
-    fn is_client_owner_of_document(&self, organization_name: &str, document_id: &DocumentID) -> Result<bool, VaultError> {
+        let document_key: StoredDocumentKey = load(&document_key_path).or_else(|_| Ok::<_, VaultError>(StoredDocumentKey {
+            encrypted_document_key: load(&document_key_path)?,
+            permission: Permission::Owner,
+            expires_at: None,
+        }))?;
+        if is_expired(document_key.expires_at) {
+            fs::remove_file(&document_key_path).map_err(|_| ServerError)?;
+            return Ok(None);
+        }
+        Ok(Some(document_key))
+    }
+
+    /// Checks that the organization holds a key for the document, with at least the `required` permission
+    fn has_permission(&self, organization_name: &str, document_id: &DocumentID, required: Permission) -> Result<bool, VaultError> {
         Ok(
-            // We check that there is a file whose name matches `document_id`
-            // in the folder where the file keys of the organization are stored.
-            fs::read_dir(self.organization_document_keys_directory(&organization_name))
-                .map_err(|_| ServerError)?
-                .filter_map(|dir_entry_result| dir_entry_result.ok())
-                .filter_map(|dir_entry| dir_entry.file_name().into_string().ok())
-                .any(|file_name| file_name == BASE32.encode(document_id))
+            self.load_document_key(organization_name, document_id)?
+                .is_some_and(|document_key| document_key.permission >= required)
         )
     }
+
+    /// Returns the names of the organizations that own a document, sorted
+    fn document_owners(&self, document_id: &DocumentID) -> Result<Vec<String>, VaultError> {
+        let mut owners = Vec::new();
+        for dir_entry in fs::read_dir(self.data_path.as_path().join(ORGANIZATIONS_FOLDER_NAME)).map_err(|_| ServerError)?.filter_map(|dir_entry_result| dir_entry_result.ok()) {
+            let organization_name = dir_entry.file_name().into_string().map_err(|_| ServerError)?;
+            if self.load_document_key(&organization_name, document_id)?.is_some() {
+                owners.push(organization_name);
+            }
+        }
+        owners.sort();
+        Ok(owners)
+    }
+
+    /// Deletes the pending invitations to a document, whose document key is no longer valid after a key rotation
+    fn delete_share_invitations_of_document(&self, document_id: &DocumentID) -> Result<(), VaultError> {
+        for dir_entry in fs::read_dir(self.data_path.as_path().join(ORGANIZATIONS_FOLDER_NAME)).map_err(|_| ServerError)?.filter_map(|dir_entry_result| dir_entry_result.ok()) {
+            let organization_name = dir_entry.file_name().into_string().map_err(|_| ServerError)?;
+            let invitation_path = self.share_invitation_path(&organization_name, document_id);
+            if invitation_path.exists() {
+                fs::remove_file(invitation_path).map_err(|_| ServerError)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the DER client certificate authorities registered by an organization.
+    /// Organizations that did not register any certificate authority do not use mutual TLS.
+    fn organization_client_certificate_authorities(&self, organization_name: &str) -> Result<Vec<Vec<u8>>, VaultError> {
+        let path = self.organization_client_certificate_authorities_path(organization_name);
+        if path.exists() {
+            load(&path)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Returns the DER client certificate authorities registered by all the organizations.
+    ///
+    /// The TLS layer uses them to ask the clients for a certificate during the handshake.
+    pub fn all_client_certificate_authorities(&self) -> Result<Vec<Vec<u8>>, VaultError> {
+        let organizations_directory = self.data_path.as_path().join(ORGANIZATIONS_FOLDER_NAME);
+        if !organizations_directory.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut client_certificate_authorities = Vec::new();
+        for dir_entry in fs::read_dir(organizations_directory).map_err(|_| ServerError)?.filter_map(|dir_entry_result| dir_entry_result.ok()) {
+            let organization_name = dir_entry.file_name().into_string().map_err(|_| ServerError)?;
+            if organization_name.contains(GROUP_SEPARATOR) {
+                continue;
+            }
+            client_certificate_authorities.append(&mut self.organization_client_certificate_authorities(&organization_name)?);
+        }
+        Ok(client_certificate_authorities)
+    }
+
+    /// Checks that `client_certificates`, the certificate chain presented by the client on its TLS connection,
+    /// was issued by one of the certificate authorities registered by the organization.
+    ///
+    /// If the organization did not register any certificate authority, any connection is accepted.
+    pub fn check_client_certificate(&self, organization_name: &str, client_certificates: &[Certificate]) -> Result<(), VaultError> {
+        let organization_name = validate_and_standardize_owner_name(organization_name)?;
+
+        let client_certificate_authorities = self.organization_client_certificate_authorities(&organization_name)?;
+        if client_certificate_authorities.is_empty() {
+            return Ok(());
+        }
+
+        let (end_entity, intermediates) = client_certificates.split_first().ok_or(ServerError)?;
+        let certificate_store = certificate_store_from_der_certificates(&client_certificate_authorities).map_err(|_| ServerError)?;
+        AllowAnyAuthenticatedClient::new(certificate_store)
+            .verify_client_cert(end_entity, intermediates, SystemTime::now())
+            .map(|_| ())
+            .map_err(|_| ServerError)
+    }
 }
 
-impl ServerConnection for LocalServer {
-    fn create_organization(&mut self,
-                           organization_name: &str,
-                           users_data: &HashMap<String, UserShare>,
-                           public_key: &dryocbox::PublicKey,
-                           argon2_config: &pwhash::Config,
+/// Request handlers.
+///
+/// The methods that take an `organization_name` but no token expect the organization of an authenticated session,
+/// as returned by `organization_name_from_token`.
+impl LocalServer {
+    pub fn create_organization(&mut self,
+                               organization_name: &str,
+                               users_data: &HashMap<String, UserShare>,
+                               public_key: &dryocbox::PublicKey,
+                               argon2_config: &pwhash::Config,
+                               client_certificate_authorities: &[Vec<u8>],
     )
-                           -> Result<(), VaultError>
+                               -> Result<(), VaultError>
     {
         let organization_name = validate_and_standardize_name(organization_name)?;
         if users_data.len() < 2 {
@@ -101,9 +372,13 @@ impl ServerConnection for LocalServer {
         for (user_name, user_share) in users_data {
             validated_users_data.insert(validate_and_standardize_name(user_name)?, user_share);
         }
+        certificate_store_from_der_certificates(client_certificate_authorities).map_err(|_| ValidationError)?;
 
         save(public_key, &self.organization_public_key_path(&organization_name), false)?;
         save(argon2_config, &self.organization_argon_config_path(&organization_name), false)?;
+        if !client_certificate_authorities.is_empty() {
+            save(client_certificate_authorities, &self.organization_client_certificate_authorities_path(&organization_name), false)?;
+        }
         for (user_name, user_share) in validated_users_data {
             save(user_share, &self.user_file_path(&organization_name, &user_name), false)?;
         }
@@ -111,8 +386,8 @@ impl ServerConnection for LocalServer {
         Ok(())
     }
 
-    fn unlock_vault(&mut self, organization_name: &str, user_name1: &str, user_name2: &str)
-                    -> Result<(UserShare, UserShare, pwhash::Config, dryocbox::PublicKey, EncryptedToken), VaultError> {
+    pub fn unlock_vault(&mut self, organization_name: &str, user_name1: &str, user_name2: &str)
+                        -> Result<(UserShare, UserShare, pwhash::Config, dryocbox::PublicKey, EncryptedToken), VaultError> {
         let organization_name = validate_and_standardize_name(organization_name)?;
         let user_name1 = validate_and_standardize_name(user_name1)?;
         let user_name2 = validate_and_standardize_name(user_name2)?;
@@ -123,16 +398,31 @@ impl ServerConnection for LocalServer {
         let user_share1: UserShare = load(&self.user_file_path(&organization_name, &user_name1))?;
         let user_share2: UserShare = load(&self.user_file_path(&organization_name, &user_name2))?;
 
-        let token = self.sessions.new_session(&organization_name);
+        let token = self.sessions.new_session(&organization_name, &[user_name1, user_name2]);
         let encrypted_token = DryocBox::seal_to_vecbox(&token, &public_key).map_err(|_| ServerError)?;
 
         Ok((user_share1, user_share2, argon_config, public_key, encrypted_token))
     }
 
-    fn revoke_user(&mut self, token: &Token, user_name: &str) -> Result<(), VaultError> {
+    /// Returns the organization of the session associated to `token`, if the session exists.
+    /// The organization of a group session is the name returned by `group_owner_name`.
+    pub fn organization_name_from_token(&mut self, token: &Token) -> Option<String> {
+        self.sessions.get_organization_name_from_token(token)
+    }
+
+    /// Returns the names of the users who unlocked the session associated to `token`, if the session exists
+    pub fn user_names_from_token(&mut self, token: &Token) -> Option<Vec<String>> {
+        self.sessions.get_user_names_from_token(token)
+    }
+
+    /// Records a request of the session associated to `token`, which postpones its expiry
+    pub fn record_session_activity(&mut self, token: &Token) {
+        self.sessions.record_activity(token);
+    }
+
+    pub fn revoke_user(&mut self, organization_name: &str, user_name: &str) -> Result<(), VaultError> {
         let user_name = validate_and_standardize_name(user_name)?;
 
-        let organization_name = self.sessions.get_organization_name_from_token(&token).ok_or(ServerError)?;
         if fs::read_dir(self.organization_users_directory(&organization_name)).map_err(|_| ServerError)?.count() == 2 {
             return Err(ServerError);
         }
@@ -140,35 +430,43 @@ impl ServerConnection for LocalServer {
         fs::remove_file(&self.user_file_path(&organization_name, &user_name)).map_err(|_| ServerError)
     }
 
-    fn revoke_token(&mut self, token: &Token) -> Result<(), VaultError> {
+    pub fn revoke_token(&mut self, token: &Token) -> Result<(), VaultError> {
         self.sessions.end_session(token);
         Ok(())
     }
 
-    fn new_document(&mut self, token: &Token, encrypted_document: &EncryptedDocument, encrypted_key: &EncryptedDocumentKey)
-                    -> Result<(), VaultError> {
-        let organization_name = self.sessions.get_organization_name_from_token(&token).ok_or(ServerError)?;
-        let document_id = &rng::randombytes_buf(DOCUMENT_ID_LENGTH_BYTES);
+    pub fn new_document(&mut self, organization_name: &str, encrypted_document: &EncryptedDocument, encrypted_key: &EncryptedDocumentKey)
+                        -> Result<DocumentID, VaultError> {
+        let document_id = rng::randombytes_buf(DOCUMENT_ID_LENGTH_BYTES);
 
-        save(encrypted_document, &self.document_path(&document_id), false)?;
-        save(encrypted_key, &self.organization_document_key_path(&organization_name, &document_id), false)?;
+        let creation_time = now();
+        let encrypted_document = EncryptedDocument { version: 0, ..encrypted_document.clone() };
+        save(&encrypted_document, &self.document_path(&document_id), false)?;
+        save(&StoredDocumentInfo::new(&encrypted_document, creation_time, creation_time), &self.document_info_path(&document_id), false)?;
+        let document_key = StoredDocumentKey { encrypted_document_key: encrypted_key.clone(), permission: Permission::Owner, expires_at: None };
+        save(&document_key, &self.organization_document_key_path(organization_name, &document_id), false)?;
 
-        Ok(())
+        self.notify(organization_name, DocumentEvent::Created { document_id: document_id.clone() });
+        Ok(document_id)
     }
 
-    fn list_documents(&mut self, token: &Token) -> Result<Vec<(DocumentID, EncryptedDocumentNameAndKey)>, VaultError> {
-        let organization_name = self.sessions.get_organization_name_from_token(&token).ok_or(ServerError)?;
-
-        let build_data = |dir_entry: DirEntry| -> Result<(DocumentID, EncryptedDocumentNameAndKey), VaultError> {
-            let document_id_os_str = dir_entry.file_name();
-            let document_id = BASE32.decode(document_id_os_str.to_str().ok_or(ServerError)?.as_bytes()).map_err(|_| ServerError)?;
-            let encrypted_document: EncryptedDocument = load(&self.document_path(&document_id))?;
-            let encrypted_key = load(&self.organization_document_key_path(&organization_name, &document_id))?;
+    /// Stores several new documents. Returns the result of each upload, in the order of `documents`.
+    pub fn new_documents(&mut self, organization_name: &str, documents: &[(EncryptedDocument, EncryptedDocumentKey)])
+                         -> Result<BatchResults<DocumentID>, VaultError> {
+        if documents.len() > MAX_BATCH_SIZE {
+            return Err(ValidationError);
+        }
 
-            Ok((document_id, EncryptedDocumentNameAndKey { data: encrypted_document.name, key: encrypted_key }))
-        };
+        Ok(
+            documents.iter()
+                .map(|(encrypted_document, encrypted_key)| self.new_document(organization_name, encrypted_document, encrypted_key))
+                .collect()
+        )
+    }
 
-        fs::read_dir(self.organization_document_keys_directory(&organization_name)).map_err(|_| ServerError)?
+    /// Returns the IDs and keys of the documents owned by an organization, whose access has not expired
+    fn organization_document_keys(&self, organization_name: &str) -> Result<Vec<(DocumentID, StoredDocumentKey)>, VaultError> {
+        let document_ids: Vec<DocumentID> = fs::read_dir(self.organization_document_keys_directory(organization_name)).map_err(|_| ServerError)?
             // filter out dir entries that are error
             .filter_map(|dir_entry_result_result| dir_entry_result_result.ok())
             // filter out dir entries that are not a file
@@ -179,72 +477,578 @@ impl ServerConnection for LocalServer {
                     false
                 }
             })
-            .map(build_data)
+            .map(|dir_entry: DirEntry| {
+                let document_id_os_str = dir_entry.file_name();
+                BASE32.decode(document_id_os_str.to_str().ok_or(ServerError)?.as_bytes()).map_err(|_| ServerError)
+            })
+            .collect::<Result<_, _>>()?;
+
+        let mut accessible_document_keys = Vec::with_capacity(document_ids.len());
+        for document_id in document_ids {
+            if let Some(document_key) = self.load_document_key(organization_name, &document_id)? {
+                accessible_document_keys.push((document_id, document_key));
+            }
+        }
+        Ok(accessible_document_keys)
+    }
+
+    /// Completes the stored information about a document with the document key of the organization
+    fn encrypted_document_info(document_id: DocumentID, document_key: StoredDocumentKey, document_info: StoredDocumentInfo)
+                               -> EncryptedDocumentInfo {
+        EncryptedDocumentInfo {
+            document_id,
+            name_and_key: EncryptedDocumentNameAndKey { data: document_info.name, key: document_key.encrypted_document_key },
+            metadata: document_info.metadata,
+            size: document_info.size,
+            created_at: document_info.created_at,
+            modified_at: document_info.modified_at,
+            version: document_info.version,
+        }
+    }
+
+    pub fn list_documents(&mut self, organization_name: &str) -> Result<Vec<EncryptedDocumentInfo>, VaultError> {
+        self.organization_document_keys(organization_name)?
+            .into_iter()
+            .map(|(document_id, document_key)| {
+                let document_info = self.load_document_info(&document_id)?;
+                Ok(Self::encrypted_document_info(document_id, document_key, document_info))
+            })
             .collect()
     }
 
-    fn get_document_key(&mut self, token: &Token, document_id: &DocumentID) -> Result<EncryptedDocumentKey, VaultError> {
-        let organization_name = self.sessions.get_organization_name_from_token(&token).ok_or(ServerError)?;
-        load(&self.organization_document_key_path(&organization_name, &document_id))
+    /// Returns a page of the documents owned by an organization, in the requested order.
+    ///
+    /// Documents with the same timestamp are ordered by ID, so that the cursor identifies a position in the listing
+    /// even if documents are added or modified between two requests.
+    ///
+    /// There is no index of the documents by timestamp, so each page reads the information files of all the documents of
+    /// the organization, as well as their keys: a page costs O(n) file reads for n documents. Only the documents of the page
+    /// are sorted.
+    pub fn list_documents_page(&mut self, organization_name: &str, page_request: &DocumentPageRequest)
+                               -> Result<EncryptedDocumentPage, VaultError> {
+        if page_request.page_size == 0 || page_request.page_size > MAX_PAGE_SIZE {
+            return Err(ValidationError);
+        }
+        let cursor = page_request.cursor.as_deref().map(parse_cursor).transpose()?;
+
+        let sort_key = |document_id: &DocumentID, document_info: &StoredDocumentInfo| match page_request.order {
+            DocumentOrder::CreatedAt => (document_info.created_at, document_id.clone()),
+            DocumentOrder::ModifiedAt => (document_info.modified_at, document_id.clone()),
+        };
+
+        let mut documents = Vec::new();
+        for (document_id, document_key) in self.organization_document_keys(organization_name)? {
+            let document_info = self.load_document_info(&document_id)?;
+            let key = sort_key(&document_id, &document_info);
+            let is_after_cursor = match &cursor {
+                None => true,
+                Some(cursor) if page_request.descending => key < *cursor,
+                Some(cursor) => key > *cursor,
+            };
+            if is_after_cursor {
+                documents.push((key, document_id, document_key, document_info));
+            }
+        }
+        type ListedDocument = ((u64, DocumentID), DocumentID, StoredDocumentKey, StoredDocumentInfo);
+        let page_order = |(key1, ..): &ListedDocument, (key2, ..): &ListedDocument|
+            if page_request.descending { key2.cmp(key1) } else { key1.cmp(key2) };
+        // The document after the page is kept, to know whether there is a next page
+        if documents.len() > page_request.page_size + 1 {
+            documents.select_nth_unstable_by(page_request.page_size, page_order);
+            documents.truncate(page_request.page_size + 1);
+        }
+        documents.sort_by(page_order);
+
+        let has_next_page = documents.len() > page_request.page_size;
+        documents.truncate(page_request.page_size);
+        let next_cursor = if has_next_page {
+            documents.last().map(|(key, ..)| format_cursor(key))
+        } else {
+            None
+        };
+
+        Ok(EncryptedDocumentPage {
+            documents: documents.into_iter()
+                .map(|(_, document_id, document_key, document_info)| Self::encrypted_document_info(document_id, document_key, document_info))
+                .collect(),
+            next_cursor,
+        })
     }
 
-    fn get_document(&mut self, token: &Token, document_id: &DocumentID) -> Result<EncryptedDocument, VaultError> {
-        let organization_name = self.sessions.get_organization_name_from_token(&token).ok_or(ServerError)?;
-        if self.is_client_owner_of_document(&organization_name, &document_id)? {
+    pub fn get_document_key(&mut self, organization_name: &str, document_id: &DocumentID) -> Result<EncryptedDocumentKey, VaultError> {
+        Ok(self.load_document_key(organization_name, document_id)?.ok_or(ServerError)?.encrypted_document_key)
+    }
+
+    pub fn get_document(&mut self, organization_name: &str, document_id: &DocumentID) -> Result<EncryptedDocument, VaultError> {
+        if self.has_permission(organization_name, document_id, Permission::Reader)? {
             load(&self.document_path(&document_id))
         } else {
             Err(ServerError)
         }
     }
 
-    fn update_document(&mut self, token: &Token, document_id: &DocumentID, encrypted_document: &EncryptedDocument)
-                       -> Result<(), VaultError> {
-        let organization_name = self.sessions.get_organization_name_from_token(&token).ok_or(ServerError)?;
-        if self.is_client_owner_of_document(&organization_name, &document_id)? {
-            save(encrypted_document, &self.document_path(&document_id), true)
+    /// Returns the result of each download, in the order of `document_ids`
+    pub fn get_documents(&mut self, organization_name: &str, document_ids: &[DocumentID])
+                         -> Result<BatchResults<(EncryptedDocument, EncryptedDocumentKey)>, VaultError> {
+        if document_ids.len() > MAX_BATCH_SIZE {
+            return Err(ValidationError);
+        }
+
+        Ok(
+            document_ids.iter()
+                .map(|document_id| Ok((
+                    self.get_document(organization_name, document_id)?,
+                    self.get_document_key(organization_name, document_id)?,
+                )))
+                .collect()
+        )
+    }
+
+    /// Replaces a document. Only the organizations with the editor or owner permission may update it.
+    /// If `expected_version` is set, fails with VersionConflict when the document is no longer at this version.
+    /// Returns the new version of the document.
+    pub fn update_document(&mut self, organization_name: &str, document_id: &DocumentID, encrypted_document: &EncryptedDocument,
+                           expected_version: Option<u64>)
+                           -> Result<u64, VaultError> {
+        if self.has_permission(organization_name, document_id, Permission::Editor)? {
+            let previous_info = self.load_document_info(document_id)?;
+            if expected_version.is_some_and(|expected_version| expected_version != previous_info.version) {
+                return Err(VersionConflict);
+            }
+
+            let mut encrypted_document = encrypted_document.clone();
+            if encrypted_document.metadata.is_none() {
+                encrypted_document.metadata = previous_info.metadata;
+            }
+            encrypted_document.version = previous_info.version + 1;
+
+            save(&encrypted_document, &self.document_path(document_id), true)?;
+            save(&StoredDocumentInfo::new(&encrypted_document, previous_info.created_at, now()), &self.document_info_path(document_id), true)?;
+            self.notify_owners(document_id, DocumentEvent::Updated { document_id: document_id.clone() })?;
+            Ok(encrypted_document.version)
         } else {
             Err(ServerError)
         }
     }
 
-    fn delete_document(&mut self, token: &Token, document_id: &DocumentID) -> Result<(), VaultError> {
-        let organization_name = self.sessions.get_organization_name_from_token(&token).ok_or(ServerError)?;
-        if self.is_client_owner_of_document(&organization_name, &document_id)? {
-            let organization_name = self.sessions.get_organization_name_from_token(&token).ok_or(ServerError)?;
-            fs::remove_file(&self.organization_document_key_path(&organization_name, &document_id)).map_err(|_| ServerError)
+    /// Removes the access of the organization to a document. Any permission allows an organization to give up its access.
+    pub fn delete_document(&mut self, organization_name: &str, document_id: &DocumentID) -> Result<(), VaultError> {
+        if self.has_permission(organization_name, document_id, Permission::Reader)? {
+            fs::remove_file(self.organization_document_key_path(organization_name, document_id)).map_err(|_| ServerError)?;
+            self.notify(organization_name, DocumentEvent::Deleted { document_id: document_id.clone() });
+            Ok(())
         } else {
             Err(ServerError)
         }
     }
 
-    fn get_public_key_of_organization(&mut self, organization_name: &str) -> Result<dryocbox::PublicKey, VaultError> {
-        let organization_name = validate_and_standardize_name(organization_name)?;
+    /// Returns the public key of an organization, or of a group designated by its `group_owner_name`
+    pub fn get_public_key_of_organization(&mut self, organization_name: &str) -> Result<dryocbox::PublicKey, VaultError> {
+        let organization_name = validate_and_standardize_owner_name(organization_name)?;
         load(&self.organization_public_key_path(&organization_name))
     }
 
-    fn add_owner(&mut self, token: &Token, document_id: &DocumentID, other_organization_name: &str, encrypted_document_key: &EncryptedDocumentKey)
-                 -> Result<(), VaultError> {
-        let other_organization_name = validate_and_standardize_name(other_organization_name)?;
+    /// Invites an other organization to access a document with `permission`, until `expires_at` if any.
+    /// Only the owners of the document may share it.
+    /// The invitation replaces the previous one of the other organization for this document, if any.
+    pub fn add_owner(&mut self, organization_name: &str, document_id: &DocumentID, other_organization_name: &str, encrypted_document_key: &EncryptedDocumentKey,
+                     permission: Permission, expires_at: Option<u64>)
+                     -> Result<(), VaultError> {
+        let other_organization_name = validate_and_standardize_owner_name(other_organization_name)?;
+        if is_expired(expires_at) {
+            return Err(ValidationError);
+        }
+
+        if !self.has_permission(organization_name, document_id, Permission::Owner)?
+            || !self.organization_public_key_path(&other_organization_name).exists()
+            || self.load_document_key(&other_organization_name, document_id)?.is_some() {
+            return Err(ServerError);
+        }
+
+        let invitation = StoredShareInvitation {
+            from_organization_name: organization_name.to_string(),
+            encrypted_document_key: encrypted_document_key.clone(),
+            permission,
+            expires_at,
+            created_at: now(),
+        };
+        save(&invitation, &self.share_invitation_path(&other_organization_name, document_id), true)?;
+
+        self.notify(&other_organization_name, DocumentEvent::Shared {
+            document_id: document_id.clone(),
+            from_organization_name: organization_name.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Returns the pending invitations of an organization, with the encrypted names of the documents.
+    /// The expired invitations are deleted.
+    pub fn list_share_invitations(&mut self, organization_name: &str) -> Result<Vec<EncryptedShareInvitation>, VaultError> {
+        let invitations_directory = self.organization_share_invitations_directory(organization_name);
+        if !invitations_directory.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut invitations = Vec::new();
+        for dir_entry in fs::read_dir(invitations_directory).map_err(|_| ServerError)?.filter_map(|dir_entry_result| dir_entry_result.ok()) {
+            let document_id = BASE32.decode(dir_entry.file_name().to_str().ok_or(ServerError)?.as_bytes()).map_err(|_| ServerError)?;
+            let invitation: StoredShareInvitation = load(&dir_entry.path())?;
+            if is_expired(invitation.expires_at) {
+                fs::remove_file(dir_entry.path()).map_err(|_| ServerError)?;
+                continue;
+            }
+            let document_info = self.load_document_info(&document_id)?;
+            invitations.push(EncryptedShareInvitation {
+                document_id,
+                from_organization_name: invitation.from_organization_name,
+                name_and_key: EncryptedDocumentNameAndKey { data: document_info.name, key: invitation.encrypted_document_key },
+                permission: invitation.permission,
+                expires_at: invitation.expires_at,
+                created_at: invitation.created_at,
+            });
+        }
+        Ok(invitations)
+    }
+
+    /// Gives the organization access to the document, with the document key, the permission and the expiry of the invitation
+    pub fn accept_share_invitation(&mut self, organization_name: &str, document_id: &DocumentID) -> Result<(), VaultError> {
+        let invitation_path = self.share_invitation_path(organization_name, document_id);
+        let invitation: StoredShareInvitation = load(&invitation_path)?;
+        if is_expired(invitation.expires_at) {
+            fs::remove_file(invitation_path).map_err(|_| ServerError)?;
+            return Err(ServerError);
+        }
+
+        let document_key = StoredDocumentKey {
+            encrypted_document_key: invitation.encrypted_document_key,
+            permission: invitation.permission,
+            expires_at: invitation.expires_at,
+        };
+        save(&document_key, &self.organization_document_key_path(organization_name, document_id), false)?;
+        fs::remove_file(invitation_path).map_err(|_| ServerError)
+    }
 
-        let organization_name = self.sessions.get_organization_name_from_token(&token).ok_or(ServerError)?;
-        if self.is_client_owner_of_document(&organization_name, &document_id)? {
-            save(&encrypted_document_key, &self.organization_document_key_path(&other_organization_name, &document_id), false)
+    pub fn decline_share_invitation(&mut self, organization_name: &str, document_id: &DocumentID) -> Result<(), VaultError> {
+        fs::remove_file(self.share_invitation_path(organization_name, document_id)).map_err(|_| ServerError)
+    }
+
+    /// Returns the organizations that have access to a document, with their permission and the expiry of their access.
+    /// Only the organizations that have access to the document may list them.
+    pub fn get_document_owners(&mut self, organization_name: &str, document_id: &DocumentID) -> Result<Vec<DocumentOwner>, VaultError> {
+        if self.has_permission(organization_name, document_id, Permission::Reader)? {
+            self.document_owners(document_id)?
+                .into_iter()
+                .map(|organization_name| {
+                    let document_key = self.load_document_key(&organization_name, document_id)?.ok_or(ServerError)?;
+                    Ok(DocumentOwner {
+                        organization_name,
+                        permission: document_key.permission,
+                        expires_at: document_key.expires_at.map(|expires_at| UNIX_EPOCH + Duration::from_secs(expires_at)),
+                    })
+                })
+                .collect()
+        } else {
+            Err(ServerError)
+        }
+    }
+
+    /// Removes the access of an other organization to a document. Only the owners of the document may remove an access.
+    /// Returns the remaining owners, including the organization of the session, which must then rotate the document key
+    /// with `rotate_document_key`.
+    pub fn remove_owner(&mut self, organization_name: &str, document_id: &DocumentID, other_organization_name: &str)
+                        -> Result<Vec<String>, VaultError> {
+        let other_organization_name = validate_and_standardize_owner_name(other_organization_name)?;
+        if other_organization_name == organization_name {
+            return Err(ValidationError);
+        }
+
+        if self.has_permission(organization_name, document_id, Permission::Owner)? {
+            // The removal succeeds if the access was already removed, so that a client can retry a failed key rotation
+            let invitation_path = self.share_invitation_path(&other_organization_name, document_id);
+            if invitation_path.exists() {
+                fs::remove_file(&invitation_path).map_err(|_| ServerError)?;
+            }
+            // An organization that only had a pending invitation never had access, so it is not notified
+            let key_path = self.organization_document_key_path(&other_organization_name, document_id);
+            if key_path.exists() {
+                fs::remove_file(&key_path).map_err(|_| ServerError)?;
+                self.notify(&other_organization_name, DocumentEvent::Deleted { document_id: document_id.clone() });
+            }
+            self.document_owners(document_id)
         } else {
             Err(ServerError)
         }
     }
+
+    /// Replaces a document encrypted with a new key, and the new key encrypted for each owner.
+    ///
+    /// `encrypted_keys` must contain exactly the current owners, so that the rotation fails if the owners changed
+    /// since the client read them. Each owner keeps its permission and expiry. The pending invitations to the document are deleted,
+    /// as their key is no longer valid.
+    pub fn rotate_document_key(&mut self, organization_name: &str, document_id: &DocumentID, encrypted_document: &EncryptedDocument,
+                               encrypted_keys: &HashMap<String, EncryptedDocumentKey>, expected_version: u64)
+                               -> Result<(), VaultError> {
+        if !self.has_permission(organization_name, document_id, Permission::Owner)? {
+            return Err(ServerError);
+        }
+        let mut new_owners = encrypted_keys.keys()
+            .map(|owner| validate_and_standardize_owner_name(owner))
+            .collect::<Result<Vec<_>, _>>()?;
+        new_owners.sort();
+        if new_owners != self.document_owners(document_id)? {
+            return Err(ValidationError);
+        }
+
+        let previous_info = self.load_document_info(document_id)?;
+        if previous_info.version != expected_version {
+            return Err(VersionConflict);
+        }
+
+        // Unlike an update, the metadata is not kept when it is missing, as it was encrypted with the previous key
+        let encrypted_document = EncryptedDocument { version: previous_info.version + 1, ..encrypted_document.clone() };
+        save(&encrypted_document, &self.document_path(document_id), true)?;
+        save(&StoredDocumentInfo::new(&encrypted_document, previous_info.created_at, now()), &self.document_info_path(document_id), true)?;
+        for (owner, encrypted_key) in encrypted_keys {
+            let owner = validate_and_standardize_owner_name(owner)?;
+            let document_key = StoredDocumentKey {
+                encrypted_document_key: encrypted_key.clone(),
+                ..self.load_document_key(&owner, document_id)?.ok_or(ServerError)?
+            };
+            save(&document_key, &self.organization_document_key_path(&owner, document_id), true)?;
+        }
+        self.delete_share_invitations_of_document(document_id)?;
+        self.notify_owners(document_id, DocumentEvent::Updated { document_id: document_id.clone() })
+    }
+
+    /// Returns the search index of the organization, `None` if it has not stored one yet
+    pub fn get_search_index(&mut self, organization_name: &str) -> Result<Option<EncryptedSearchIndex>, VaultError> {
+        let path = self.organization_search_index_path(organization_name);
+        if path.exists() {
+            load(&path).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn set_search_index(&mut self, organization_name: &str, search_index: &EncryptedSearchIndex) -> Result<(), VaultError> {
+        save(search_index, &self.organization_search_index_path(organization_name), true)
+    }
+}
+
+/// Groups.
+///
+/// A group owns documents like an organization, under the name returned by `group_owner_name`, and its members access
+/// them through a group session. The methods take the names of the users who unlocked the session, as returned by
+/// `user_names_from_token`: only the sessions unlocked by at least one member of a group may use the group.
+impl LocalServer {
+    /// Validates that the user names are users of the organization. Returns them sorted, without duplicates.
+    fn validate_organization_users(&self, organization_name: &str, user_names: &[String]) -> Result<Vec<String>, VaultError> {
+        let mut validated_user_names = user_names.iter()
+            .map(|user_name| validate_and_standardize_name(user_name))
+            .collect::<Result<Vec<_>, _>>()?;
+        validated_user_names.sort();
+        validated_user_names.dedup();
+        if validated_user_names.iter().any(|user_name| !self.user_file_path(organization_name, user_name).exists()) {
+            return Err(ValidationError);
+        }
+        Ok(validated_user_names)
+    }
+
+    /// Loads a group of the organization of the session, if one of the users who unlocked the session is a member.
+    /// Returns the name under which the group owns documents, and the group.
+    fn load_group_of_member(&self, organization_name: &str, session_user_names: &[String], group_name: &str)
+                            -> Result<(String, StoredGroup), VaultError> {
+        let group_owner_name = group_owner_name(organization_name, &validate_and_standardize_name(group_name)?);
+        let group: StoredGroup = load(&self.group_file_path(&group_owner_name))?;
+        if session_user_names.iter().any(|user_name| group.members.contains(user_name)) {
+            Ok((group_owner_name, group))
+        } else {
+            Err(ServerError)
+        }
+    }
+
+    /// Creates a group of users of the organization, with its key pair.
+    /// `member_keys` contains the private key encrypted for some of the members, by user name.
+    pub fn create_group(&mut self, organization_name: &str, group_name: &str, members: &[String], public_key: &dryocbox::PublicKey,
+                        encrypted_private_key: &EncryptedGroupPrivateKey, member_keys: &HashMap<String, SymEncryptedData>)
+                        -> Result<(), VaultError> {
+        // Groups can not be nested
+        if organization_name.contains(GROUP_SEPARATOR) {
+            return Err(ServerError);
+        }
+        let group_owner_name = group_owner_name(organization_name, &validate_and_standardize_name(group_name)?);
+        let members = self.validate_organization_users(organization_name, members)?;
+        if members.is_empty() {
+            return Err(ValidationError);
+        }
+        let mut validated_member_keys = HashMap::new();
+        for (user_name, member_key) in member_keys {
+            let user_name = validate_and_standardize_name(user_name)?;
+            if !members.contains(&user_name) {
+                return Err(ValidationError);
+            }
+            validated_member_keys.insert(user_name, member_key.clone());
+        }
+
+        save(public_key, &self.organization_public_key_path(&group_owner_name), false)?;
+        let group = StoredGroup { members, encrypted_private_key: encrypted_private_key.clone(), member_keys: validated_member_keys };
+        save(&group, &self.group_file_path(&group_owner_name), false)?;
+        fs::create_dir_all(self.organization_document_keys_directory(&group_owner_name)).map_err(|_| ServerError)
+    }
+
+    /// Returns the groups of the organization that have one of the users who unlocked the session as a member, sorted by name.
+    /// The private keys encrypted for the other members are not returned.
+    pub fn list_groups(&mut self, organization_name: &str, session_user_names: &[String]) -> Result<Vec<EncryptedGroup>, VaultError> {
+        let group_owner_name_prefix = group_owner_name(organization_name, "");
+
+        let mut groups = Vec::new();
+        for dir_entry in fs::read_dir(self.data_path.as_path().join(ORGANIZATIONS_FOLDER_NAME)).map_err(|_| ServerError)?.filter_map(|dir_entry_result| dir_entry_result.ok()) {
+            let directory_name = dir_entry.file_name().into_string().map_err(|_| ServerError)?;
+            let Some(group_name) = directory_name.strip_prefix(&group_owner_name_prefix) else {
+                continue;
+            };
+            let Ok((group_owner_name, group)) = self.load_group_of_member(organization_name, session_user_names, group_name) else {
+                continue;
+            };
+
+            groups.push(EncryptedGroup {
+                name: group_name.to_string(),
+                public_key: load(&self.organization_public_key_path(&group_owner_name))?,
+                encrypted_private_key: group.encrypted_private_key,
+                member_keys: group.member_keys.into_iter()
+                    .filter(|(user_name, _)| session_user_names.contains(user_name))
+                    .collect(),
+                members: group.members,
+            });
+        }
+        groups.sort_by(|group1, group2| group1.name.cmp(&group2.name));
+        Ok(groups)
+    }
+
+    /// Opens a session of the group, unlocked by the same users as the session of the organization.
+    /// Returns the token of the group session, encrypted with the group public key.
+    pub fn open_group_session(&mut self, organization_name: &str, session_user_names: &[String], group_name: &str)
+                              -> Result<EncryptedToken, VaultError> {
+        let (group_owner_name, _) = self.load_group_of_member(organization_name, session_user_names, group_name)?;
+        let public_key: dryocbox::PublicKey = load(&self.organization_public_key_path(&group_owner_name))?;
+
+        let token = self.sessions.new_session(&group_owner_name, session_user_names);
+        DryocBox::seal_to_vecbox(&token, &public_key).map_err(|_| ServerError)
+    }
+
+    pub fn add_group_member(&mut self, organization_name: &str, session_user_names: &[String], group_name: &str, user_name: &str)
+                            -> Result<(), VaultError> {
+        let (group_owner_name, mut group) = self.load_group_of_member(organization_name, session_user_names, group_name)?;
+        let user_name = self.validate_organization_users(organization_name, &[user_name.to_string()])?.remove(0);
+
+        if !group.members.contains(&user_name) {
+            group.members.push(user_name);
+            group.members.sort();
+        }
+        save(&group, &self.group_file_path(&group_owner_name), true)
+    }
+
+    /// Removes a member of a group, with its encrypted private key, and ends the group sessions that the member unlocked.
+    /// The last member of a group can not be removed.
+    pub fn remove_group_member(&mut self, organization_name: &str, session_user_names: &[String], group_name: &str, user_name: &str)
+                               -> Result<(), VaultError> {
+        let (group_owner_name, mut group) = self.load_group_of_member(organization_name, session_user_names, group_name)?;
+        let user_name = validate_and_standardize_name(user_name)?;
+
+        group.members.retain(|member| *member != user_name);
+        if group.members.is_empty() {
+            return Err(ValidationError);
+        }
+        group.member_keys.remove(&user_name);
+        save(&group, &self.group_file_path(&group_owner_name), true)?;
+        self.sessions.end_sessions_of_user(&group_owner_name, &user_name);
+        Ok(())
+    }
+
+    /// Stores the private key of a group encrypted for a member. The member must be one of the users who unlocked the session.
+    pub fn set_group_member_key(&mut self, organization_name: &str, session_user_names: &[String], group_name: &str, user_name: &str,
+                                encrypted_private_key: &SymEncryptedData)
+                                -> Result<(), VaultError> {
+        let (group_owner_name, mut group) = self.load_group_of_member(organization_name, session_user_names, group_name)?;
+        let user_name = validate_and_standardize_name(user_name)?;
+        if !session_user_names.contains(&user_name) || !group.members.contains(&user_name) {
+            return Err(ServerError);
+        }
+
+        group.member_keys.insert(user_name, encrypted_private_key.clone());
+        save(&group, &self.group_file_path(&group_owner_name), true)
+    }
+}
+
+/// Share links.
+///
+/// A share link gives access to a copy of a document to a recipient that has no organization on the server.
+/// Its ID is the only credential of the recipient, so anyone who knows it can download the copy, once.
+impl LocalServer {
+    /// Stores a copy of a document, encrypted by the client with a new key, until `expires_at` (in seconds since the Unix epoch).
+    /// The organization must be allowed to read the document. The expired links are deleted.
+    pub fn create_share_link(&mut self, organization_name: &str, document_id: &DocumentID, encrypted_document: &EncryptedDocument,
+                             expires_at: u64)
+                             -> Result<ShareLinkID, VaultError> {
+        if !self.has_permission(organization_name, document_id, Permission::Reader)? {
+            return Err(ServerError);
+        }
+        if is_expired(Some(expires_at)) {
+            return Err(ValidationError);
+        }
+        self.delete_expired_share_links()?;
+
+        let link_id = rng::randombytes_buf(SHARE_LINK_ID_LENGTH_BYTES);
+        let share_link = StoredShareLink { encrypted_document: encrypted_document.clone(), expires_at };
+        save(&share_link, &self.share_link_path(&link_id), false)?;
+        Ok(link_id)
+    }
+
+    /// Returns the copy of the document of a share link, and deletes the link.
+    /// Does not require a session: the recipient of the link has no organization.
+    pub fn open_share_link(&mut self, link_id: &ShareLinkID) -> Result<EncryptedDocument, VaultError> {
+        let link_path = self.share_link_path(link_id);
+        let share_link: StoredShareLink = load(&link_path)?;
+        fs::remove_file(&link_path).map_err(|_| ServerError)?;
+
+        if is_expired(Some(share_link.expires_at)) {
+            return Err(ServerError);
+        }
+        Ok(share_link.encrypted_document)
+    }
+
+    fn delete_expired_share_links(&self) -> Result<(), VaultError> {
+        let share_links_directory = self.share_links_directory();
+        if !share_links_directory.exists() {
+            return Ok(());
+        }
+
+        for dir_entry in fs::read_dir(share_links_directory).map_err(|_| ServerError)?.filter_map(|dir_entry_result| dir_entry_result.ok()) {
+            let share_link: StoredShareLink = load(&dir_entry.path())?;
+            if is_expired(Some(share_link.expires_at)) {
+                fs::remove_file(dir_entry.path()).map_err(|_| ServerError)?;
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
     use std::path::PathBuf;
+    use std::time::{Duration, UNIX_EPOCH};
     use dryoc::{dryocbox, pwhash};
+    use dryoc::dryocbox::DryocBox;
+    use rustls::Certificate;
     use uuid::Uuid;
-    use crate::data::{DocumentID, EncryptedDocument, random_encrypted_document_key, Token, UserShare};
+    use crate::data::{DocumentEvent, DocumentID, DocumentOrder, DocumentPageRequest, EncryptedDocument, Permission, random_encrypted_document_key, Token, UserShare};
     use crate::error::VaultError;
-    use crate::server::local_server::LocalServer;
-    use crate::server_connection::ServerConnection;
+    use crate::http_api::{MAX_BATCH_SIZE, MAX_PAGE_SIZE};
+    use crate::server::local_server::{LocalServer, now, StoredDocumentKey, StoredShareInvitation, StoredShareLink};
+    use crate::server::serde_json_disk::{load, save};
+    use crate::symmetric_encryption_helper::SymEncryptedData;
+    use crate::utils::get_certificates_der_from_pem;
 
     fn create_server() -> LocalServer {
         LocalServer::new(
@@ -252,17 +1056,20 @@ mod tests {
         )
     }
 
-    fn create_server_with_organizations_and_documents() -> (LocalServer, Vec<Token>, DocumentID) {
+    /// Returns the server, the names of the organizations as returned by their sessions, and the id of a document of the first organization
+    fn create_server_with_organizations_and_documents() -> (LocalServer, Vec<String>, DocumentID) {
         let mut server = create_server();
 
-        let mut tokens = Vec::new();
-        tokens.push(create_organization_and_unlock("ApertureScience", &mut server));
-        tokens.push(create_organization_and_unlock("BlackMesa", &mut server));
+        let mut organizations = Vec::new();
+        for name in ["ApertureScience", "BlackMesa"] {
+            let token = create_organization_and_unlock(name, &mut server);
+            organizations.push(server.organization_name_from_token(&token).unwrap());
+        }
 
-        server.new_document(&tokens[0], &EncryptedDocument::create_random(), &random_encrypted_document_key()).unwrap();
-        let document_id = server.list_documents(&tokens[0]).unwrap().iter().next().unwrap().0.clone();
+        server.new_document(&organizations[0], &EncryptedDocument::create_random(), &random_encrypted_document_key()).unwrap();
+        let document_id = server.list_documents(&organizations[0]).unwrap().first().unwrap().document_id.clone();
 
-        (server, tokens, document_id)
+        (server, organizations, document_id)
     }
 
     fn create_organization_and_unlock(name: &str, server: &mut LocalServer) -> Token {
@@ -275,6 +1082,12 @@ mod tests {
     }
 
     fn create_organization(name: &str, username1: &str, username2: &str, server: &mut LocalServer) -> Result<dryocbox::KeyPair, VaultError> {
+        create_organization_with_client_certificate_authorities(name, username1, username2, &[], server)
+    }
+
+    fn create_organization_with_client_certificate_authorities(name: &str, username1: &str, username2: &str,
+                                                               client_certificate_authorities: &[Vec<u8>], server: &mut LocalServer)
+                                                               -> Result<dryocbox::KeyPair, VaultError> {
         let key_pair = dryocbox::KeyPair::gen();
 
         let mut user_data = HashMap::new();
@@ -286,6 +1099,7 @@ mod tests {
             &user_data,
             &key_pair.public_key,
             &pwhash::Config::default(),
+            client_certificate_authorities,
         )?;
 
         Ok(key_pair)
@@ -293,23 +1107,262 @@ mod tests {
 
 
     #[test]
-    fn correct_token() {
-        let (mut server, tokens, document_id) = create_server_with_organizations_and_documents();
+    fn token() {
+        let mut server = create_server();
+        let token = create_organization_and_unlock("ApertureScience", &mut server);
+
+        assert_eq!(server.organization_name_from_token(&token).unwrap(), "aperturescience");
+
+        server.revoke_token(&token).unwrap();
+        assert!(server.organization_name_from_token(&token).is_none());
+    }
+
+    #[test]
+    fn owner_organization() {
+        let (mut server, organizations, document_id) = create_server_with_organizations_and_documents();
+
+        server.get_document(&organizations[0], &document_id).unwrap();
+        server.update_document(&organizations[0], &document_id, &EncryptedDocument::create_random(), None).unwrap();
+        server.add_owner(&organizations[0], &document_id, "BlackMesa", &random_encrypted_document_key(), Permission::Owner, None).unwrap();
+        server.delete_document(&organizations[0], &document_id).unwrap();
+    }
+
+    #[test]
+    fn document_versions() {
+        let (mut server, organizations, document_id) = create_server_with_organizations_and_documents();
+        assert_eq!(server.get_document(&organizations[0], &document_id).unwrap().version, 0);
+
+        assert_eq!(server.update_document(&organizations[0], &document_id, &EncryptedDocument::create_random(), Some(0)).unwrap(), 1);
+        assert!(matches!(
+            server.update_document(&organizations[0], &document_id, &EncryptedDocument::create_random(), Some(0)),
+            Err(VaultError::VersionConflict)
+        ));
+        assert_eq!(server.update_document(&organizations[0], &document_id, &EncryptedDocument::create_random(), None).unwrap(), 2);
+        assert_eq!(server.get_document(&organizations[0], &document_id).unwrap().version, 2);
+    }
+
+    #[test]
+    fn other_organization() {
+        let (mut server, organizations, document_id) = create_server_with_organizations_and_documents();
+
+        assert!(server.get_document(&organizations[1], &document_id).is_err());
+        assert!(server.update_document(&organizations[1], &document_id, &EncryptedDocument::create_random(), None).is_err());
+        assert!(server.add_owner(&organizations[1], &document_id, "BlackMesa", &random_encrypted_document_key(), Permission::Owner, None).is_err());
+        assert!(server.delete_document(&organizations[1], &document_id).is_err());
+    }
+
+    #[test]
+    fn permissions() {
+        let (mut server, organizations, document_id) = create_server_with_organizations_and_documents();
+        let lotr_token = create_organization_and_unlock("LotR", &mut server);
+        let lotr = server.organization_name_from_token(&lotr_token).unwrap();
+        server.add_owner(&organizations[0], &document_id, "BlackMesa", &random_encrypted_document_key(), Permission::Reader, None).unwrap();
+        server.accept_share_invitation(&organizations[1], &document_id).unwrap();
+
+        // A reader may read the document, but neither update nor share it
+        server.get_document(&organizations[1], &document_id).unwrap();
+        server.get_document_owners(&organizations[1], &document_id).unwrap();
+        assert!(server.update_document(&organizations[1], &document_id, &EncryptedDocument::create_random(), None).is_err());
+        assert!(server.add_owner(&organizations[1], &document_id, "LotR", &random_encrypted_document_key(), Permission::Reader, None).is_err());
+        assert!(server.remove_owner(&organizations[1], &document_id, "ApertureScience").is_err());
+
+        // An editor may also update the document
+        server.add_owner(&organizations[0], &document_id, "LotR", &random_encrypted_document_key(), Permission::Editor, None).unwrap();
+        server.accept_share_invitation(&lotr, &document_id).unwrap();
+        server.update_document(&lotr, &document_id, &EncryptedDocument::create_random(), None).unwrap();
+        assert!(server.add_owner(&lotr, &document_id, "BlackMesa", &random_encrypted_document_key(), Permission::Owner, None).is_err());
+
+        let permissions: Vec<Permission> = server.get_document_owners(&organizations[0], &document_id).unwrap()
+            .into_iter()
+            .map(|owner| owner.permission)
+            .collect();
+        assert_eq!(permissions, vec![Permission::Owner, Permission::Reader, Permission::Editor]);
+
+        // The permissions are kept when the document key is rotated
+        let encrypted_keys = [&organizations[0], &organizations[1], &lotr].into_iter()
+            .map(|owner| (owner.clone(), random_encrypted_document_key()))
+            .collect();
+        let version = server.get_document(&organizations[0], &document_id).unwrap().version;
+        server.rotate_document_key(&organizations[0], &document_id, &EncryptedDocument::create_random(), &encrypted_keys, version).unwrap();
+        assert!(server.update_document(&organizations[1], &document_id, &EncryptedDocument::create_random(), None).is_err());
+
+        // Any organization may give up its access
+        server.delete_document(&organizations[1], &document_id).unwrap();
+    }
+
+    #[test]
+    fn expired_share() {
+        let (mut server, organizations, document_id) = create_server_with_organizations_and_documents();
+        let past = now() - 1;
+        assert_eq!(
+            server.add_owner(&organizations[0], &document_id, "BlackMesa", &random_encrypted_document_key(), Permission::Reader, Some(past)),
+            Err(VaultError::ValidationError)
+        );
+
+        let expires_at = now() + 3600;
+        server.add_owner(&organizations[0], &document_id, "BlackMesa", &random_encrypted_document_key(), Permission::Reader, Some(expires_at)).unwrap();
+        assert_eq!(server.list_share_invitations(&organizations[1]).unwrap()[0].expires_at, Some(expires_at));
+        server.accept_share_invitation(&organizations[1], &document_id).unwrap();
+        let owners = server.get_document_owners(&organizations[0], &document_id).unwrap();
+        assert_eq!(owners[1].expires_at, Some(UNIX_EPOCH + Duration::from_secs(expires_at)));
+
+        // Once the access expires, the key is neither served nor listed, and it is deleted
+        let key_path = server.organization_document_key_path(&organizations[1], &document_id);
+        let mut document_key: StoredDocumentKey = load(&key_path).unwrap();
+        document_key.expires_at = Some(past);
+        save(&document_key, &key_path, true).unwrap();
+        assert!(server.get_document_key(&organizations[1], &document_id).is_err());
+        assert!(server.get_document(&organizations[1], &document_id).is_err());
+        assert!(server.list_documents(&organizations[1]).unwrap().is_empty());
+        assert!(!key_path.exists());
+        assert_eq!(server.get_document_owners(&organizations[0], &document_id).unwrap().len(), 1);
+
+        // An expired invitation can not be accepted
+        server.add_owner(&organizations[0], &document_id, "BlackMesa", &random_encrypted_document_key(), Permission::Reader, Some(expires_at)).unwrap();
+        let invitation_path = server.share_invitation_path(&organizations[1], &document_id);
+        let mut invitation: StoredShareInvitation = load(&invitation_path).unwrap();
+        invitation.expires_at = Some(past);
+        save(&invitation, &invitation_path, true).unwrap();
+        assert!(server.list_share_invitations(&organizations[1]).unwrap().is_empty());
+        assert!(server.accept_share_invitation(&organizations[1], &document_id).is_err());
+    }
+
+    #[test]
+    fn legacy_document_key() {
+        let (mut server, organizations, document_id) = create_server_with_organizations_and_documents();
+        let encrypted_key = random_encrypted_document_key();
+        save(&encrypted_key, &server.organization_document_key_path(&organizations[0], &document_id), true).unwrap();
 
-        server.get_document(&tokens[0], &document_id).unwrap();
-        server.update_document(&tokens[0], &document_id, &EncryptedDocument::create_random()).unwrap();
-        server.add_owner(&tokens[0], &document_id, "BlackMesa", &random_encrypted_document_key()).unwrap();
-        server.delete_document(&tokens[0], &document_id).unwrap();
+        assert_eq!(server.get_document_key(&organizations[0], &document_id).unwrap(), encrypted_key);
+        server.update_document(&organizations[0], &document_id, &EncryptedDocument::create_random(), None).unwrap();
     }
 
     #[test]
-    fn wrong_token() {
-        let (mut server, tokens, document_id) = create_server_with_organizations_and_documents();
+    fn document_owners() {
+        let (mut server, organizations, document_id) = create_server_with_organizations_and_documents();
+        assert!(server.get_document_owners(&organizations[1], &document_id).is_err());
+
+        server.add_owner(&organizations[0], &document_id, "BlackMesa", &random_encrypted_document_key(), Permission::Owner, None).unwrap();
+        server.accept_share_invitation(&organizations[1], &document_id).unwrap();
+        let owners: Vec<String> = server.get_document_owners(&organizations[1], &document_id).unwrap()
+            .into_iter()
+            .map(|owner| owner.organization_name)
+            .collect();
+        assert_eq!(owners, organizations);
+    }
 
-        assert!(server.get_document(&tokens[1], &document_id).is_err());
-        assert!(server.update_document(&tokens[1], &document_id, &EncryptedDocument::create_random()).is_err());
-        assert!(server.add_owner(&tokens[1], &document_id, "BlackMesa", &random_encrypted_document_key()).is_err());
-        assert!(server.delete_document(&tokens[1], &document_id).is_err());
+    #[test]
+    fn share_invitations() {
+        let (mut server, organizations, document_id) = create_server_with_organizations_and_documents();
+        assert!(server.add_owner(&organizations[0], &document_id, "XenTechnologies", &random_encrypted_document_key(), Permission::Owner, None).is_err());
+
+        let encrypted_key = random_encrypted_document_key();
+        server.add_owner(&organizations[0], &document_id, "BlackMesa", &encrypted_key, Permission::Owner, None).unwrap();
+        let invitations = server.list_share_invitations(&organizations[1]).unwrap();
+        assert_eq!(invitations.len(), 1);
+        assert_eq!(invitations[0].document_id, document_id);
+        assert_eq!(invitations[0].from_organization_name, organizations[0]);
+        assert_eq!(invitations[0].name_and_key.key, encrypted_key);
+
+        // The invited organization is not an owner until it accepts
+        assert!(server.get_document(&organizations[1], &document_id).is_err());
+        server.decline_share_invitation(&organizations[1], &document_id).unwrap();
+        assert!(server.list_share_invitations(&organizations[1]).unwrap().is_empty());
+        assert!(server.accept_share_invitation(&organizations[1], &document_id).is_err());
+
+        server.add_owner(&organizations[0], &document_id, "BlackMesa", &encrypted_key, Permission::Owner, None).unwrap();
+        server.accept_share_invitation(&organizations[1], &document_id).unwrap();
+        assert!(server.list_share_invitations(&organizations[1]).unwrap().is_empty());
+        server.get_document(&organizations[1], &document_id).unwrap();
+        assert!(server.add_owner(&organizations[0], &document_id, "BlackMesa", &encrypted_key, Permission::Owner, None).is_err());
+    }
+
+    #[test]
+    fn remove_owner() {
+        let (mut server, organizations, document_id) = create_server_with_organizations_and_documents();
+        server.add_owner(&organizations[0], &document_id, "BlackMesa", &random_encrypted_document_key(), Permission::Owner, None).unwrap();
+        server.accept_share_invitation(&organizations[1], &document_id).unwrap();
+
+        assert!(server.remove_owner(&organizations[1], &document_id, &organizations[1]).is_err());
+        let remaining_owners = server.remove_owner(&organizations[1], &document_id, "ApertureScience").unwrap();
+        assert_eq!(remaining_owners, vec![organizations[1].clone()]);
+        assert!(server.get_document(&organizations[0], &document_id).is_err());
+        assert_eq!(server.remove_owner(&organizations[1], &document_id, "ApertureScience").unwrap(), remaining_owners);
+
+        // The new keys must be encrypted for exactly the remaining owners
+        let new_document = EncryptedDocument::create_random();
+        let mut encrypted_keys = HashMap::new();
+        encrypted_keys.insert(organizations[0].clone(), random_encrypted_document_key());
+        assert_eq!(server.rotate_document_key(&organizations[1], &document_id, &new_document, &encrypted_keys, 0), Err(VaultError::ValidationError));
+        assert!(server.rotate_document_key(&organizations[0], &document_id, &new_document, &encrypted_keys, 0).is_err());
+
+        // The rotation fails if the document was updated since the client re-encrypted it
+        let new_key = random_encrypted_document_key();
+        let encrypted_keys = HashMap::from([(organizations[1].clone(), new_key.clone())]);
+        server.update_document(&organizations[1], &document_id, &EncryptedDocument::create_random(), None).unwrap();
+        assert_eq!(server.rotate_document_key(&organizations[1], &document_id, &new_document, &encrypted_keys, 0), Err(VaultError::VersionConflict));
+
+        server.rotate_document_key(&organizations[1], &document_id, &new_document, &encrypted_keys, 1).unwrap();
+        assert_eq!(server.get_document(&organizations[1], &document_id).unwrap(), EncryptedDocument { version: 2, ..new_document });
+        assert_eq!(server.get_document_key(&organizations[1], &document_id).unwrap(), new_key);
+    }
+
+    #[test]
+    fn batch_requests() {
+        let (mut server, organizations, document_id) = create_server_with_organizations_and_documents();
+
+        let documents = vec![(EncryptedDocument::create_random(), random_encrypted_document_key()); 3];
+        let new_document_ids: Vec<DocumentID> = server.new_documents(&organizations[0], &documents).unwrap()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(new_document_ids.len(), 3);
+
+        let results = server.get_documents(&organizations[1], &[document_id.clone(), new_document_ids[0].clone()]).unwrap();
+        assert!(results.iter().all(Result::is_err));
+        let results = server.get_documents(&organizations[0], &[document_id, new_document_ids[0].clone()]).unwrap();
+        assert_eq!(results[1].as_ref().unwrap(), &documents[0]);
+
+        let too_many_documents = vec![(EncryptedDocument::create_random(), random_encrypted_document_key()); MAX_BATCH_SIZE + 1];
+        assert_eq!(server.new_documents(&organizations[0], &too_many_documents), Err(VaultError::ValidationError));
+    }
+
+    #[test]
+    fn paginated_listing() {
+        let (mut server, organizations, ..) = create_server_with_organizations_and_documents();
+        for _ in 0..4 {
+            server.new_document(&organizations[0], &EncryptedDocument::create_random(), &random_encrypted_document_key()).unwrap();
+        }
+
+        for descending in [false, true] {
+            let mut page_request = DocumentPageRequest { order: DocumentOrder::ModifiedAt, descending, cursor: None, page_size: 2 };
+            let mut listed_document_ids = Vec::new();
+            loop {
+                let page = server.list_documents_page(&organizations[0], &page_request).unwrap();
+                assert!(page.documents.len() <= 2);
+                listed_document_ids.extend(page.documents.into_iter().map(|document_info| document_info.document_id));
+                match page.next_cursor {
+                    Some(cursor) => page_request.cursor = Some(cursor),
+                    None => break,
+                }
+            }
+
+            // Documents modified within the same second are ordered by ID
+            let mut documents = server.list_documents(&organizations[0]).unwrap();
+            documents.sort_by_key(|document_info| (document_info.modified_at, document_info.document_id.clone()));
+            let mut expected_document_ids: Vec<DocumentID> = documents.into_iter()
+                .map(|document_info| document_info.document_id)
+                .collect();
+            if descending {
+                expected_document_ids.reverse();
+            }
+            assert_eq!(listed_document_ids, expected_document_ids);
+        }
+
+        let too_large_page_request = DocumentPageRequest { order: DocumentOrder::CreatedAt, descending: false, cursor: None, page_size: MAX_PAGE_SIZE + 1 };
+        assert_eq!(server.list_documents_page(&organizations[0], &too_large_page_request), Err(VaultError::ValidationError));
+        let invalid_cursor_request = DocumentPageRequest { order: DocumentOrder::CreatedAt, descending: false, cursor: Some("cursor".to_string()), page_size: 2 };
+        assert_eq!(server.list_documents_page(&organizations[0], &invalid_cursor_request), Err(VaultError::ValidationError));
     }
 
     #[test]
@@ -344,10 +1397,10 @@ mod tests {
 
     #[test]
     fn names_validation_revoke_user() {
-        let (mut server, tokens, ..) = create_server_with_organizations_and_documents();
+        let (mut server, organizations, ..) = create_server_with_organizations_and_documents();
 
         assert!(matches!(
-            server.revoke_user(&tokens[0], "../../user1"),
+            server.revoke_user(&organizations[0], "../../user1"),
             Err(VaultError::ValidationError)
         ));
     }
@@ -361,4 +1414,207 @@ mod tests {
             Err(VaultError::ValidationError)
         ));
     }
-}
\ No newline at end of file
+
+    fn certificate_chain(pem: &[u8]) -> Vec<Certificate> {
+        get_certificates_der_from_pem(pem).unwrap().into_iter().map(Certificate).collect()
+    }
+
+    #[test]
+    fn client_certificate() {
+        let mut server = create_server();
+        let client_certificate_authorities = get_certificates_der_from_pem(include_bytes!("../../tests/certificates/client_ca.pem")).unwrap();
+        create_organization_with_client_certificate_authorities("BlackMesa", "user1", "user2", &client_certificate_authorities, &mut server).unwrap();
+        create_organization("ApertureScience", "user1", "user2", &mut server).unwrap();
+
+        assert_eq!(server.all_client_certificate_authorities().unwrap(), client_certificate_authorities);
+
+        assert!(server.check_client_certificate("BlackMesa", &[]).is_err());
+        assert!(server.check_client_certificate(
+            "BlackMesa",
+            &certificate_chain(include_bytes!("../../tests/certificates/other_client_certificate.pem"))).is_err()
+        );
+        server.check_client_certificate(
+            "BlackMesa",
+            &certificate_chain(include_bytes!("../../tests/certificates/client_certificate.pem"))).unwrap();
+
+        server.check_client_certificate("ApertureScience", &[]).unwrap();
+    }
+
+    /// Returns the server with the organization `aperturescience` of users chell, glados and wheatley,
+    /// and the key pair of its group `testing` of members chell and glados
+    fn create_server_with_group() -> (LocalServer, dryocbox::KeyPair) {
+        let mut server = create_server();
+        let user_data = HashMap::from(["Chell", "GLaDOS", "Wheatley"].map(|user_name| (user_name.to_string(), UserShare::create_random())));
+        server.create_organization("ApertureScience", &user_data, &dryocbox::KeyPair::gen().public_key, &pwhash::Config::default(), &[]).unwrap();
+
+        let group_key_pair = dryocbox::KeyPair::gen();
+        let encrypted_private_key = DryocBox::seal_to_vecbox(&group_key_pair.secret_key, &dryocbox::KeyPair::gen().public_key).unwrap();
+        server.create_group(
+            "aperturescience",
+            "Testing",
+            &["Chell".to_string(), "GLaDOS".to_string()],
+            &group_key_pair.public_key,
+            &encrypted_private_key,
+            &HashMap::from([("chell".to_string(), SymEncryptedData::create_random())]),
+        ).unwrap();
+
+        (server, group_key_pair)
+    }
+
+    fn user_names(user_names: &[&str]) -> Vec<String> {
+        user_names.iter().map(|user_name| user_name.to_string()).collect()
+    }
+
+    #[test]
+    fn create_group() {
+        let (mut server, group_key_pair) = create_server_with_group();
+        let members = user_names(&["chell"]);
+        let encrypted_private_key = DryocBox::seal_to_vecbox(&group_key_pair.secret_key, &group_key_pair.public_key).unwrap();
+
+        let mut create_group = |group_name: &str, members: &[String], member_keys: &[&str]| server.create_group(
+            "aperturescience",
+            group_name,
+            members,
+            &group_key_pair.public_key,
+            &encrypted_private_key,
+            &member_keys.iter().map(|user_name| (user_name.to_string(), SymEncryptedData::create_random())).collect(),
+        );
+        assert!(create_group("Testing", &members, &[]).is_err());
+        assert!(matches!(create_group("Cake", &[], &[]), Err(VaultError::ValidationError)));
+        assert!(matches!(create_group("Cake", &user_names(&["cave"]), &[]), Err(VaultError::ValidationError)));
+        assert!(matches!(create_group("Cake", &members, &["glados"]), Err(VaultError::ValidationError)));
+        create_group("Cake", &members, &["chell"]).unwrap();
+
+        // Groups can not be nested
+        assert!(server.create_group("aperturescience.testing", "Cake", &members, &group_key_pair.public_key, &encrypted_private_key, &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn list_groups() {
+        let (mut server, group_key_pair) = create_server_with_group();
+
+        let groups = server.list_groups("aperturescience", &user_names(&["chell", "wheatley"])).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].name, "testing");
+        assert_eq!(groups[0].members, user_names(&["chell", "glados"]));
+        assert_eq!(groups[0].public_key, group_key_pair.public_key);
+        assert!(groups[0].member_keys.contains_key("chell"));
+
+        // Only the keys of the users who unlocked the session are returned
+        let groups = server.list_groups("aperturescience", &user_names(&["glados", "wheatley"])).unwrap();
+        assert!(groups[0].member_keys.is_empty());
+
+        assert!(server.list_groups("aperturescience", &user_names(&["wheatley"])).unwrap().is_empty());
+    }
+
+    #[test]
+    fn group_session() {
+        let (mut server, group_key_pair) = create_server_with_group();
+        let session_user_names = user_names(&["glados", "wheatley"]);
+
+        assert!(server.open_group_session("aperturescience", &user_names(&["wheatley"]), "testing").is_err());
+        let encrypted_token = server.open_group_session("aperturescience", &session_user_names, "Testing").unwrap();
+        let token = encrypted_token.unseal_to_vec(&group_key_pair).unwrap();
+        let group = server.organization_name_from_token(&token).unwrap();
+        assert_eq!(group, "aperturescience.testing");
+        assert_eq!(server.user_names_from_token(&token).unwrap(), session_user_names);
+
+        // The documents of the group are not documents of the organization
+        server.new_document(&group, &EncryptedDocument::create_random(), &random_encrypted_document_key()).unwrap();
+        let document_id = server.list_documents(&group).unwrap()[0].document_id.clone();
+        assert!(server.list_documents("aperturescience").unwrap().is_empty());
+        assert!(server.get_document("aperturescience", &document_id).is_err());
+
+        // The organization can share its documents with the group
+        server.new_document("aperturescience", &EncryptedDocument::create_random(), &random_encrypted_document_key()).unwrap();
+        let document_id = server.list_documents("aperturescience").unwrap()[0].document_id.clone();
+        server.add_owner("aperturescience", &document_id, "ApertureScience.Testing", &random_encrypted_document_key(), Permission::Reader, None).unwrap();
+        server.accept_share_invitation(&group, &document_id).unwrap();
+        server.get_document(&group, &document_id).unwrap();
+        assert_eq!(server.get_public_key_of_organization("ApertureScience.Testing").unwrap(), group_key_pair.public_key);
+    }
+
+    #[test]
+    fn group_members() {
+        let (mut server, group_key_pair) = create_server_with_group();
+        let chell = user_names(&["chell"]);
+
+        assert!(server.add_group_member("aperturescience", &user_names(&["wheatley"]), "testing", "wheatley").is_err());
+        assert!(server.add_group_member("aperturescience", &chell, "testing", "cave").is_err());
+        server.add_group_member("aperturescience", &chell, "testing", "Wheatley").unwrap();
+        let encrypted_token = server.open_group_session("aperturescience", &user_names(&["wheatley"]), "testing").unwrap();
+        let wheatley_token = encrypted_token.unseal_to_vec(&group_key_pair).unwrap();
+
+        // Members may only store their own key
+        assert!(server.set_group_member_key("aperturescience", &chell, "testing", "wheatley", &SymEncryptedData::create_random()).is_err());
+        server.set_group_member_key("aperturescience", &user_names(&["wheatley"]), "testing", "wheatley", &SymEncryptedData::create_random()).unwrap();
+
+        server.remove_group_member("aperturescience", &chell, "testing", "glados").unwrap();
+        server.remove_group_member("aperturescience", &chell, "testing", "wheatley").unwrap();
+        // The group sessions of a removed member end
+        assert!(server.organization_name_from_token(&wheatley_token).is_none());
+        assert!(server.list_groups("aperturescience", &user_names(&["wheatley"])).unwrap().is_empty());
+        assert!(matches!(server.remove_group_member("aperturescience", &chell, "testing", "chell"), Err(VaultError::ValidationError)));
+    }
+
+    #[test]
+    fn share_link() {
+        let (mut server, organizations, document_id) = create_server_with_organizations_and_documents();
+        let encrypted_document = EncryptedDocument::create_random();
+        let expires_at = now() + 60;
+
+        assert!(server.create_share_link(&organizations[1], &document_id, &encrypted_document, expires_at).is_err());
+        assert!(matches!(server.create_share_link(&organizations[0], &document_id, &encrypted_document, now() - 1), Err(VaultError::ValidationError)));
+
+        let link_id = server.create_share_link(&organizations[0], &document_id, &encrypted_document, expires_at).unwrap();
+        assert_eq!(server.open_share_link(&link_id).unwrap(), encrypted_document);
+        assert!(server.open_share_link(&link_id).is_err());
+    }
+
+    #[test]
+    fn expired_share_link() {
+        let (mut server, organizations, document_id) = create_server_with_organizations_and_documents();
+        let link_id = server.create_share_link(&organizations[0], &document_id, &EncryptedDocument::create_random(), now() + 60).unwrap();
+        let link_path = server.share_link_path(&link_id);
+        let mut share_link: StoredShareLink = load(&link_path).unwrap();
+        share_link.expires_at = now() - 1;
+        save(&share_link, &link_path, true).unwrap();
+
+        // Expired links are deleted when an other link is created
+        server.create_share_link(&organizations[0], &document_id, &EncryptedDocument::create_random(), now() + 60).unwrap();
+        assert!(!link_path.exists());
+    }
+
+    #[test]
+    fn events() {
+        let (mut server, organizations, document_id) = create_server_with_organizations_and_documents();
+        let mut events = server.subscribe_events();
+
+        server.add_owner(&organizations[0], &document_id, "BlackMesa", &random_encrypted_document_key(), Permission::Editor, None).unwrap();
+        server.accept_share_invitation(&organizations[1], &document_id).unwrap();
+        server.update_document(&organizations[1], &document_id, &EncryptedDocument::create_random(), None).unwrap();
+        server.remove_owner(&organizations[0], &document_id, "BlackMesa").unwrap();
+
+        let document_id = || document_id.clone();
+        let expected_events = [
+            (&organizations[1], DocumentEvent::Shared { document_id: document_id(), from_organization_name: organizations[0].clone() }),
+            (&organizations[0], DocumentEvent::Updated { document_id: document_id() }),
+            (&organizations[1], DocumentEvent::Updated { document_id: document_id() }),
+            (&organizations[1], DocumentEvent::Deleted { document_id: document_id() }),
+        ];
+        for (organization_name, event) in expected_events {
+            assert_eq!(events.try_recv().unwrap(), (organization_name.clone(), event));
+        }
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn invalid_client_certificate_authority() {
+        let mut server = create_server();
+
+        assert!(matches!(
+            create_organization_with_client_certificate_authorities("BlackMesa", "user1", "user2", &[vec![1, 2, 3]], &mut server),
+            Err(VaultError::ValidationError)
+        ));
+    }
+}
@@ -1,5 +1,5 @@
 mod local_server;
-mod serde_json_disk;
+pub(crate) mod serde_json_disk;
 pub mod http_server;
 mod session_manager;
 pub mod server_config;
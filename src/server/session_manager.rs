@@ -4,8 +4,9 @@ use dryoc::rng;
 use crate::data::{Token, TOKEN_LENGTH_BYTES};
 
 /// Represents a pool of current client sessions.
-/// Each session is associated to a unique token and an organization name.
-/// If no activity from a session is detected for a certain amount of time, the session is removed
+/// Each session is associated to a unique token, an organization name and the names of the users who unlocked it.
+/// If no activity from a session is detected for a certain amount of time, the session is removed.
+/// Each request of a session is an activity, recorded with `record_activity`.
 pub struct SessionManager {
     sessions: HashMap<Token, Session>,
     timeout: u64,
@@ -14,6 +15,7 @@ pub struct SessionManager {
 /// Represents a client session.
 struct Session {
     organization_name: String,
+    user_names: Vec<String>,
     last_activity_time: Instant,
 }
 
@@ -22,7 +24,7 @@ impl SessionManager {
         Self { sessions: HashMap::new(), timeout }
     }
 
-    pub fn new_session(&mut self, organization_name: &str) -> Token {
+    pub fn new_session(&mut self, organization_name: &str, user_names: &[String]) -> Token {
         self.purge_sessions();
 
         let token = rng::randombytes_buf(TOKEN_LENGTH_BYTES);
@@ -30,6 +32,7 @@ impl SessionManager {
             token.clone(),
             Session {
                 organization_name: organization_name.to_string(),
+                user_names: user_names.to_vec(),
                 last_activity_time: Instant::now(),
             },
         );
@@ -42,10 +45,31 @@ impl SessionManager {
         Some(self.sessions.get(token)?.organization_name.clone())
     }
 
+    pub fn get_user_names_from_token(&mut self, token: &Token) -> Option<Vec<String>> {
+        self.purge_sessions();
+
+        Some(self.sessions.get(token)?.user_names.clone())
+    }
+
+    /// Restarts the timeout of the session associated to `token`, if it has not expired yet
+    pub fn record_activity(&mut self, token: &Token) {
+        self.purge_sessions();
+
+        if let Some(session) = self.sessions.get_mut(token) {
+            session.last_activity_time = Instant::now();
+        }
+    }
+
     pub fn end_session(&mut self, token: &Token){
         self.sessions.remove(token);
     }
 
+    /// Ends the sessions of an organization that were unlocked by `user_name`
+    pub fn end_sessions_of_user(&mut self, organization_name: &str, user_name: &str) {
+        self.sessions.retain(|_, session|
+            session.organization_name != organization_name || !session.user_names.iter().any(|session_user_name| session_user_name == user_name));
+    }
+
     fn purge_sessions(&mut self) {
         self.sessions.retain(|_, session|
             session.last_activity_time.elapsed().as_secs() < self.timeout);
@@ -64,38 +88,72 @@ mod tests {
     fn tokens() {
         let mut session_manager = SessionManager::new(60);
 
-        let token1 = session_manager.new_session("org1");
-        let token2 = session_manager.new_session("org2");
-        let token3 = session_manager.new_session("org3");
+        let token1 = session_manager.new_session("org1", &[]);
+        let token2 = session_manager.new_session("org2", &[]);
+        let token3 = session_manager.new_session("org3", &[]);
 
         assert_eq!(session_manager.get_organization_name_from_token(&token2).unwrap(), "org2");
         assert_eq!(session_manager.get_organization_name_from_token(&token1).unwrap(), "org1");
         assert_eq!(session_manager.get_organization_name_from_token(&token3).unwrap(), "org3");
     }
 
+    #[test]
+    fn user_names() {
+        let mut session_manager = SessionManager::new(60);
+
+        let token = session_manager.new_session("org", &["chell".to_string(), "wheatley".to_string()]);
+        assert_eq!(session_manager.get_user_names_from_token(&token).unwrap(), vec!["chell".to_string(), "wheatley".to_string()]);
+    }
+
     #[test]
     fn end_session() {
         let mut session_manager = SessionManager::new(60);
 
-        let token = session_manager.new_session("org");
+        let token = session_manager.new_session("org", &[]);
         session_manager.end_session(&token);
         assert!(session_manager.get_organization_name_from_token(&token).is_none());
     }
 
+    #[test]
+    fn end_sessions_of_user() {
+        let mut session_manager = SessionManager::new(60);
+
+        let chell_token = session_manager.new_session("org", &["chell".to_string(), "wheatley".to_string()]);
+        let other_token = session_manager.new_session("org", &["glados".to_string(), "wheatley".to_string()]);
+        let other_organization_token = session_manager.new_session("other", &["chell".to_string(), "cave".to_string()]);
+        session_manager.end_sessions_of_user("org", "chell");
+        assert!(session_manager.get_organization_name_from_token(&chell_token).is_none());
+        assert!(session_manager.get_organization_name_from_token(&other_token).is_some());
+        assert!(session_manager.get_organization_name_from_token(&other_organization_token).is_some());
+    }
+
     #[test]
     fn timeout() {
         let mut session_manager = SessionManager::new(1);
-        let token = session_manager.new_session("org1");
+        let token = session_manager.new_session("org1", &[]);
         sleep(Duration::from_secs(2));
         assert!(session_manager.get_organization_name_from_token(&token).is_none());
     }
 
+    #[test]
+    fn activity_restarts_timeout() {
+        let mut session_manager = SessionManager::new(2);
+        let token = session_manager.new_session("org1", &[]);
+        sleep(Duration::from_millis(1200));
+        session_manager.record_activity(&token);
+        sleep(Duration::from_millis(1200));
+        assert!(session_manager.get_organization_name_from_token(&token).is_some());
+        sleep(Duration::from_millis(2200));
+        session_manager.record_activity(&token);
+        assert!(session_manager.get_organization_name_from_token(&token).is_none());
+    }
+
     #[test]
     fn wrong_token() {
         let mut session_manager = SessionManager::new(60);
-        session_manager.new_session("org1");
-        session_manager.new_session("org2");
-        session_manager.new_session("org3");
+        session_manager.new_session("org1", &[]);
+        session_manager.new_session("org2", &[]);
+        session_manager.new_session("org3", &[]);
 
         assert!(
             session_manager.get_organization_name_from_token(
@@ -1,38 +1,437 @@
 //! API that the server provides to the client
+//!
+//! `AsyncServerConnection` is implemented by the connections to the server. `BlockingServerConnection` turns any of them
+//! into a `ServerConnection`, the blocking counterpart, by running its requests on its own runtime.
 
 use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
 use dryoc::{dryocbox, pwhash};
-use crate::data::{DocumentID, EncryptedDocumentKey, EncryptedDocumentNameAndKey, EncryptedToken, Token, UserShare, EncryptedDocument};
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc;
+
+use crate::data::{BatchResults, DocumentEvent, DocumentID, DocumentOwner, DocumentPageRequest, EncryptedDocumentPage, EncryptedDocumentInfo, EncryptedDocumentKey, EncryptedGroup, EncryptedGroupPrivateKey, EncryptedSearchIndex, EncryptedShareInvitation, EncryptedToken, Permission, ShareLinkID, Token, UserShare, EncryptedDocument};
 use crate::error::VaultError;
+use crate::symmetric_encryption_helper::SymEncryptedData;
 
+/// Changes of the documents of the organization of a session, in the order of the server.
+/// The stream ends, and `recv` returns `None`, when the session or the connection ends.
+pub type DocumentEventReceiver = mpsc::UnboundedReceiver<DocumentEvent>;
+
+/// Requests concerning an unlocked vault are authenticated with the token set by `set_token`.
 pub trait ServerConnection {
-    fn create_organization(&mut self, organization_name: &str, users_data: &HashMap<String, UserShare>, public_key: &dryocbox::PublicKey, argon2_config: &pwhash::Config)
+    /// `client_certificate_authorities` contains DER certificates. If it is not empty, the server only accepts
+    /// requests for the organization that come from TLS connections presenting a client certificate issued by one of them.
+    fn create_organization(&mut self, organization_name: &str, users_data: &HashMap<String, UserShare>, public_key: &dryocbox::PublicKey, argon2_config: &pwhash::Config,
+                           client_certificate_authorities: &[Vec<u8>])
                            -> Result<(), VaultError>;
 
     fn unlock_vault(&mut self, organization_name: &str, user_name1: &str, user_name2: &str)
                     -> Result<(UserShare, UserShare, pwhash::Config, dryocbox::PublicKey, EncryptedToken), VaultError>;
 
-    fn revoke_user(&mut self, token: &Token, user_name: &str) -> Result<(), VaultError>;
+    /// Sets the token that authenticates the following requests
+    fn set_token(&mut self, token: &Token);
+
+    fn revoke_user(&mut self, user_name: &str) -> Result<(), VaultError>;
     
-    fn revoke_token(&mut self, token: &Token) -> Result<(), VaultError>;
+    fn revoke_token(&mut self) -> Result<(), VaultError>;
     
-    fn new_document(&mut self, token: &Token, encrypted_document: &EncryptedDocument, encrypted_key: &EncryptedDocumentKey)
-                    -> Result<(), VaultError>;
+    fn new_document(&mut self, encrypted_document: &EncryptedDocument, encrypted_key: &EncryptedDocumentKey)
+                    -> Result<DocumentID, VaultError>;
+
+    fn list_documents(&mut self) -> Result<Vec<EncryptedDocumentInfo>, VaultError>;
 
-    fn list_documents(&mut self, token: &Token) -> Result<Vec<(DocumentID, EncryptedDocumentNameAndKey)>, VaultError>;
+    fn list_documents_page(&mut self, page_request: &DocumentPageRequest) -> Result<EncryptedDocumentPage, VaultError>;
 
-    fn get_document_key(&mut self, token: &Token, document_id: &DocumentID) -> Result<EncryptedDocumentKey, VaultError>;
+    fn get_document_key(&mut self, document_id: &DocumentID) -> Result<EncryptedDocumentKey, VaultError>;
 
-    fn get_document(&mut self, token: &Token, document_id: &DocumentID) -> Result<EncryptedDocument, VaultError>;
+    fn get_document(&mut self, document_id: &DocumentID) -> Result<EncryptedDocument, VaultError>;
 
-    fn update_document(&mut self, token: &Token, document_id: &DocumentID, encrypted_document: &EncryptedDocument)
-                       -> Result<(), VaultError>;
+    fn update_document(&mut self, document_id: &DocumentID, encrypted_document: &EncryptedDocument,
+                       expected_version: Option<u64>)
+                       -> Result<u64, VaultError>;
 
-    fn delete_document(&mut self, token: &Token, document_id: &DocumentID) -> Result<(), VaultError>;
+    fn delete_document(&mut self, document_id: &DocumentID) -> Result<(), VaultError>;
 
     fn get_public_key_of_organization(&mut self, organization_name: &str) -> Result<dryocbox::PublicKey, VaultError>;
 
-    fn add_owner(&mut self, token: &Token, document_id: &DocumentID, other_organization_name: &str, encrypted_document_key: &EncryptedDocumentKey)
-                 -> Result<(), VaultError>;
-    
-}
\ No newline at end of file
+    /// Invites an other organization to access a document with `permission`, until `expires_at` (in seconds since the Unix epoch) if any
+    fn add_owner(&mut self, document_id: &DocumentID, other_organization_name: &str, encrypted_document_key: &EncryptedDocumentKey,
+                 permission: Permission, expires_at: Option<u64>) -> Result<(), VaultError>;
+
+    /// Returns the pending invitations of the organization to become an owner of a document
+    fn list_share_invitations(&mut self) -> Result<Vec<EncryptedShareInvitation>, VaultError>;
+
+    /// Makes the organization an owner of the document it was invited to
+    fn accept_share_invitation(&mut self, document_id: &DocumentID) -> Result<(), VaultError>;
+
+    /// Deletes an invitation
+    fn decline_share_invitation(&mut self, document_id: &DocumentID) -> Result<(), VaultError>;
+
+    /// Returns the organizations that own a document
+    fn get_document_owners(&mut self, document_id: &DocumentID) -> Result<Vec<DocumentOwner>, VaultError>;
+
+    /// Removes the access of an other organization to a document. Returns the remaining owners, for which the
+    /// document key must then be rotated with `rotate_document_key`.
+    fn remove_owner(&mut self, document_id: &DocumentID, other_organization_name: &str) -> Result<Vec<String>, VaultError>;
+
+    /// Replaces a document with its version encrypted with a new key. `encrypted_keys` contains the new key encrypted
+    /// for each owner of the document. Fails with VersionConflict if the document is no longer at `expected_version`,
+    /// the version that was re-encrypted.
+    fn rotate_document_key(&mut self, document_id: &DocumentID, encrypted_document: &EncryptedDocument,
+                           encrypted_keys: &HashMap<String, EncryptedDocumentKey>, expected_version: u64)
+                           -> Result<(), VaultError>;
+
+    /// Uploads several documents in one request. Returns the result of each upload, in the order of `documents`.
+    fn new_documents(&mut self, documents: &[(EncryptedDocument, EncryptedDocumentKey)])
+                     -> Result<BatchResults<DocumentID>, VaultError>;
+
+    /// Downloads several documents and their keys in one request. Returns the result of each download, in the order of `document_ids`.
+    fn get_documents(&mut self, document_ids: &[DocumentID])
+                     -> Result<BatchResults<(EncryptedDocument, EncryptedDocumentKey)>, VaultError>;
+
+    /// Returns the encrypted search index of the organization, `None` if it has not been stored yet
+    fn get_search_index(&mut self) -> Result<Option<EncryptedSearchIndex>, VaultError>;
+
+    /// Replaces the encrypted search index of the organization
+    fn set_search_index(&mut self, search_index: &EncryptedSearchIndex) -> Result<(), VaultError>;
+
+    /// Creates a group of users of the organization, that owns documents like an organization.
+    /// `member_keys` contains the group private key encrypted for some of the members, by user name.
+    fn create_group(&mut self, group_name: &str, members: &[String], public_key: &dryocbox::PublicKey,
+                    encrypted_private_key: &EncryptedGroupPrivateKey, member_keys: &HashMap<String, SymEncryptedData>)
+                    -> Result<(), VaultError>;
+
+    /// Returns the groups that have one of the users who unlocked the session as a member
+    fn list_groups(&mut self) -> Result<Vec<EncryptedGroup>, VaultError>;
+
+    /// Opens a session of a group, unlocked by the same users. Returns its token, encrypted with the group public key.
+    fn open_group_session(&mut self, group_name: &str) -> Result<EncryptedToken, VaultError>;
+
+    fn add_group_member(&mut self, group_name: &str, user_name: &str) -> Result<(), VaultError>;
+
+    /// The last member of a group can not be removed
+    fn remove_group_member(&mut self, group_name: &str, user_name: &str) -> Result<(), VaultError>;
+
+    /// Stores the group private key encrypted for a member, who must be one of the users who unlocked the session
+    fn set_group_member_key(&mut self, group_name: &str, user_name: &str, encrypted_private_key: &SymEncryptedData)
+                           -> Result<(), VaultError>;
+
+    /// Stores a copy of a document, encrypted with the key of a share link, until `expires_at` (in seconds since the Unix epoch).
+    /// Returns the ID of the link.
+    fn create_share_link(&mut self, document_id: &DocumentID, encrypted_document: &EncryptedDocument, expires_at: u64)
+                         -> Result<ShareLinkID, VaultError>;
+
+    /// Downloads the copy of the document of a share link, which the server then deletes. Does not require a token.
+    fn open_share_link(&mut self, link_id: &ShareLinkID) -> Result<EncryptedDocument, VaultError>;
+
+    /// Subscribes to the changes of the documents of the organization that happen from now on
+    fn subscribe_events(&mut self) -> Result<DocumentEventReceiver, VaultError>;
+}
+
+/// Asynchronous counterpart of `ServerConnection`
+#[async_trait]
+pub trait AsyncServerConnection: Send + Sync {
+    /// `client_certificate_authorities` contains DER certificates. If it is not empty, the server only accepts
+    /// requests for the organization that come from TLS connections presenting a client certificate issued by one of them.
+    async fn create_organization(&mut self, organization_name: &str, users_data: &HashMap<String, UserShare>, public_key: &dryocbox::PublicKey, argon2_config: &pwhash::Config,
+                                 client_certificate_authorities: &[Vec<u8>])
+                                 -> Result<(), VaultError>;
+
+    async fn unlock_vault(&mut self, organization_name: &str, user_name1: &str, user_name2: &str)
+                          -> Result<(UserShare, UserShare, pwhash::Config, dryocbox::PublicKey, EncryptedToken), VaultError>;
+
+    /// Sets the token that authenticates the following requests
+    fn set_token(&mut self, token: &Token);
+
+    async fn revoke_user(&mut self, user_name: &str) -> Result<(), VaultError>;
+
+    async fn revoke_token(&mut self) -> Result<(), VaultError>;
+
+    async fn new_document(&mut self, encrypted_document: &EncryptedDocument, encrypted_key: &EncryptedDocumentKey)
+                          -> Result<DocumentID, VaultError>;
+
+    async fn list_documents(&mut self) -> Result<Vec<EncryptedDocumentInfo>, VaultError>;
+
+    async fn list_documents_page(&mut self, page_request: &DocumentPageRequest) -> Result<EncryptedDocumentPage, VaultError>;
+
+    async fn get_document_key(&mut self, document_id: &DocumentID) -> Result<EncryptedDocumentKey, VaultError>;
+
+    async fn get_document(&mut self, document_id: &DocumentID) -> Result<EncryptedDocument, VaultError>;
+
+    async fn update_document(&mut self, document_id: &DocumentID, encrypted_document: &EncryptedDocument,
+                             expected_version: Option<u64>)
+                             -> Result<u64, VaultError>;
+
+    async fn delete_document(&mut self, document_id: &DocumentID) -> Result<(), VaultError>;
+
+    async fn get_public_key_of_organization(&mut self, organization_name: &str) -> Result<dryocbox::PublicKey, VaultError>;
+
+    /// Invites an other organization to access a document with `permission`, until `expires_at` (in seconds since the Unix epoch) if any
+    async fn add_owner(&mut self, document_id: &DocumentID, other_organization_name: &str, encrypted_document_key: &EncryptedDocumentKey,
+                       permission: Permission, expires_at: Option<u64>) -> Result<(), VaultError>;
+
+    /// Returns the pending invitations of the organization to become an owner of a document
+    async fn list_share_invitations(&mut self) -> Result<Vec<EncryptedShareInvitation>, VaultError>;
+
+    /// Makes the organization an owner of the document it was invited to
+    async fn accept_share_invitation(&mut self, document_id: &DocumentID) -> Result<(), VaultError>;
+
+    /// Deletes an invitation
+    async fn decline_share_invitation(&mut self, document_id: &DocumentID) -> Result<(), VaultError>;
+
+    /// Returns the organizations that own a document
+    async fn get_document_owners(&mut self, document_id: &DocumentID) -> Result<Vec<DocumentOwner>, VaultError>;
+
+    /// Removes the access of an other organization to a document. Returns the remaining owners, for which the
+    /// document key must then be rotated with `rotate_document_key`.
+    async fn remove_owner(&mut self, document_id: &DocumentID, other_organization_name: &str) -> Result<Vec<String>, VaultError>;
+
+    /// Replaces a document with its version encrypted with a new key. `encrypted_keys` contains the new key encrypted
+    /// for each owner of the document. Fails with VersionConflict if the document is no longer at `expected_version`,
+    /// the version that was re-encrypted.
+    async fn rotate_document_key(&mut self, document_id: &DocumentID, encrypted_document: &EncryptedDocument,
+                                 encrypted_keys: &HashMap<String, EncryptedDocumentKey>, expected_version: u64)
+                                 -> Result<(), VaultError>;
+
+    /// Uploads several documents in one request. Returns the result of each upload, in the order of `documents`.
+    async fn new_documents(&mut self, documents: &[(EncryptedDocument, EncryptedDocumentKey)])
+                           -> Result<BatchResults<DocumentID>, VaultError>;
+
+    /// Downloads several documents and their keys in one request. Returns the result of each download, in the order of `document_ids`.
+    async fn get_documents(&mut self, document_ids: &[DocumentID])
+                           -> Result<BatchResults<(EncryptedDocument, EncryptedDocumentKey)>, VaultError>;
+
+    /// Returns the encrypted search index of the organization, `None` if it has not been stored yet
+    async fn get_search_index(&mut self) -> Result<Option<EncryptedSearchIndex>, VaultError>;
+
+    /// Replaces the encrypted search index of the organization
+    async fn set_search_index(&mut self, search_index: &EncryptedSearchIndex) -> Result<(), VaultError>;
+
+    /// Creates a group of users of the organization, that owns documents like an organization.
+    /// `member_keys` contains the group private key encrypted for some of the members, by user name.
+    async fn create_group(&mut self, group_name: &str, members: &[String], public_key: &dryocbox::PublicKey,
+                          encrypted_private_key: &EncryptedGroupPrivateKey, member_keys: &HashMap<String, SymEncryptedData>)
+                          -> Result<(), VaultError>;
+
+    /// Returns the groups that have one of the users who unlocked the session as a member
+    async fn list_groups(&mut self) -> Result<Vec<EncryptedGroup>, VaultError>;
+
+    /// Opens a session of a group, unlocked by the same users. Returns its token, encrypted with the group public key.
+    async fn open_group_session(&mut self, group_name: &str) -> Result<EncryptedToken, VaultError>;
+
+    async fn add_group_member(&mut self, group_name: &str, user_name: &str) -> Result<(), VaultError>;
+
+    /// The last member of a group can not be removed
+    async fn remove_group_member(&mut self, group_name: &str, user_name: &str) -> Result<(), VaultError>;
+
+    /// Stores the group private key encrypted for a member, who must be one of the users who unlocked the session
+    async fn set_group_member_key(&mut self, group_name: &str, user_name: &str, encrypted_private_key: &SymEncryptedData)
+                                 -> Result<(), VaultError>;
+
+    /// Stores a copy of a document, encrypted with the key of a share link, until `expires_at` (in seconds since the Unix epoch).
+    /// Returns the ID of the link.
+    async fn create_share_link(&mut self, document_id: &DocumentID, encrypted_document: &EncryptedDocument, expires_at: u64)
+                               -> Result<ShareLinkID, VaultError>;
+
+    /// Downloads the copy of the document of a share link, which the server then deletes. Does not require a token.
+    async fn open_share_link(&mut self, link_id: &ShareLinkID) -> Result<EncryptedDocument, VaultError>;
+
+    /// Subscribes to the changes of the documents of the organization that happen from now on
+    async fn subscribe_events(&mut self) -> Result<DocumentEventReceiver, VaultError>;
+}
+
+/// Blocking wrapper around an `AsyncServerConnection`.
+///
+/// The requests are run on a runtime owned by the wrapper, which is shared by its clones.
+/// The methods must not be called from an asynchronous context.
+#[derive(Clone)]
+pub struct BlockingServerConnection<A: AsyncServerConnection> {
+    connection: A,
+    runtime: Arc<Runtime>,
+}
+
+impl<A: AsyncServerConnection> BlockingServerConnection<A> {
+    /// Wraps a connection, creating the runtime used to run its requests
+    pub fn wrap(connection: A) -> Self {
+        Self::with_runtime(connection, Arc::new(Self::create_runtime()))
+    }
+
+    pub(crate) fn with_runtime(connection: A, runtime: Arc<Runtime>) -> Self {
+        BlockingServerConnection { connection, runtime }
+    }
+
+    pub(crate) fn create_runtime() -> Runtime {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .expect("Could not create the client runtime")
+    }
+
+    /// Returns the wrapped asynchronous connection
+    pub fn async_connection(&self) -> &A {
+        &self.connection
+    }
+
+    pub(crate) fn runtime(&self) -> &Arc<Runtime> {
+        &self.runtime
+    }
+}
+
+impl<A: AsyncServerConnection + fmt::Debug> fmt::Debug for BlockingServerConnection<A> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.debug_struct("BlockingServerConnection").field("connection", &self.connection).finish()
+    }
+}
+
+impl<A: AsyncServerConnection> ServerConnection for BlockingServerConnection<A> {
+    fn create_organization(&mut self, organization_name: &str, users_data: &HashMap<String, UserShare>, public_key: &dryocbox::PublicKey, argon2_config: &pwhash::Config,
+                           client_certificate_authorities: &[Vec<u8>])
+                           -> Result<(), VaultError> {
+        self.runtime.block_on(
+            self.connection.create_organization(organization_name, users_data, public_key, argon2_config, client_certificate_authorities)
+        )
+    }
+
+    fn unlock_vault(&mut self, organization_name: &str, user_name1: &str, user_name2: &str)
+                    -> Result<(UserShare, UserShare, pwhash::Config, dryocbox::PublicKey, EncryptedToken), VaultError> {
+        self.runtime.block_on(self.connection.unlock_vault(organization_name, user_name1, user_name2))
+    }
+
+    fn set_token(&mut self, token: &Token) {
+        self.connection.set_token(token)
+    }
+
+    fn revoke_user(&mut self, user_name: &str) -> Result<(), VaultError> {
+        self.runtime.block_on(self.connection.revoke_user(user_name))
+    }
+
+    fn revoke_token(&mut self) -> Result<(), VaultError> {
+        self.runtime.block_on(self.connection.revoke_token())
+    }
+
+    fn new_document(&mut self, encrypted_document: &EncryptedDocument, encrypted_key: &EncryptedDocumentKey) -> Result<DocumentID, VaultError> {
+        self.runtime.block_on(self.connection.new_document(encrypted_document, encrypted_key))
+    }
+
+    fn list_documents(&mut self) -> Result<Vec<EncryptedDocumentInfo>, VaultError> {
+        self.runtime.block_on(self.connection.list_documents())
+    }
+
+    fn list_documents_page(&mut self, page_request: &DocumentPageRequest) -> Result<EncryptedDocumentPage, VaultError> {
+        self.runtime.block_on(self.connection.list_documents_page(page_request))
+    }
+
+    fn get_document_key(&mut self, document_id: &DocumentID) -> Result<EncryptedDocumentKey, VaultError> {
+        self.runtime.block_on(self.connection.get_document_key(document_id))
+    }
+
+    fn get_document(&mut self, document_id: &DocumentID) -> Result<EncryptedDocument, VaultError> {
+        self.runtime.block_on(self.connection.get_document(document_id))
+    }
+
+    fn update_document(&mut self, document_id: &DocumentID, encrypted_document: &EncryptedDocument, expected_version: Option<u64>)
+                       -> Result<u64, VaultError> {
+        self.runtime.block_on(self.connection.update_document(document_id, encrypted_document, expected_version))
+    }
+
+    fn delete_document(&mut self, document_id: &DocumentID) -> Result<(), VaultError> {
+        self.runtime.block_on(self.connection.delete_document(document_id))
+    }
+
+    fn get_public_key_of_organization(&mut self, organization_name: &str) -> Result<dryocbox::PublicKey, VaultError> {
+        self.runtime.block_on(self.connection.get_public_key_of_organization(organization_name))
+    }
+
+    fn add_owner(&mut self, document_id: &DocumentID, other_organization_name: &str, encrypted_document_key: &EncryptedDocumentKey,
+                 permission: Permission, expires_at: Option<u64>) -> Result<(), VaultError> {
+        self.runtime.block_on(self.connection.add_owner(document_id, other_organization_name, encrypted_document_key, permission, expires_at))
+    }
+
+    fn list_share_invitations(&mut self) -> Result<Vec<EncryptedShareInvitation>, VaultError> {
+        self.runtime.block_on(self.connection.list_share_invitations())
+    }
+
+    fn accept_share_invitation(&mut self, document_id: &DocumentID) -> Result<(), VaultError> {
+        self.runtime.block_on(self.connection.accept_share_invitation(document_id))
+    }
+
+    fn decline_share_invitation(&mut self, document_id: &DocumentID) -> Result<(), VaultError> {
+        self.runtime.block_on(self.connection.decline_share_invitation(document_id))
+    }
+
+    fn get_document_owners(&mut self, document_id: &DocumentID) -> Result<Vec<DocumentOwner>, VaultError> {
+        self.runtime.block_on(self.connection.get_document_owners(document_id))
+    }
+
+    fn remove_owner(&mut self, document_id: &DocumentID, other_organization_name: &str) -> Result<Vec<String>, VaultError> {
+        self.runtime.block_on(self.connection.remove_owner(document_id, other_organization_name))
+    }
+
+    fn rotate_document_key(&mut self, document_id: &DocumentID, encrypted_document: &EncryptedDocument,
+                           encrypted_keys: &HashMap<String, EncryptedDocumentKey>, expected_version: u64)
+                           -> Result<(), VaultError> {
+        self.runtime.block_on(self.connection.rotate_document_key(document_id, encrypted_document, encrypted_keys, expected_version))
+    }
+
+    fn new_documents(&mut self, documents: &[(EncryptedDocument, EncryptedDocumentKey)])
+                     -> Result<BatchResults<DocumentID>, VaultError> {
+        self.runtime.block_on(self.connection.new_documents(documents))
+    }
+
+    fn get_documents(&mut self, document_ids: &[DocumentID])
+                     -> Result<BatchResults<(EncryptedDocument, EncryptedDocumentKey)>, VaultError> {
+        self.runtime.block_on(self.connection.get_documents(document_ids))
+    }
+
+    fn get_search_index(&mut self) -> Result<Option<EncryptedSearchIndex>, VaultError> {
+        self.runtime.block_on(self.connection.get_search_index())
+    }
+
+    fn set_search_index(&mut self, search_index: &EncryptedSearchIndex) -> Result<(), VaultError> {
+        self.runtime.block_on(self.connection.set_search_index(search_index))
+    }
+
+    fn create_group(&mut self, group_name: &str, members: &[String], public_key: &dryocbox::PublicKey,
+                    encrypted_private_key: &EncryptedGroupPrivateKey, member_keys: &HashMap<String, SymEncryptedData>)
+                    -> Result<(), VaultError> {
+        self.runtime.block_on(self.connection.create_group(group_name, members, public_key, encrypted_private_key, member_keys))
+    }
+
+    fn list_groups(&mut self) -> Result<Vec<EncryptedGroup>, VaultError> {
+        self.runtime.block_on(self.connection.list_groups())
+    }
+
+    fn open_group_session(&mut self, group_name: &str) -> Result<EncryptedToken, VaultError> {
+        self.runtime.block_on(self.connection.open_group_session(group_name))
+    }
+
+    fn add_group_member(&mut self, group_name: &str, user_name: &str) -> Result<(), VaultError> {
+        self.runtime.block_on(self.connection.add_group_member(group_name, user_name))
+    }
+
+    fn remove_group_member(&mut self, group_name: &str, user_name: &str) -> Result<(), VaultError> {
+        self.runtime.block_on(self.connection.remove_group_member(group_name, user_name))
+    }
+
+    fn set_group_member_key(&mut self, group_name: &str, user_name: &str, encrypted_private_key: &SymEncryptedData)
+                            -> Result<(), VaultError> {
+        self.runtime.block_on(self.connection.set_group_member_key(group_name, user_name, encrypted_private_key))
+    }
+
+    fn create_share_link(&mut self, document_id: &DocumentID, encrypted_document: &EncryptedDocument, expires_at: u64)
+                         -> Result<ShareLinkID, VaultError> {
+        self.runtime.block_on(self.connection.create_share_link(document_id, encrypted_document, expires_at))
+    }
+
+    fn open_share_link(&mut self, link_id: &ShareLinkID) -> Result<EncryptedDocument, VaultError> {
+        self.runtime.block_on(self.connection.open_share_link(link_id))
+    }
+
+    fn subscribe_events(&mut self) -> Result<DocumentEventReceiver, VaultError> {
+        self.runtime.block_on(self.connection.subscribe_events())
+    }
+}
@@ -26,6 +26,11 @@ impl SymEncryptedData {
         self.secret_box.decrypt_to_vec(&self.nonce, key).map_err(|_| CryptographyError)
     }
 
+    /// Size of the encrypted data, including the authentication tag
+    pub fn ciphertext_size(&self) -> u64 {
+        self.secret_box.to_vec().len() as u64
+    }
+
     /// Creates a mock SymEncryptedData.
     /// Useful for testing.
     pub fn create_random() -> Self {
@@ -1,18 +1,40 @@
 use std::{fs, io};
+use std::io::ErrorKind::InvalidData;
 use std::path::PathBuf;
 
+use rustls::{Certificate, RootCertStore};
+
 pub fn get_certificate_der_from_pem_file(pem_file_path: &PathBuf) -> Result<Vec<u8>, io::Error> {
-    let mut certificates = rustls_pemfile::certs(
+    let certificates = rustls_pemfile::certs(
         &mut fs::read(pem_file_path)?.as_slice()
     )?;
-    // remove and not get because we want to get an owned value, not a reference
-    Ok(certificates.remove(0))
+    // into_iter and not get because we want to get an owned value, not a reference
+    certificates.into_iter().next().ok_or(io::Error::new(InvalidData, "No certificate found in file"))
+}
+
+/// Returns all the certificates contained in PEM data, in DER format
+pub fn get_certificates_der_from_pem(pem: &[u8]) -> Result<Vec<Vec<u8>>, io::Error> {
+    let certificates = rustls_pemfile::certs(&mut &pem[..])?;
+    if certificates.is_empty() {
+        Err(io::Error::new(InvalidData, "No certificate found"))
+    } else {
+        Ok(certificates)
+    }
 }
 
 pub fn get_key_der_from_pem_file(pem_file_path: &PathBuf) -> Result<Vec<u8>, io::Error>  {
-    let mut keys = rustls_pemfile::pkcs8_private_keys(
+    let keys = rustls_pemfile::pkcs8_private_keys(
         &mut fs::read(pem_file_path)?.as_slice()
     )?;
-    // remove and not get because we want to get an owned value, not a reference
-    Ok(keys.remove(0))
+    // into_iter and not get because we want to get an owned value, not a reference
+    keys.into_iter().next().ok_or(io::Error::new(InvalidData, "No private key found in file"))
+}
+
+/// Builds a certificate store whose trust anchors are the given DER certificates
+pub fn certificate_store_from_der_certificates(der_certificates: &[Vec<u8>]) -> Result<RootCertStore, io::Error> {
+    let mut store = RootCertStore::empty();
+    for der_certificate in der_certificates {
+        store.add(&Certificate(der_certificate.clone())).map_err(|_| io::Error::new(InvalidData, "Invalid certificate"))?;
+    }
+    Ok(store)
 }
\ No newline at end of file
@@ -11,4 +11,22 @@ pub fn validate_and_standardize_name(name: &str) -> Result<String, VaultError> {
     } else{
         Ok(name.to_lowercase())
     }
-}
\ No newline at end of file
+}
+
+/// Separates the name of an organization from the name of one of its groups, e.g. `aperturescience.legal`
+pub const GROUP_SEPARATOR: char = '.';
+
+/// Returns the name under which a group of an organization owns documents
+pub fn group_owner_name(organization_name: &str, group_name: &str) -> String {
+    format!("{organization_name}{GROUP_SEPARATOR}{group_name}")
+}
+
+/// Validates the name of a document owner: an organization, or a group of an organization.
+/// Returns the standard lowercase version of the name.
+pub fn validate_and_standardize_owner_name(name: &str) -> Result<String, VaultError> {
+    match name.split_once(GROUP_SEPARATOR) {
+        Some((organization_name, group_name)) =>
+            Ok(group_owner_name(&validate_and_standardize_name(organization_name)?, &validate_and_standardize_name(group_name)?)),
+        None => validate_and_standardize_name(name),
+    }
+}